@@ -1,11 +1,21 @@
 use crate::_test_support::Result;
 use crate::hub::{HubEvent, get_hub};
 use std::sync::Arc;
-use tokio::sync::{Mutex, oneshot};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{Mutex, Notify, oneshot};
+
+/// The max wait of the sentinel in `into_content_all` (only reached if the capture is broken)
+const SENTINEL_MAX_WAIT: Duration = Duration::from_secs(10);
+
+static SENTINEL_COUNT: AtomicUsize = AtomicUsize::new(0);
 
 #[allow(unused)]
 pub struct HubCapture {
 	content: Arc<Mutex<String>>,
+	/// Notified on each captured event
+	captured: Arc<Notify>,
 	stop_signal: Option<oneshot::Sender<()>>,
 }
 
@@ -15,6 +25,8 @@ impl HubCapture {
 		let (stop_tx, stop_rx) = oneshot::channel();
 		let content = Arc::new(Mutex::new(String::new()));
 		let content_clone = Arc::clone(&content);
+		let captured = Arc::new(Notify::new());
+		let captured_clone = Arc::clone(&captured);
 		let mut rx = get_hub().subscriber();
 
 		// Spawn a background task to handle the events and stop signal
@@ -24,7 +36,13 @@ impl HubCapture {
 					// Stop signal received, exit the loop
 				}
 				_ = async {
-					while let Ok(event) = rx.recv().await {
+					loop {
+						let event = match rx.recv().await {
+							Ok(event) => event,
+							// Note: Some events were missed (hub channel full), continue with the next ones
+							Err(RecvError::Lagged(_)) => continue,
+							Err(RecvError::Closed) => break,
+						};
 						match event {
 							HubEvent::Message(msg) => {
 								let mut content = content_clone.lock().await;
@@ -40,10 +58,18 @@ impl HubCapture {
 								let mut content = content_clone.lock().await;
 								content.push_str(&format!("LuaPrint: {text}\n"));
 							}
+							HubEvent::AiStreamChunk(chunk) => {
+								let mut content = content_clone.lock().await;
+								content.push_str(&chunk);
+							}
 							HubEvent::Executor(exec_event)=> {
 								let mut content = content_clone.lock().await;
 								content.push_str(&format!("Exec: {exec_event} \n"));
 							},
+							HubEvent::Review(request) => {
+								let mut content = content_clone.lock().await;
+								content.push_str(&format!("Review: {}\n", request.display_path));
+							}
 							HubEvent::DoExecRedo => {
 								let mut content = content_clone.lock().await;
 								content.push_str("DoExecRedo\n");
@@ -54,6 +80,7 @@ impl HubCapture {
 							},
 
 						}
+						captured_clone.notify_waiters();
 					}
 				} => {
 					// The event receiver loop completes
@@ -63,10 +90,42 @@ impl HubCapture {
 
 		Self {
 			content,
+			captured,
 			stop_signal: Some(stop_tx),
 		}
 	}
 
+	/// Returns the content of all the events published before this call.
+	///
+	/// Publishes a sentinel message and waits until it is captured (the hub events are received in order),
+	/// so that the events published just before (e.g., by the end of a run) are not missed.
+	pub async fn into_content_all(self) -> Result<String> {
+		let sentinel = format!(
+			"-- hub capture sentinel {}",
+			SENTINEL_COUNT.fetch_add(1, Ordering::Relaxed)
+		);
+		get_hub().publish(sentinel.clone()).await;
+
+		let wait_sentinel = async {
+			loop {
+				// Note: Created before the check, so that a capture in between is not missed
+				let notified = self.captured.notified();
+				if self.content.lock().await.contains(&sentinel) {
+					break;
+				}
+				notified.await;
+			}
+		};
+		tokio::time::timeout(SENTINEL_MAX_WAIT, wait_sentinel)
+			.await
+			.map_err(|_| format!("Hub capture sentinel not received after {SENTINEL_MAX_WAIT:?}"))?;
+
+		let content = self.into_content().await?;
+		let content = content.replace(&format!("{sentinel}\n"), "");
+
+		Ok(content)
+	}
+
 	pub async fn into_content(mut self) -> Result<String> {
 		// Send stop signal to stop the background polling
 		if let Some(stop_tx) = self.stop_signal.take() {
@@ -0,0 +1,150 @@
+use crate::_test_support::Result;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+/// A mock OpenAI-compatible chat completions server, answering the requests in order with the given responses
+/// (the last response is repeated when there are more requests).
+///
+/// Used with the `mock::<model>` models of the `Runtime::new_test_runtime_sandbox_01_with_mock(..)`.
+pub struct MockLlmServer {
+	base_url: String,
+	requests: Arc<Mutex<Vec<String>>>,
+}
+
+/// A response of the `MockLlmServer`
+#[derive(Debug, Clone)]
+pub enum MockLlmResponse {
+	/// The response content, streamed as one chunk per word (with the `stream: true` requests)
+	Content(String),
+	/// Streams the content, and then closes the connection before the end of the response (a stream error)
+	StreamCut(String),
+}
+
+/// Constructors
+impl MockLlmServer {
+	/// The response contents (see `MockLlmResponse::Content`)
+	pub async fn start(contents: Vec<&str>) -> Result<Self> {
+		let responses = contents
+			.into_iter()
+			.map(|content| MockLlmResponse::Content(content.to_string()))
+			.collect();
+		Self::start_with_responses(responses).await
+	}
+
+	pub async fn start_with_responses(responses: Vec<MockLlmResponse>) -> Result<Self> {
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let base_url = format!("http://{}/v1", listener.local_addr()?);
+		let requests: Arc<Mutex<Vec<String>>> = Arc::default();
+
+		let requests_clone = Arc::clone(&requests);
+		tokio::spawn(async move {
+			let mut idx: usize = 0;
+			while let Ok((mut socket, _)) = listener.accept().await {
+				let Ok(request) = read_http_request(&mut socket).await else {
+					continue;
+				};
+				let Some(mock_response) = responses.get(idx).or(responses.last()).cloned() else {
+					continue;
+				};
+				idx += 1;
+				let is_stream = request.contains(r#""stream":true"#);
+				let response = match mock_response {
+					MockLlmResponse::Content(content) if is_stream => stream_response(&content, false),
+					MockLlmResponse::Content(content) => json_response(&content),
+					MockLlmResponse::StreamCut(content) => stream_response(&content, true),
+				};
+				requests_clone.lock().await.push(request);
+				let _ = socket.write_all(response.as_bytes()).await;
+			}
+		});
+
+		Ok(Self { base_url, requests })
+	}
+}
+
+/// Getters
+impl MockLlmServer {
+	pub fn base_url(&self) -> &str {
+		&self.base_url
+	}
+
+	/// The raw http requests received so far
+	pub async fn requests(&self) -> Vec<String> {
+		self.requests.lock().await.clone()
+	}
+}
+
+// region:    --- Support
+
+fn json_response(content: &str) -> String {
+	let body = json!({
+		"id": "chatcmpl-mock",
+		"object": "chat.completion",
+		"model": "mock-model",
+		"choices": [{"index": 0, "message": {"role": "assistant", "content": content}, "finish_reason": "stop"}],
+		"usage": {"prompt_tokens": 3, "completion_tokens": 4, "total_tokens": 7}
+	})
+	.to_string();
+	format!(
+		"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+		body.len()
+	)
+}
+
+/// The server-sent events response, one chunk per word (with its trailing space)
+/// When `cut`, the response has no end, and its content-length is more than the sent body.
+fn stream_response(content: &str, cut: bool) -> String {
+	let mut body = String::new();
+	for word in content.split_inclusive(' ') {
+		let chunk = json!({
+			"id": "chatcmpl-mock",
+			"object": "chat.completion.chunk",
+			"model": "mock-model",
+			"choices": [{"index": 0, "delta": {"content": word}, "finish_reason": null}]
+		});
+		body.push_str(&format!("data: {chunk}\n\n"));
+	}
+	let content_length = if cut {
+		body.len() + 1000
+	} else {
+		body.push_str("data: [DONE]\n\n");
+		body.len()
+	};
+	format!(
+		"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ncontent-length: {content_length}\r\nconnection: close\r\n\r\n{body}"
+	)
+}
+
+/// Read the http request (headers and content-length body) as string
+pub async fn read_http_request(socket: &mut TcpStream) -> std::io::Result<String> {
+	let mut buf: Vec<u8> = Vec::new();
+	let mut chunk = [0u8; 4096];
+	loop {
+		let n = socket.read(&mut chunk).await?;
+		if n == 0 {
+			break;
+		}
+		buf.extend_from_slice(&chunk[..n]);
+		let content = String::from_utf8_lossy(&buf);
+		if let Some(header_end) = content.find("\r\n\r\n") {
+			let content_length = content[..header_end]
+				.lines()
+				.find_map(|line| {
+					let (name, value) = line.split_once(':')?;
+					name.eq_ignore_ascii_case("content-length")
+						.then(|| value.trim().parse::<usize>().ok())
+						.flatten()
+				})
+				.unwrap_or(0);
+			if buf.len() >= header_end + 4 + content_length {
+				break;
+			}
+		}
+	}
+	Ok(String::from_utf8_lossy(&buf).to_string())
+}
+
+// endregion: --- Support
@@ -5,6 +5,7 @@ mod base;
 mod hub_capture;
 mod loaders;
 mod lua_test_support;
+mod mock_llm;
 mod runners;
 
 pub use asserts::*;
@@ -13,6 +14,7 @@ pub use base::*;
 pub use hub_capture::*;
 pub use loaders::*;
 pub use lua_test_support::*;
+pub use mock_llm::*;
 pub use runners::*;
 
 pub type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>;
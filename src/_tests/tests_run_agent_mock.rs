@@ -0,0 +1,133 @@
+//! The agents run against the `MockLlmServer` (OpenAI-compatible chat completions on localhost)
+
+type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+use super::*;
+use crate::_test_support::{
+	HubCapture, MockLlmResponse, MockLlmServer, assert_contains, load_inline_agent, run_test_agent_with_input,
+};
+use serde_json::json;
+
+#[tokio::test]
+async fn test_run_agent_mock_follow_up_max_turns_last_response() -> Result<()> {
+	// -- Setup & Fixtures
+	let server = MockLlmServer::start(vec!["first answer", "second answer"]).await?;
+	let runtime = Runtime::new_test_runtime_sandbox_01_with_mock(server.base_url())?;
+	let fx_agent = r#"
+# Options
+
+```toml
+model = "mock::my-model"
+max_turns = 2
+```
+
+# Instruction
+
+Some instruction
+
+# Output
+
+```lua
+return aipack.follow_up("Again")
+```
+	"#;
+	let agent = load_inline_agent("./dummy/path.aip", fx_agent)?;
+	let hub_capture = HubCapture::new_and_start();
+
+	// -- Exec
+	let res = run_test_agent_with_input(&runtime, &agent, json!({"name": "fx-max-turns-input"})).await?;
+
+	// -- Check
+	assert_eq!(res.as_str(), Some("second answer"));
+	assert_eq!(server.requests().await.len(), 2);
+	let hub_content = hub_capture.into_content_all().await?;
+	assert_contains(&hub_content, "max turns reached (2) for input: fx-max-turns-input");
+
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_run_agent_mock_stream_concurrent_inputs_not_mixed() -> Result<()> {
+	// -- Setup & Fixtures
+	let server = MockLlmServer::start(vec!["fx-concurrent-stream alpha beta gamma delta"]).await?;
+	let runtime = Runtime::new_test_runtime_sandbox_01_with_mock(server.base_url())?;
+	let fx_agent = r#"
+# Options
+
+```toml
+model = "mock::my-model"
+stream = true
+input_concurrency = 2
+```
+
+# Instruction
+
+Some instruction
+	"#;
+	let agent = load_inline_agent("./dummy/path.aip", fx_agent)?;
+	let inputs = vec![json!({"name": "fx-stream-input-a"}), json!({"name": "fx-stream-input-b"})];
+	let hub_capture = HubCapture::new_and_start();
+
+	// -- Exec
+	let res = run_command_agent(&runtime, agent, Some(inputs), &RunBaseOptions::default(), true).await?;
+
+	// -- Check
+	let outputs = res.outputs.ok_or("Should have outputs")?;
+	assert_eq!(outputs.len(), 2);
+	let hub_content = hub_capture.into_content_all().await?;
+	// Note: Each input stream is published at once, with its input label
+	assert_contains(
+		&hub_content,
+		"-- AI stream for input: fx-stream-input-a\nfx-concurrent-stream alpha beta gamma delta\n",
+	);
+	assert_contains(
+		&hub_content,
+		"-- AI stream for input: fx-stream-input-b\nfx-concurrent-stream alpha beta gamma delta\n",
+	);
+
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_run_agent_mock_stream_retry_not_republished() -> Result<()> {
+	// -- Setup & Fixtures
+	let server = MockLlmServer::start_with_responses(vec![
+		MockLlmResponse::StreamCut("fx-retry-stream partial ".to_string()),
+		MockLlmResponse::Content("fx-retry-stream full answer".to_string()),
+	])
+	.await?;
+	let runtime = Runtime::new_test_runtime_sandbox_01_with_mock(server.base_url())?;
+	let fx_agent = r#"
+# Options
+
+```toml
+model = "mock::my-model"
+stream = true
+retry_max = 1
+retry_backoff_ms = 1
+```
+
+# Instruction
+
+Some instruction
+	"#;
+	let agent = load_inline_agent("./dummy/path.aip", fx_agent)?;
+	let hub_capture = HubCapture::new_and_start();
+
+	// -- Exec
+	let res = run_test_agent_with_input(&runtime, &agent, json!({"name": "fx-retry-stream-input"})).await?;
+
+	// -- Check
+	assert_eq!(res.as_str(), Some("fx-retry-stream full answer"));
+	assert_eq!(server.requests().await.len(), 2);
+	let hub_content = hub_capture.into_content_all().await?;
+	assert_contains(&hub_content, "fx-retry-stream partial");
+	// Note: Only once, in the agent output (not streamed again by the retry)
+	assert_eq!(
+		hub_content.matches("fx-retry-stream full answer").count(),
+		1,
+		"the retry should not be published again. Hub content:\n{hub_content}"
+	);
+
+	Ok(())
+}
@@ -1,8 +1,13 @@
 type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
 
 use super::*;
-use crate::_test_support::{assert_contains, load_inline_agent, load_test_agent, run_test_agent_with_input};
+use crate::_test_support::{
+	HubCapture, assert_contains, load_inline_agent, load_test_agent, run_test_agent_with_input,
+};
+use crate::cli::RunArgs;
+use crate::run::RunCommandOptions;
 use crate::types::FileMeta;
+use clap::Parser;
 use simple_fs::SPath;
 
 #[tokio::test]
@@ -219,3 +224,177 @@ return "output for: " .. input
 
 	Ok(())
 }
+
+#[tokio::test]
+async fn test_run_agent_script_follow_up_no_inst_err() -> Result<()> {
+	// -- Setup & Fixtures
+	let runtime = Runtime::new_test_runtime_sandbox_01()?;
+	let fx_agent = r#"
+# Output
+
+```lua
+return aipack.follow_up("Some follow up")
+```
+	"#;
+	let agent = load_inline_agent("./dummy/path.aip", fx_agent)?;
+
+	// -- Exec
+	let res = run_test_agent_with_input(&runtime, &agent, "one").await;
+
+	// -- Check
+	let err = res.err().ok_or("Should have returned an error")?;
+	assert_contains(
+		&err.to_string(),
+		"FollowUp is not supported when the agent has no instruction",
+	);
+
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_run_agent_script_data_response_options() -> Result<()> {
+	// -- Setup & Fixtures
+	let runtime = Runtime::new_test_runtime_sandbox_01()?;
+	let fx_agent = r#"
+# Data
+
+```lua
+local model = "cheap-model"
+if input == "big" then
+  model = "strong-model"
+end
+return aipack.data_response({ data = { name = input }, options = { model = model, temperature = 0.3 } })
+```
+
+# Output
+
+```lua
+return data.name .. " - " .. options.model .. " - " .. options.temperature
+```
+	"#;
+	let agent = load_inline_agent("./dummy/path.aip", fx_agent)?;
+
+	// -- Exec
+	let res_small = run_test_agent_with_input(&runtime, &agent, "small").await?;
+	let res_big = run_test_agent_with_input(&runtime, &agent, "big").await?;
+
+	// -- Check
+	assert_eq!(
+		res_small.as_str().ok_or("Should be string")?,
+		"small - cheap-model - 0.3"
+	);
+	assert_eq!(res_big.as_str().ok_or("Should be string")?, "big - strong-model - 0.3");
+	// the agent options are not changed for the other inputs
+	assert_ne!(agent.options_as_ref().model(), Some("strong-model"));
+
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_run_agent_script_continue_on_error() -> Result<()> {
+	// -- Setup & Fixtures
+	let runtime = Runtime::new_test_runtime_sandbox_01()?;
+	let fx_agent = r#"
+# Options
+
+```toml
+continue_on_error = true
+```
+
+# Data
+
+```lua
+if input == "two" then
+  error("Bad input two")
+end
+return input
+```
+
+# Output
+
+```lua
+return "output for: " .. data
+```
+
+# After All
+
+```lua
+return {
+  errors_count = #errors,
+  first_stage  = errors[1].stage,
+  first_label  = errors[1].label,
+}
+```
+	"#;
+	let agent = load_inline_agent("./dummy/path.aip", fx_agent)?;
+	let inputs = vec!["one".into(), "two".into(), "three".into()];
+
+	// -- Exec
+	let res = run_command_agent(&runtime, agent, Some(inputs), &RunBaseOptions::default(), true).await?;
+
+	// -- Check
+	let outputs = res.outputs.ok_or("Should have output values")?;
+	assert_eq!(outputs.len(), 3, "should have one output per input");
+	assert_eq!(
+		outputs[0].as_str().ok_or("output 0 should be string")?,
+		"output for: one"
+	);
+	assert!(outputs[1].is_null(), "failed input output should be null");
+	assert_eq!(res.errors.len(), 1);
+	assert_contains(&res.errors[0].error, "Bad input two");
+	let after_all = res.after_all.ok_or("Should have after_all")?;
+	assert_eq!(after_all.x_get::<i64>("errors_count")?, 1);
+	assert_eq!(after_all.x_get_str("first_stage")?, "data");
+	assert_eq!(after_all.x_get_str("first_label")?, "input index: 1");
+
+	Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_run_agent_script_dry_plan_input_error_plan_saved() -> Result<()> {
+	// -- Setup & Fixtures
+	let runtime = Runtime::new_test_runtime_sandbox_01()?;
+	let fx_agent = r#"
+# Data
+
+```lua
+if input == "fail" then
+  error("fx plan input failed")
+end
+utils.file.save(".tmp/test_run_agent_script_dry_plan_input_error/out-" .. input .. ".txt", "content")
+return aipack.skip("no ai")
+```
+	"#;
+	let agent = load_inline_agent("./dummy/path.aip", fx_agent)?;
+	let inputs = vec!["ok".into(), "fail".into()];
+	let run_options = RunCommandOptions::new(RunArgs::try_parse_from(["run", "dummy", "--dry", "plan"])?)?;
+	let hub_capture = HubCapture::new_and_start();
+
+	// -- Exec
+	let res = run_command_agent(&runtime, agent, Some(inputs), run_options.base_run_config(), true).await;
+
+	// -- Check
+	let err = res.err().ok_or("Should fail on the 'fail' input")?;
+	assert_contains(&err.to_string(), "fx plan input failed");
+	let hub_content = hub_capture.into_content_all().await?;
+	// Note: The plan of the completed input is still published and saved
+	assert_contains(
+		&hub_content,
+		"+++ b/.tmp/test_run_agent_script_dry_plan_input_error/out-ok.txt",
+	);
+	let run_id = hub_content
+		.lines()
+		.find_map(|line| line.trim().strip_prefix("To apply it: aip apply "))
+		.ok_or("Should have saved the plan")?;
+
+	// -- Clean
+	let run_dir = runtime
+		.dir_context()
+		.aipack_paths()
+		.wks_aipack_dir()
+		.join_str(".runs")
+		.join_str(run_id);
+	std::fs::remove_dir_all(run_dir.path())?;
+
+	Ok(())
+}
@@ -0,0 +1,404 @@
+//! Static validation of an agent `.aip` file (for `aip check`)
+//!
+//! NOTE: The `AgentDoc` parser is lenient by design (e.g., unknown sections and non-lua blocks are ignored),
+//!       so this goes over the sections of its lexer (with the line numbers) to report what would be silently dropped.
+
+use crate::agent::AgentOptions;
+use crate::agent::agent_doc::{Block, Section, SectionKind, lex_sections};
+use crate::support::tomls::parse_toml;
+use std::fmt;
+
+/// A diagnostic of an agent file, with its 1-based line number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentDiagnostic {
+	pub line: usize,
+	pub message: String,
+}
+
+impl AgentDiagnostic {
+	fn new(line: usize, message: impl Into<String>) -> Self {
+		AgentDiagnostic {
+			line,
+			message: message.into(),
+		}
+	}
+}
+
+impl fmt::Display for AgentDiagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "line {}: {}", self.line, self.message)
+	}
+}
+
+/// Check the content of an agent file, and return its diagnostics (sorted by line).
+///
+/// Checks:
+/// - Unknown top-level `#` sections (which are ignored when running the agent)
+/// - Unterminated code blocks
+/// - Missing, empty, or wrong language code blocks (e.g., `# Data` must have a ```` ```lua ```` block)
+/// - `# Options` (or legacy `# Config`) TOML syntax and option values
+/// - Lua syntax of the scripts and tool handlers (compile only, nothing is executed)
+/// - Handlebars syntax of the prompt parts (`# System`, `# Instruction`, `# Assistant`)
+pub fn check_agent_content(content: &str) -> Vec<AgentDiagnostic> {
+	let sections = lex_sections(content);
+
+	let mut diags: Vec<AgentDiagnostic> = Vec::new();
+	let lua = mlua::Lua::new();
+
+	let mut options_heading: Option<&Section> = None;
+	let mut config_heading: Option<&Section> = None;
+
+	for section in sections.iter() {
+		for block in section.blocks.iter().filter(|b| b.close_line.is_none()) {
+			diags.push(AgentDiagnostic::new(
+				block.fence_line,
+				format!("Unterminated code block '{}' (missing closing ```)", block.fence),
+			));
+		}
+
+		let Some(kind) = &section.kind else {
+			continue;
+		};
+
+		match kind {
+			SectionKind::Unknown => {
+				let hint = closest_section_name(&section.header)
+					.map(|name| format!(". Did you mean '# {name}'?"))
+					.unwrap_or_default();
+				diags.push(AgentDiagnostic::new(
+					section.heading_line,
+					format!("Unknown section '{}' (it will be ignored){hint}", section.heading),
+				));
+			}
+			SectionKind::Options | SectionKind::Config => {
+				if matches!(kind, SectionKind::Options) {
+					options_heading = Some(section);
+				} else {
+					config_heading = Some(section);
+				}
+				check_toml_section(section, matches!(kind, SectionKind::Config), &mut diags);
+			}
+			SectionKind::BeforeAll | SectionKind::Data | SectionKind::Output | SectionKind::AfterAll => {
+				check_script_section(&lua, section, &mut diags)
+			}
+			SectionKind::PromptPart(_) => check_prompt_part(section, &mut diags),
+			SectionKind::Tools => check_tools_section(&lua, section, &mut diags),
+		}
+	}
+
+	if let (Some(options), Some(config)) = (options_heading, config_heading) {
+		let line = options.heading_line.max(config.heading_line);
+		diags.push(AgentDiagnostic::new(
+			line,
+			"Agent cannot have both a '# Config' and a '# Options' section (use '# Options')",
+		));
+	}
+
+	diags.sort_by_key(|d| d.line);
+	diags
+}
+
+// region:    --- Section Names
+
+const SECTION_NAMES: &[&str] = &[
+	"Options",
+	"Before All",
+	"Data",
+	"System",
+	"Instruction",
+	"Assistant",
+	"Tools",
+	"Output",
+	"After All",
+];
+
+/// Returns the known section name close to this (lowercase) header (e.g., `ouput` -> `Output`)
+fn closest_section_name(header: &str) -> Option<&'static str> {
+	SECTION_NAMES
+		.iter()
+		.map(|name| (name, strsim::levenshtein(header, &name.to_lowercase())))
+		.filter(|(_, dist)| *dist <= 2)
+		.min_by_key(|(_, dist)| *dist)
+		.map(|(name, _)| *name)
+}
+
+// endregion: --- Section Names
+
+// region:    --- Section Checks
+
+fn check_toml_section(section: &Section, is_config: bool, diags: &mut Vec<AgentDiagnostic>) {
+	let heading = section.heading;
+	// Note: Like the AgentDoc lexer, only the first ```toml block is used
+	let Some(block) = section.blocks.iter().find(|b| b.lang == "toml") else {
+		diags.push(AgentDiagnostic::new(
+			section.heading_line,
+			format!("Section '{heading}' has no ```toml code block (it will be ignored)"),
+		));
+		return;
+	};
+
+	let code = block.code();
+	if let Err(err) = toml::from_str::<toml::Value>(&code) {
+		let line = err
+			.span()
+			.map(|span| block.first_content_line() + code[..span.start].matches('\n').count())
+			.unwrap_or(block.fence_line);
+		diags.push(AgentDiagnostic::new(
+			line,
+			format!("Invalid TOML in '{heading}'. Cause: {}", err.message()),
+		));
+		return;
+	}
+
+	let options_res = parse_toml(&code).and_then(|value| {
+		if is_config {
+			AgentOptions::from_config_value(value)
+		} else {
+			AgentOptions::from_options_value(value)
+		}
+	});
+	if let Err(err) = options_res {
+		diags.push(AgentDiagnostic::new(
+			block.fence_line,
+			format!("Invalid options in '{heading}'. Cause: {err}"),
+		));
+	}
+}
+
+fn check_script_section(lua: &mlua::Lua, section: &Section, diags: &mut Vec<AgentDiagnostic>) {
+	let heading = section.heading;
+	// Note: Like the AgentDoc lexer, only the first ```lua block is used
+	let Some(block) = section.blocks.iter().find(|b| b.lang == "lua") else {
+		let message = match section.blocks.first() {
+			Some(block) => format!(
+				"Section '{heading}' code block must be ```lua (found '{}'), so it will be ignored",
+				block.fence
+			),
+			None => format!("Section '{heading}' has no ```lua code block (it will be ignored)"),
+		};
+		diags.push(AgentDiagnostic::new(section.heading_line, message));
+		return;
+	};
+
+	if block.is_empty() {
+		diags.push(AgentDiagnostic::new(
+			block.fence_line,
+			format!("Section '{heading}' has an empty ```lua code block"),
+		));
+		return;
+	}
+
+	check_lua_block(lua, heading, block, diags);
+}
+
+fn check_prompt_part(section: &Section, diags: &mut Vec<AgentDiagnostic>) {
+	// Note: The whole section content is the handlebars template (code blocks included)
+	let content = section.lines.iter().map(|(_, line)| *line).collect::<Vec<_>>().join("\n");
+
+	if content.trim().is_empty() {
+		diags.push(AgentDiagnostic::new(
+			section.heading_line,
+			format!("Section '{}' is empty", section.heading),
+		));
+		return;
+	}
+
+	if let Err(err) = handlebars::Template::compile(&content) {
+		let line = err
+			.pos()
+			.map(|(line_no, _)| section.heading_line + line_no)
+			.unwrap_or(section.heading_line);
+		diags.push(AgentDiagnostic::new(
+			line,
+			format!(
+				"Invalid handlebars template in '{}'. Cause: {}",
+				section.heading,
+				err.reason()
+			),
+		));
+	}
+}
+
+fn check_tools_section(lua: &mlua::Lua, section: &Section, diags: &mut Vec<AgentDiagnostic>) {
+	for block in section.blocks.iter() {
+		if block.tool_line.is_none() && (block.lang == "json" || block.lang == "lua") {
+			diags.push(AgentDiagnostic::new(
+				block.fence_line,
+				"Code block in '# Tools' section must be below a '## tool_name' heading",
+			));
+		}
+	}
+
+	for &(heading_line, tool_name) in section.tool_headings.iter() {
+		let tool_blocks = section.blocks.iter().filter(|b| b.tool_line == Some(heading_line));
+
+		let mut has_lua = false;
+		for block in tool_blocks {
+			let label = format!("## {tool_name}");
+			match block.lang.as_str() {
+				"json" => {
+					if let Err(err) = serde_json::from_str::<serde_json::Value>(&block.code()) {
+						let line = block.first_content_line() + err.line().saturating_sub(1);
+						diags.push(AgentDiagnostic::new(
+							line,
+							format!("Tool '{tool_name}' has an invalid json schema. Cause: {err}"),
+						));
+					}
+				}
+				"lua" if !has_lua => {
+					has_lua = true;
+					if block.is_empty() {
+						diags.push(AgentDiagnostic::new(
+							block.fence_line,
+							format!("Tool '{tool_name}' has an empty ```lua handler block"),
+						));
+					} else {
+						check_lua_block(lua, &label, block, diags);
+					}
+				}
+				_ => (),
+			}
+		}
+
+		if !has_lua {
+			diags.push(AgentDiagnostic::new(
+				heading_line,
+				format!("Tool '{tool_name}' is missing its ```lua handler block"),
+			));
+		}
+	}
+}
+
+/// Compile (without executing) the lua code of this block
+fn check_lua_block(lua: &mlua::Lua, label: &str, block: &Block, diags: &mut Vec<AgentDiagnostic>) {
+	const CHUNK_NAME: &str = "script";
+
+	let code = block.code();
+	let Err(err) = lua.load(&code).set_name(format!("={CHUNK_NAME}")).into_function() else {
+		return;
+	};
+
+	let message = match &err {
+		mlua::Error::SyntaxError { message, .. } => message.clone(),
+		other => other.to_string(),
+	};
+
+	// e.g., `script:3: '=' expected near 'x'`
+	let lua_line_and_cause = message.strip_prefix(&format!("{CHUNK_NAME}:")).and_then(|rest| {
+		let (line_num, cause) = rest.split_once(':')?;
+		Some((line_num.parse::<usize>().ok()?, cause.trim()))
+	});
+
+	let (line, cause) = match lua_line_and_cause {
+		Some((lua_line, cause)) => (block.first_content_line() + lua_line - 1, cause),
+		None => (block.fence_line, message.as_str()),
+	};
+
+	diags.push(AgentDiagnostic::new(
+		line,
+		format!("Lua syntax error in '{label}'. Cause: {cause}"),
+	));
+}
+
+// endregion: --- Section Checks
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::assert_contains;
+
+	#[test]
+	fn test_agent_check_valid() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_content = r#"
+# Options
+
+```toml
+model = "gpt-4o-mini"
+```
+
+# Data
+
+```lua
+return { "one", "two" }
+```
+
+# Instruction
+
+Say hello to {{data}}
+
+# Output
+
+```lua
+return ai_response.content
+```
+"#;
+
+		// -- Exec
+		let diags = check_agent_content(fx_content);
+
+		// -- Check
+		assert!(diags.is_empty(), "Should have no diagnostics, but got: {diags:?}");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_agent_check_errors_with_lines() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_content = r#"# Options
+
+```toml
+model = "gpt-4o-mini
+```
+
+# Data
+
+```js
+return 1
+```
+
+# Instruction
+
+Hello {{#if data}} world {{/each}}
+
+# Ouput
+
+```lua
+return ai_response.content
+```
+
+# After All
+
+```lua
+local x = = 1
+"#;
+
+		// -- Exec
+		let diags = check_agent_content(fx_content);
+
+		// -- Check
+		let diags: Vec<String> = diags.iter().map(|d| d.to_string()).collect();
+		assert_eq!(diags.len(), 6, "diags: {diags:#?}");
+		assert_contains(&diags[0], "line 4: Invalid TOML in '# Options'");
+		assert_contains(
+			&diags[1],
+			"line 7: Section '# Data' code block must be ```lua (found '```js')",
+		);
+		assert_contains(&diags[2], "line 15: Invalid handlebars template in '# Instruction'");
+		assert_contains(
+			&diags[3],
+			"line 17: Unknown section '# Ouput' (it will be ignored). Did you mean '# Output'?",
+		);
+		assert_contains(&diags[4], "line 25: Unterminated code block '```lua'");
+		assert_contains(&diags[5], "line 26: Lua syntax error in '# After All'");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
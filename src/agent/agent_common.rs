@@ -1,6 +1,6 @@
-use crate::agent::PromptPart;
 use crate::agent::agent_options::AgentOptions;
 use crate::agent::agent_ref::AgentRef;
+use crate::agent::{AgentTool, PromptPart};
 use crate::{Error, Result};
 use genai::ModelName;
 use genai::chat::ChatOptions;
@@ -30,7 +30,7 @@ impl Agent {
 		})?;
 		let model_resolved = inner.agent_options.resolve_model().map(|v| v.into()).unwrap_or(model.clone());
 
-		let chat_options = ChatOptions::from(&*inner.agent_options);
+		let chat_options = ChatOptions::try_from(&*inner.agent_options)?;
 
 		Ok(Agent {
 			inner,
@@ -52,7 +52,7 @@ impl Agent {
 		let model_resolved = options.resolve_model().map(|v| v.into()).unwrap_or(model.clone());
 
 		// -- Build the genai chat optoins
-		let chat_options = ChatOptions::from(&options);
+		let chat_options = ChatOptions::try_from(&options)?;
 
 		// -- Returns
 		Ok(Agent {
@@ -123,6 +123,10 @@ impl Agent {
 		self.inner.prompt_parts.iter().collect()
 	}
 
+	pub fn tools(&self) -> &[AgentTool] {
+		&self.inner.tools
+	}
+
 	pub fn data_script(&self) -> Option<&str> {
 		self.inner.data_script.as_deref()
 	}
@@ -159,10 +163,57 @@ pub(super) struct AgentInner {
 	/// Contains the instruction, system, assistant in order of the file
 	pub prompt_parts: Vec<PromptPart>,
 
+	/// The tools from the `# Tools` section (in order of the file)
+	pub tools: Vec<AgentTool>,
+
 	/// Script
 	pub data_script: Option<String>,
 	pub output_script: Option<String>,
 	pub after_all_script: Option<String>,
 }
 
+impl AgentInner {
+	/// Set the final agent options (and the model name from them)
+	pub(super) fn with_options(mut self, agent_options: AgentOptions) -> AgentInner {
+		self.model_name = agent_options.model().map(ModelName::from);
+		self.agent_options = Arc::new(agent_options);
+		self
+	}
+
+	/// Inherit the sections missing in this agent from the parent agent (for the `extends` option).
+	///
+	/// - Scripts and tools are taken from the parent only when this agent does not have them.
+	/// - Prompt parts follow the `extends_prompt` option (`replace` by default, `prepend`, or `append`).
+	///
+	/// Note: The options are already merged (parent then child) when the child doc is parsed.
+	pub(super) fn extend_from(mut self, parent: &Agent) -> Result<AgentInner> {
+		let extends_prompt = self.agent_options.extends_prompt().unwrap_or("replace");
+		let parent_parts = parent.prompt_parts().into_iter().cloned();
+		self.prompt_parts = match extends_prompt {
+			"replace" if self.prompt_parts.is_empty() => parent_parts.collect(),
+			"replace" => self.prompt_parts,
+			"prepend" => self.prompt_parts.into_iter().chain(parent_parts).collect(),
+			"append" => parent_parts.chain(self.prompt_parts).collect(),
+			other => {
+				return Err(Error::custom(format!(
+					"Agent '{}' has an invalid extends_prompt '{other}'. Must be 'replace', 'prepend', or 'append'",
+					self.file_path
+				)));
+			}
+		};
+
+		if self.tools.is_empty() {
+			self.tools = parent.tools().to_vec();
+		}
+
+		let inherit = |script: Option<String>, parent_script: Option<&str>| script.or(parent_script.map(String::from));
+		self.before_all_script = inherit(self.before_all_script, parent.before_all_script());
+		self.data_script = inherit(self.data_script, parent.data_script());
+		self.output_script = inherit(self.output_script, parent.output_script());
+		self.after_all_script = inherit(self.after_all_script, parent.after_all_script());
+
+		Ok(self)
+	}
+}
+
 // endregion: --- AgentInner
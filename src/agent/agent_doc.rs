@@ -1,15 +1,17 @@
-use crate::Result;
 use crate::agent::agent_options::AgentOptions;
 use crate::agent::agent_ref::AgentRef;
-use crate::agent::{Agent, AgentInner, PartKind, PromptPart};
+use crate::agent::{Agent, AgentInner, AgentTool, PartKind, PromptPart};
+use crate::support::interpolate::interpolate_value;
 use crate::support::md::InBlockState;
 use crate::support::tomls::parse_toml;
+use crate::{Error, Result};
 use genai::ModelName;
+use serde_json::Value;
 use simple_fs::{SPath, read_to_string};
 use std::path::Path;
 use std::sync::Arc;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AgentDoc {
 	spath: SPath,
 	raw_content: String,
@@ -29,231 +31,68 @@ impl AgentDoc {
 		Ok(agent)
 	}
 
-	/// Internal method to create the first part of the agent inner
-	/// This is sort of a Lexer, but very customize to extracting the Agent parts
-	fn into_agent_inner(self, name: &str, agent_ref: AgentRef, agent_options: AgentOptions) -> Result<AgentInner> {
-		#[derive(Debug)]
-		enum CaptureMode {
-			None,
-
-			// Below the output heading (perhaps not in a code block)
-			BeforeAllSection,
-			// Inside the code block
-			BeforeAllCodeBlock,
-
-			// Below the # Options section
-			OptionsSection,
-			OptionsTomlBlock,
-
-			// (legacy) Below the # Config section
-			ConfigSection,
-			// (legacy) inside the ConfigTomlBlock
-			ConfigTomlBlock,
-
-			// Below the data heading (perhaps not in a code block)
-			DataSection,
-			// Inside the code block
-			DataCodeBlock,
-
-			PromptPart,
-
-			// Below the output heading (perhaps not in a code block)
-			OutputSection,
-			// Inside the code block
-			OutputCodeBlock,
-
-			// Below the output heading (perhaps not in a code block)
-			AfterAllSection,
-			// Inside the code block
-			AfterAllCodeBlock,
-		}
-
-		impl CaptureMode {
-			/// Here we are inside a code block that is getting captured for an action
-			/// either Lua script, toml, ...
-			///
-			/// NOTE: This is not used anymore since we have the `is_in_any_block`, but can be later.
-			#[allow(unused)]
-			fn is_inside_actionable_block(&self) -> bool {
-				matches!(
-					self,
-					CaptureMode::ConfigTomlBlock
-						| CaptureMode::OptionsTomlBlock
-						| CaptureMode::BeforeAllCodeBlock
-						| CaptureMode::DataCodeBlock
-						| CaptureMode::OutputCodeBlock
-						| CaptureMode::AfterAllCodeBlock
-				)
-			}
-		}
+	/// Internal method to create the first part of the agent inner (with the options of this agent file merged on `agent_options`)
+	pub(super) fn into_agent_inner(
+		self,
+		name: &str,
+		agent_ref: AgentRef,
+		agent_options: AgentOptions,
+	) -> Result<AgentInner> {
+		let (agent_inner, agent_options_ov) = self.into_agent_inner_and_options_ov(name, agent_ref)?;
+		let agent_options = match agent_options_ov {
+			Some(agent_options_ov) => agent_options.merge(agent_options_ov)?,
+			None => agent_options,
+		};
+		Ok(agent_inner.with_options(agent_options))
+	}
 
-		let mut capture_mode = CaptureMode::None;
+	/// Parse the agent inner, and return it with the options of this agent file (to be merged on the base options)
+	/// The sections are from the `lex_sections` lexer (also used by `aip check`)
+	///
+	/// Note: The returned agent inner has the default options (see `AgentInner::with_options`)
+	pub(super) fn into_agent_inner_and_options_ov(
+		self,
+		name: &str,
+		agent_ref: AgentRef,
+	) -> Result<(AgentInner, Option<AgentOptions>)> {
+		let sections = lex_sections(&self.raw_content);
 
 		// -- The buffers
-		let mut config_toml: Vec<&str> = Vec::new();
-		let mut options_toml: Vec<&str> = Vec::new();
-		let mut before_all_script: Vec<&str> = Vec::new();
-		let mut data_script: Vec<&str> = Vec::new();
-		let mut output_script: Vec<&str> = Vec::new();
-		let mut after_all_script: Vec<&str> = Vec::new();
-
+		let mut config_toml: Vec<String> = Vec::new();
+		let mut options_toml: Vec<String> = Vec::new();
+		let mut before_all_script: Vec<String> = Vec::new();
+		let mut data_script: Vec<String> = Vec::new();
+		let mut output_script: Vec<String> = Vec::new();
+		let mut after_all_script: Vec<String> = Vec::new();
 		let mut prompt_parts: Vec<PromptPart> = Vec::new();
-		// the vec String allow to be more efficient (as join later is more efficient)
-		let mut current_part: Option<CurrentPromptPart> = None;
-
-		// -- The actual parsing
-		// NOTE: Need custom parser/lexer given the nature of the agent format.
-		//       Markdown parsers tend to be lossless and would need wuite a bit of extra post-processing anyway.
-		//       So, here we do one path, and capture what we need, exactly the way we need it
-
-		let mut block_state = InBlockState::Out;
-
-		for line in self.raw_content.lines() {
-			block_state = block_state.compute_new(line);
-			// If heading we decide the capture mode
-			if block_state.is_out() && line.starts_with('#') && !line.starts_with("##") {
-				let header = line[1..].trim().to_lowercase();
-				if header == "config" {
-					capture_mode = CaptureMode::ConfigSection;
-				} else if header == "options" {
-					capture_mode = CaptureMode::OptionsSection;
-				} else if header == "before all" {
-					capture_mode = CaptureMode::BeforeAllSection;
-				} else if header == "data" {
-					capture_mode = CaptureMode::DataSection;
-				} else if header == "output" {
-					capture_mode = CaptureMode::OutputSection;
-				} else if header == "after all" {
-					capture_mode = CaptureMode::AfterAllSection;
-				} else if let Some(part_kind) = get_prompt_part_kind(&header) {
-					capture_mode = CaptureMode::PromptPart;
-					// we finalize the previous part if present
-					finalize_current_prompt_part(&mut current_part, &mut prompt_parts);
-					// then, we create the new current_part
-					current_part = Some(CurrentPromptPart(part_kind, Vec::new()));
-				} else {
-					// Stop processing current section if new top-level header
-					capture_mode = CaptureMode::None;
-				}
-				continue;
-			}
-
-			match capture_mode {
-				CaptureMode::None => {}
+		let mut tools: Vec<AgentTool> = Vec::new();
 
-				// -- Config (legacy, now use options)
-				CaptureMode::ConfigSection => {
-					if line.starts_with("```toml") {
-						capture_mode = CaptureMode::ConfigTomlBlock;
-						continue;
-					}
-				}
-
-				CaptureMode::ConfigTomlBlock => {
-					if line.starts_with("```") {
-						capture_mode = CaptureMode::None;
-						continue;
-					} else {
-						push_line(&mut config_toml, line);
-					}
-				}
-
-				// -- Options
-				CaptureMode::OptionsSection => {
-					if line.starts_with("```toml") {
-						capture_mode = CaptureMode::OptionsTomlBlock;
-						continue;
-					}
-				}
-
-				CaptureMode::OptionsTomlBlock => {
-					if line.starts_with("```") {
-						capture_mode = CaptureMode::None;
-						continue;
-					} else {
-						push_line(&mut options_toml, line);
-					}
-				}
-
-				// -- Before All
-				CaptureMode::BeforeAllSection => {
-					if line.starts_with("```lua") {
-						capture_mode = CaptureMode::BeforeAllCodeBlock;
-						continue;
-					}
-				}
-				CaptureMode::BeforeAllCodeBlock => {
-					if line.starts_with("```") {
-						capture_mode = CaptureMode::None;
-						continue;
-					} else {
-						push_line(&mut before_all_script, line);
-					}
-				}
-
-				// -- Data
-				CaptureMode::DataSection => {
-					if line.starts_with("```lua") {
-						capture_mode = CaptureMode::DataCodeBlock;
-						continue;
-					}
-				}
-				CaptureMode::DataCodeBlock => {
-					if line.starts_with("```") {
-						capture_mode = CaptureMode::None;
-						continue;
-					} else {
-						push_line(&mut data_script, line);
-					}
-				}
-
-				// -- Pompt Part
-				CaptureMode::PromptPart => {
-					if let Some(current_part) = &mut current_part {
-						current_part.1.push(line);
-					} else {
-						// This should not happen, as the current_part should be been created when we enterred the section
-						// TODO: Need to capture warning if we reach this point.
-					}
-				}
-
-				// -- Output
-				CaptureMode::OutputSection => {
-					if line.starts_with("```lua") {
-						capture_mode = CaptureMode::OutputCodeBlock;
-						continue;
-					}
-				}
-				CaptureMode::OutputCodeBlock => {
-					if line.starts_with("```") {
-						capture_mode = CaptureMode::None;
-						continue;
-					} else {
-						push_line(&mut output_script, line);
-					}
-				}
-
-				// -- After All
-				CaptureMode::AfterAllSection => {
-					if line.starts_with("```lua") {
-						capture_mode = CaptureMode::AfterAllCodeBlock;
-						continue;
-					}
-				}
-				CaptureMode::AfterAllCodeBlock => {
-					if line.starts_with("```") {
-						capture_mode = CaptureMode::None;
-						continue;
-					} else {
-						push_line(&mut after_all_script, line);
-					}
+		// Note: Like before, the same sections are concatenated, and only their first toml/lua block is used
+		for section in sections.iter() {
+			let Some(kind) = section.kind.as_ref() else {
+				continue;
+			};
+			match kind {
+				SectionKind::Config => config_toml.extend(section.first_block_code("toml")),
+				SectionKind::Options => options_toml.extend(section.first_block_code("toml")),
+				SectionKind::BeforeAll => before_all_script.extend(section.first_block_code("lua")),
+				SectionKind::Data => data_script.extend(section.first_block_code("lua")),
+				SectionKind::Output => output_script.extend(section.first_block_code("lua")),
+				SectionKind::AfterAll => after_all_script.extend(section.first_block_code("lua")),
+				SectionKind::PromptPart(part_kind) => {
+					// the whole section content, with the last new line
+					let mut content: Vec<&str> = section.lines.iter().map(|(_, line)| *line).collect();
+					content.push("");
+					prompt_parts.push(PromptPart {
+						kind: part_kind.clone(),
+						content: content.join("\n"),
+					});
 				}
+				SectionKind::Tools => tools.extend(self.section_tools(section)?),
+				SectionKind::Unknown => (),
 			}
 		}
 
-		// -- We finilize the last part if it was not closed
-		finalize_current_prompt_part(&mut current_part, &mut prompt_parts);
-
 		// -- Returning the data
 
 		let config_toml = buffer_to_string(config_toml);
@@ -261,8 +100,12 @@ impl AgentDoc {
 
 		let agent_options_ov: Option<AgentOptions> = match (config_toml, options_toml) {
 			(None, None) => None,
-			(None, Some(options_toml)) => Some(AgentOptions::from_options_value(parse_toml(&options_toml)?)?),
-			(Some(config_toml), None) => Some(AgentOptions::from_config_value(parse_toml(&config_toml)?)?),
+			(None, Some(options_toml)) => Some(AgentOptions::from_options_value(
+				self.parse_interpolated_toml(&options_toml)?,
+			)?),
+			(Some(config_toml), None) => Some(AgentOptions::from_config_value(
+				self.parse_interpolated_toml(&config_toml)?,
+			)?),
 			(Some(_), Some(_)) => {
 				return Err("\
 Agent .aipack file cannot have a '# Config' and '# Options' section.
@@ -272,17 +115,9 @@ Use the '# Options' section ('# Config' is not the legacy way to provides agent
 			}
 		};
 
-		let agent_options = match agent_options_ov {
-			Some(agent_options_ov) => agent_options.merge(agent_options_ov)?,
-			None => agent_options,
-		};
-
-		// -- Get the model name
-		let model_name = agent_options.model().map(ModelName::from);
-
 		// -- Build the AgentInner
 		let agent_inner = AgentInner {
-			agent_options: Arc::new(agent_options),
+			agent_options: Arc::new(AgentOptions::default()),
 
 			name: name.to_string(),
 			agent_ref,
@@ -290,18 +125,108 @@ Use the '# Options' section ('# Config' is not the legacy way to provides agent
 			file_name: self.spath.name().to_string(),
 			file_path: self.spath.to_str().to_string(),
 
-			model_name,
+			model_name: None,
 
 			before_all_script: buffer_to_string(before_all_script),
 			data_script: buffer_to_string(data_script),
 
 			prompt_parts,
 
+			tools,
+
 			output_script: buffer_to_string(output_script),
 			after_all_script: buffer_to_string(after_all_script),
 		};
 
-		Ok(agent_inner)
+		Ok((agent_inner, agent_options_ov))
+	}
+
+	/// The tools of a `# Tools` section (each `## tool_name` sub heading, with its ```` ```json ```` schema
+	/// and ```` ```lua ```` handler blocks, the other lines being the description)
+	///
+	/// Note: Fails if a tool does not have a lua handler or if its json schema is invalid
+	fn section_tools(&self, section: &Section) -> Result<Vec<AgentTool>> {
+		let is_tool_block = |block: &&Block| block.lang == "json" || block.lang == "lua";
+
+		if section
+			.blocks
+			.iter()
+			.filter(is_tool_block)
+			.any(|block| block.tool_line.is_none())
+		{
+			return Err(format!(
+				"Agent '{}' - code block in '# Tools' section must be below a '## tool_name' heading",
+				self.spath
+			)
+			.into());
+		}
+
+		let mut tools: Vec<AgentTool> = Vec::new();
+		for (idx, (heading_line, name)) in section.tool_headings.iter().enumerate() {
+			let next_heading_line = section.tool_headings.get(idx + 1).map(|(line, _)| *line).unwrap_or(usize::MAX);
+			let tool_blocks: Vec<&Block> = section
+				.blocks
+				.iter()
+				.filter(is_tool_block)
+				.filter(|block| block.tool_line == Some(*heading_line))
+				.collect();
+
+			let description = section
+				.lines
+				.iter()
+				.filter(|(line_num, _)| *line_num > *heading_line && *line_num < next_heading_line)
+				.filter(|(line_num, _)| !tool_blocks.iter().any(|block| block.contains_line(*line_num)))
+				.map(|(_, line)| *line)
+				.collect::<Vec<_>>()
+				.join("\n")
+				.trim()
+				.to_string();
+			let description = if description.is_empty() {
+				None
+			} else {
+				Some(description)
+			};
+
+			let block_code = |lang: &str| {
+				let codes: Vec<String> = tool_blocks
+					.iter()
+					.filter(|b| b.lang == lang)
+					.map(|b| b.code())
+					.filter(|code| !code.is_empty())
+					.collect();
+				buffer_to_string(codes)
+			};
+
+			let schema = match block_code("json") {
+				Some(schema) => Some(
+					serde_json::from_str::<Value>(&schema)
+						.map_err(|err| format!("Tool '{name}' has an invalid json schema.\n    Cause: {err}"))?,
+				),
+				None => None,
+			};
+
+			let script =
+				block_code("lua").ok_or_else(|| format!("Tool '{name}' is missing its ```lua handler block"))?;
+
+			tools.push(AgentTool {
+				name: name.to_string(),
+				description,
+				schema,
+				script,
+			});
+		}
+
+		Ok(tools)
+	}
+
+	/// Parse the `# Options` (or `# Config`) toml, and resolve the `${env:VAR}` and `${keyring:name}` references
+	fn parse_interpolated_toml(&self, toml_content: &str) -> Result<Value> {
+		let mut value = parse_toml(toml_content)?;
+		interpolate_value(&mut value).map_err(|err| Error::Config {
+			path: self.spath.to_string(),
+			reason: err.to_string(),
+		})?;
+		Ok(value)
 	}
 }
 
@@ -329,33 +254,355 @@ fn get_prompt_part_kind(header: &str) -> Option<PartKind> {
 	}
 }
 
-/// Type of the function below and the `into_agent_inner` lexer
-struct CurrentPromptPart<'a>(PartKind, Vec<&'a str>);
+fn buffer_to_string(content: Vec<String>) -> Option<String> {
+	if content.is_empty() {
+		None
+	} else {
+		Some(content.join(""))
+	}
+}
 
-/// Finalize a eventual current_part
-fn finalize_current_prompt_part(current_part: &mut Option<CurrentPromptPart<'_>>, prompt_parts: &mut Vec<PromptPart>) {
-	if let Some(current_part) = current_part.take() {
-		// to have the last line
-		let kind = current_part.0;
-		let mut content = current_part.1;
-		content.push("");
-		let content = content.join("\n");
+// endregion: --- Support
+
+// region:    --- Lexer
+
+/// The kind of a top-level `# ...` section of an agent file
+pub(super) enum SectionKind {
+	Options,
+	/// (legacy) `# Config`
+	Config,
+	BeforeAll,
+	Data,
+	Output,
+	AfterAll,
+	PromptPart(PartKind),
+	Tools,
+	Unknown,
+}
+
+/// A top-level section of an agent file, with its code blocks and the 1-based line numbers
+pub(super) struct Section<'a> {
+	/// None for the content before the first heading
+	pub kind: Option<SectionKind>,
+	/// The heading line as in the file (e.g., `# Ouput`)
+	pub heading: &'a str,
+	/// The lowercase trimmed header name (e.g., `ouput`)
+	pub header: String,
+	pub heading_line: usize,
+	/// All of the lines below the heading (with their line number)
+	pub lines: Vec<(usize, &'a str)>,
+	pub blocks: Vec<Block<'a>>,
+	/// The `## tool_name` sub headings, outside of the code blocks (only for the `# Tools` section)
+	pub tool_headings: Vec<(usize, &'a str)>,
+}
 
-		let part = PromptPart { kind, content };
-		prompt_parts.push(part);
+impl Section<'_> {
+	/// The code of the first block of this language (e.g., `lua`, `toml`), if any and not without lines
+	pub fn first_block_code(&self, lang: &str) -> Option<String> {
+		self.blocks
+			.iter()
+			.find(|block| block.lang == lang)
+			.map(|block| block.code())
+			.filter(|code| !code.is_empty())
 	}
 }
 
-/// Push a new line and the a \n to respect the new line
-fn push_line<'a, 'b, 'c: 'b>(content: &'a mut Vec<&'b str>, line: &'c str) {
-	content.push(line);
-	content.push("\n");
+/// A code block of a section
+pub(super) struct Block<'a> {
+	/// The opening fence line (e.g., ```` ```lua ````)
+	pub fence: &'a str,
+	pub fence_line: usize,
+	/// The code block language (e.g., `lua`, `toml`), empty if none
+	pub lang: String,
+	pub lines: Vec<(usize, &'a str)>,
+	/// The closing fence line (None when the block is not terminated)
+	pub close_line: Option<usize>,
+	/// The line of the `## tool_name` sub heading above this block (only for the `# Tools` section)
+	pub tool_line: Option<usize>,
 }
 
-fn buffer_to_string(content: Vec<&str>) -> Option<String> {
-	if content.is_empty() {
-		None
-	} else {
-		Some(content.join(""))
+impl Block<'_> {
+	pub fn first_content_line(&self) -> usize {
+		self.fence_line + 1
+	}
+
+	/// The code, with a new line after each line
+	pub fn code(&self) -> String {
+		self.lines.iter().map(|(_, line)| format!("{line}\n")).collect()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.lines.iter().all(|(_, line)| line.trim().is_empty())
+	}
+
+	/// True if the line is one of the block (fences included)
+	fn contains_line(&self, line_num: usize) -> bool {
+		line_num >= self.fence_line && line_num <= self.close_line.unwrap_or(usize::MAX)
+	}
+}
+
+/// Split the agent content by top-level `#` sections (outside of code blocks), with their code blocks
+pub(super) fn lex_sections(content: &str) -> Vec<Section<'_>> {
+	let mut sections = vec![Section {
+		kind: None,
+		heading: "",
+		header: String::new(),
+		heading_line: 0,
+		lines: Vec::new(),
+		blocks: Vec::new(),
+		tool_headings: Vec::new(),
+	}];
+
+	let mut block_state = InBlockState::Out;
+
+	for (idx, line) in content.lines().enumerate() {
+		let line_num = idx + 1;
+		let was_out = block_state.is_out();
+		block_state = block_state.compute_new(line);
+
+		if block_state.is_out() && was_out && line.starts_with('#') && !line.starts_with("##") {
+			let header = line[1..].trim().to_lowercase();
+			sections.push(Section {
+				kind: Some(section_kind(&header)),
+				heading: line.trim(),
+				header,
+				heading_line: line_num,
+				lines: Vec::new(),
+				blocks: Vec::new(),
+				tool_headings: Vec::new(),
+			});
+			continue;
+		}
+
+		// Note: There is always at least the first pseudo section
+		let Some(section) = sections.last_mut() else {
+			continue;
+		};
+		section.lines.push((line_num, line));
+
+		if was_out && !block_state.is_out() {
+			let tool_line = section.tool_headings.last().map(|(tool_line, _)| *tool_line);
+			section.blocks.push(Block {
+				fence: line.trim(),
+				fence_line: line_num,
+				lang: line.trim_start_matches('`').trim().to_lowercase(),
+				lines: Vec::new(),
+				close_line: None,
+				tool_line,
+			});
+		} else if !was_out && block_state.is_out() {
+			if let Some(block) = section.blocks.last_mut() {
+				block.close_line = Some(line_num);
+			}
+		} else if !block_state.is_out() {
+			if let Some(block) = section.blocks.last_mut() {
+				block.lines.push((line_num, line));
+			}
+		} else if matches!(section.kind, Some(SectionKind::Tools)) {
+			if let Some(tool_name) = line.strip_prefix("## ") {
+				section.tool_headings.push((line_num, tool_name.trim()));
+			}
+		}
+	}
+
+	sections
+}
+
+fn section_kind(header: &str) -> SectionKind {
+	match header {
+		"options" => SectionKind::Options,
+		"config" => SectionKind::Config,
+		"before all" => SectionKind::BeforeAll,
+		"data" => SectionKind::Data,
+		"output" => SectionKind::Output,
+		"after all" => SectionKind::AfterAll,
+		"tools" => SectionKind::Tools,
+		_ => match get_prompt_part_kind(header) {
+			Some(part_kind) => SectionKind::PromptPart(part_kind),
+			None => SectionKind::Unknown,
+		},
+	}
+}
+
+// endregion: --- Lexer
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::{assert_contains, default_agent_config_for_test};
+
+	#[test]
+	fn test_agent_doc_tools_simple() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_content = r#"
+# Tools
+
+## load_file
+
+Load a file content from the workspace
+
+```json
+{ "type": "object", "properties": { "path": { "type": "string" } } }
+```
+
+```lua
+return utils.file.load(args.path).content
+```
+
+## get_time
+
+```lua
+return "12:00"
+```
+
+# Instruction
+
+Some instruction
+"#;
+		let doc = AgentDoc::from_content("./dummy/path.aip", fx_content)?;
+
+		// -- Exec
+		let agent = doc.into_agent(
+			"dummy",
+			AgentRef::LocalPath("./dummy/path.aip".into()),
+			default_agent_config_for_test(),
+		)?;
+
+		// -- Check
+		let tools = agent.tools();
+		assert_eq!(tools.len(), 2);
+		let load_file = &tools[0];
+		assert_eq!(load_file.name, "load_file");
+		assert_eq!(
+			load_file.description.as_deref(),
+			Some("Load a file content from the workspace")
+		);
+		assert_eq!(
+			load_file.schema.as_ref().and_then(|s| s.pointer("/type")),
+			Some(&"object".into())
+		);
+		assert_contains(&load_file.script, "utils.file.load(args.path)");
+		let get_time = &tools[1];
+		assert!(get_time.description.is_none(), "get_time should not have description");
+		assert!(get_time.schema.is_none(), "get_time should not have schema");
+		assert_eq!(agent.prompt_parts().len(), 1);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_agent_doc_tools_missing_lua_err() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_content = r#"
+# Tools
+
+## no_handler
+
+```json
+{ "type": "object" }
+```
+"#;
+		let doc = AgentDoc::from_content("./dummy/path.aip", fx_content)?;
+
+		// -- Exec
+		let res = doc.into_agent(
+			"dummy",
+			AgentRef::LocalPath("./dummy/path.aip".into()),
+			default_agent_config_for_test(),
+		);
+
+		// -- Check
+		let err = res.err().ok_or("Should have returned an error")?;
+		assert_contains(
+			&err.to_string(),
+			"Tool 'no_handler' is missing its ```lua handler block",
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_agent_doc_lex_sections_lines() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_content = r#"# Data
+
+```lua
+return 1
+```
+
+# Tools
+
+## get_time
+
+Get the time
+
+```lua
+return "12:00"
+```
+
+# Output
+
+```lua
+return data
+"#;
+
+		// -- Exec
+		let sections = lex_sections(fx_content);
+
+		// -- Check
+		// the first pseudo section, then Data, Tools, Output
+		assert_eq!(sections.len(), 4);
+		let data = &sections[1];
+		assert!(matches!(data.kind, Some(SectionKind::Data)));
+		assert_eq!(data.heading_line, 1);
+		assert_eq!(data.blocks[0].fence_line, 3);
+		assert_eq!(data.blocks[0].close_line, Some(5));
+		assert_eq!(data.first_block_code("lua").as_deref(), Some("return 1\n"));
+		let tools = &sections[2];
+		assert_eq!(tools.tool_headings, vec![(9, "get_time")]);
+		assert_eq!(tools.blocks[0].tool_line, Some(9));
+		let output = &sections[3];
+		assert_eq!(output.blocks[0].close_line, None, "should be unterminated");
+		assert_eq!(output.blocks[0].lines, vec![(20, "return data")]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_agent_doc_options_env_missing() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_content = r#"
+# Options
+
+```toml
+model = "${env:AIPACK_FX_NOT_A_REAL_VAR}"
+```
+
+# Instruction
+
+Some instruction
+"#;
+		let doc = AgentDoc::from_content("./dummy/path.aip", fx_content)?;
+
+		// -- Exec
+		let res = doc.into_agent(
+			"dummy",
+			AgentRef::LocalPath("./dummy/path.aip".into()),
+			default_agent_config_for_test(),
+		);
+
+		// -- Check
+		let err = res.err().ok_or("Should fail for a missing env variable")?;
+		let err = err.to_string();
+		assert_contains(&err, "Config invalid (config path: ./dummy/path.aip)");
+		assert_contains(&err, "Environment variable 'AIPACK_FX_NOT_A_REAL_VAR' not found");
+
+		Ok(())
 	}
 }
+
+// endregion: --- Tests
@@ -3,19 +3,113 @@
 
 use crate::agent::agent_ref::{AgentRef, PartialAgentRef};
 use crate::agent::{Agent, AgentDoc, AgentOptions};
-use crate::dir_context::{DirContext, PathResolver, find_pack_dirs};
-use crate::pack::LocalPackRef;
+use crate::dir_context::{DirContext, PackDir, PathResolver, find_pack_dirs};
+use crate::pack::{LocalPackRef, PartialPackRef};
+use crate::support::interpolate::interpolate_value;
 use crate::support::tomls::parse_toml;
 use crate::{Error, Result};
 use simple_fs::{SPath, read_to_string};
 
+/// The env variable to select the config profile (when no `--profile`)
+const AIPACK_PROFILE_ENV: &str = "AIPACK_PROFILE";
+
 pub fn find_agent(name: &str, dir_context: &DirContext) -> Result<Agent> {
-	let partial_agent_ref = PartialAgentRef::new(name);
+	find_agent_with_profile(name, dir_context, None)
+}
+
+/// Find the agent, with the `[profiles.<profile>]` config options layered on top of the `[default_options]`.
+///
+/// - When `profile` is None, the `AIPACK_PROFILE` env variable is used (if set)
+pub fn find_agent_with_profile(name: &str, dir_context: &DirContext, profile: Option<&str>) -> Result<Agent> {
+	let env_profile = std::env::var(AIPACK_PROFILE_ENV).ok().filter(|v| !v.trim().is_empty());
+	let profile = profile.or(env_profile.as_deref());
+
+	let base_options = load_and_merge_configs_agent_options(dir_context, profile)?;
+
+	find_agent_with_extends(name, dir_context, &base_options, &mut Vec::new())
+}
 
-	let base_options = load_and_merge_configs_agent_options(dir_context)?;
+/// Find the agent, and if it has an `extends` option, resolve the parent agent (recursively) and inherit from it.
+///
+/// - The options are merged parent then child (the parent options are the base options of the child)
+/// - A local path `extends` is relative to the agent file dir (a pack ref, `ns@pack/...`, is resolved as is)
+/// - `extends_chain` has the agent file paths being resolved, to detect the cycles
+fn find_agent_with_extends(
+	name: &str,
+	dir_context: &DirContext,
+	base_options: &AgentOptions,
+	extends_chain: &mut Vec<String>,
+) -> Result<Agent> {
+	let (found_path, agent_ref) = find_agent_file(name, dir_context)?;
+
+	let found_path_str = found_path.to_str().to_string();
+	if extends_chain.contains(&found_path_str) {
+		extends_chain.push(found_path_str);
+		return Err(Error::custom(format!(
+			"Agent extends cycle detected: {}",
+			extends_chain.join(" -> ")
+		)));
+	}
+	extends_chain.push(found_path_str);
+
+	let (agent_inner, options_ov) =
+		AgentDoc::from_file(&found_path)?.into_agent_inner_and_options_ov(name, agent_ref)?;
+
+	// -- Resolve the parent (its options being the base options of this agent)
+	let parent = match options_ov.as_ref().and_then(|o| o.extends()) {
+		None => None,
+		Some(parent_name) => {
+			let parent_name = resolve_extends_name(parent_name, &found_path)?;
+			Some(find_agent_with_extends(
+				&parent_name,
+				dir_context,
+				base_options,
+				extends_chain,
+			)?)
+		}
+	};
+	let base_options = parent.as_ref().map(|p| p.options_as_ref()).unwrap_or(base_options).clone();
+	let agent_options = match options_ov {
+		Some(options_ov) => base_options.merge(options_ov)?,
+		None => base_options,
+	};
+
+	let agent_inner = agent_inner.with_options(agent_options);
+	let agent = match parent {
+		None => Agent::new(agent_inner)?,
+		Some(parent) => Agent::new(agent_inner.extend_from(&parent)?)?,
+	};
+
+	extends_chain.pop();
+
+	Ok(agent)
+}
+
+/// The agent name of an `extends` value, with the local paths made relative to the agent file dir
+fn resolve_extends_name(extends: &str, agent_path: &SPath) -> Result<String> {
+	let name = match PartialAgentRef::new(extends) {
+		PartialAgentRef::PackRef(_) => extends.to_string(),
+		PartialAgentRef::LocalPath(local_path) => {
+			let local_path = SPath::new(local_path)?;
+			if local_path.path().is_absolute() {
+				local_path.to_str().to_string()
+			} else {
+				let agent_dir = agent_path
+					.parent()
+					.ok_or_else(|| Error::custom(format!("Agent file '{agent_path}' has no parent dir")))?;
+				agent_dir.join(local_path)?.to_str().to_string()
+			}
+		}
+	};
+	Ok(name)
+}
+
+/// Find the agent file path (and its agent_ref) for a given agent name, without parsing it.
+pub fn find_agent_file(name: &str, dir_context: &DirContext) -> Result<(SPath, AgentRef)> {
+	let partial_agent_ref = PartialAgentRef::new(name);
 
 	// -- For now, if end with .aip, we try to find direct
-	let agent = match partial_agent_ref {
+	let res = match partial_agent_ref {
 		PartialAgentRef::LocalPath(local_path) => {
 			let path = dir_context.resolve_path(&local_path, PathResolver::CurrentDir)?;
 			let possible_paths = possible_aip_paths(path.clone(), false);
@@ -26,27 +120,13 @@ pub fn find_agent(name: &str, dir_context: &DirContext) -> Result<Agent> {
 					path.to_str()
 				))
 			})?;
-			let doc = AgentDoc::from_file(found_path)?;
 
 			let agent_ref = AgentRef::LocalPath(local_path.to_string());
 
-			doc.into_agent(name, agent_ref, base_options)?
+			(found_path, agent_ref)
 		}
 		PartialAgentRef::PackRef(pack_ref) => {
-			let pack_dirs = find_pack_dirs(dir_context, pack_ref.namespace.as_deref(), Some(&pack_ref.name))?;
-
-			// -- in case > 1, for now, no support
-			if pack_dirs.len() > 1 {
-				return Err(Error::custom(format!(
-					"{name} matches multiple AI packs across different namespaces.\n\nRun aip run ... with one of the full AI pack references below:\n\n{}\n",
-					pack_dirs.iter().map(|p| p.to_string()).collect::<Vec<String>>().join("\n")
-				)));
-			}
-
-			// -- Get the pack dir
-			let Some(pack_dir) = pack_dirs.into_iter().next() else {
-				return Err(Error::custom(format!("No aipack matches for {pack_ref}.")));
-			};
+			let pack_dir = find_single_pack_dir(name, &pack_ref, dir_context)?;
 
 			// -- Find the aip path
 			// Note: if it is None, the pack_dir, then, we have the as_dir to avoid do the dir.aip
@@ -64,17 +144,65 @@ pub fn find_agent(name: &str, dir_context: &DirContext) -> Result<Agent> {
 			// TODO: Need to cleanup this strategy. Perhaps have PartialPackRef, and PackRef with namespace and pack_name
 			let agent_ref = AgentRef::PackRef(LocalPackRef::from_partial(pack_dir, pack_ref));
 
-			// -- Build and return the agent
-			let doc = AgentDoc::from_file(found_path)?;
-			doc.into_agent(name, agent_ref, base_options)?
+			(found_path, agent_ref)
 		}
 	};
 
-	Ok(agent)
+	Ok(res)
+}
+
+/// Resolve the path of a file referenced by an agent (e.g., `{{include "..."}}`)
+///
+/// - `ns@pack/path/to/file.md` - relative to the pack dir
+/// - `path/to/file.md` - relative to the agent file dir (absolute paths are kept as is)
+pub fn find_agent_relative_file(path: &str, agent: &Agent, dir_context: &DirContext) -> Result<SPath> {
+	let file = match PartialAgentRef::new(path) {
+		PartialAgentRef::PackRef(pack_ref) => {
+			let pack_dir = find_single_pack_dir(path, &pack_ref, dir_context)?;
+			let sub_path = pack_ref
+				.sub_path
+				.as_deref()
+				.ok_or_else(|| Error::custom(format!("'{path}' must have a file path after the pack name")))?;
+			pack_dir.path.join_str(sub_path)
+		}
+		PartialAgentRef::LocalPath(local_path) => {
+			let local_path = SPath::new(local_path)?;
+			if local_path.path().is_absolute() {
+				local_path
+			} else {
+				agent.file_dir()?.join(local_path)?
+			}
+		}
+	};
+
+	if !file.exists() {
+		return Err(Error::custom(format!("File not found: '{file}'")));
+	}
+
+	Ok(file)
 }
 
 // region:    --- Support
 
+/// Returns the single pack dir matching this pack ref (error if none or more than one)
+fn find_single_pack_dir(name: &str, pack_ref: &PartialPackRef, dir_context: &DirContext) -> Result<PackDir> {
+	let pack_dirs = find_pack_dirs(dir_context, pack_ref.namespace.as_deref(), Some(&pack_ref.name))?;
+
+	// -- in case > 1, for now, no support
+	if pack_dirs.len() > 1 {
+		return Err(Error::custom(format!(
+			"{name} matches multiple AI packs across different namespaces.\n\nRun aip run ... with one of the full AI pack references below:\n\n{}\n",
+			pack_dirs.iter().map(|p| p.to_string()).collect::<Vec<String>>().join("\n")
+		)));
+	}
+
+	// -- Get the pack dir
+	pack_dirs
+		.into_iter()
+		.next()
+		.ok_or_else(|| Error::custom(format!("No aipack matches for {pack_ref}.")))
+}
+
 /// Returns the ossible .aip path for a given path
 ///
 /// - `as_dir` allows to treat the path as dir even if it does not end with /
@@ -100,23 +228,49 @@ pub fn possible_aip_paths(path: SPath, as_dir: bool) -> Vec<SPath> {
 
 /// Loads the base agent options.
 ///
-pub fn load_and_merge_configs_agent_options(dir_context: &DirContext) -> Result<AgentOptions> {
+/// - The `[default_options]` of the configs are merged (base config, then workspace config)
+/// - Then, if a `profile` is given, the `[profiles.<profile>]` of the configs are merged on top (same order)
+/// - The `${env:VAR}` and `${keyring:name}` references are resolved on the merged options only
+///   (the other profiles and sections are not resolved)
+pub fn load_and_merge_configs_agent_options(dir_context: &DirContext, profile: Option<&str>) -> Result<AgentOptions> {
 	let config_paths = dir_context.aipack_paths().get_wks_config_toml_paths()?;
 
 	let mut all_options = Vec::new();
+	let mut all_profile_options = Vec::new();
 
-	for config_path in config_paths {
-		let config_content = read_to_string(&config_path)?;
+	for config_path in config_paths.iter() {
+		let config_content = read_to_string(config_path)?;
 		let config_value = parse_toml(&config_content)?;
 
+		// -- Extract the profile options (before the config value is consumed)
+		if let Some(profile_value) = profile.and_then(|profile| config_value.pointer(&format!("/profiles/{profile}"))) {
+			let profile_options =
+				AgentOptions::from_options_value(profile_value.clone()).map_err(|err| Error::Config {
+					path: config_path.to_string(),
+					reason: format!("Invalid [profiles.{}]. Cause: {err}", profile.unwrap_or_default()),
+				})?;
+			all_profile_options.push(profile_options);
+		}
+
 		let options = AgentOptions::from_config_value(config_value).map_err(|err| Error::Config {
 			path: config_path.to_string(),
 			reason: err.to_string(),
 		})?;
 		all_options.push(options);
 	}
+
+	let config_paths_str = config_paths.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(", ");
+	if let Some(profile) = profile {
+		if all_profile_options.is_empty() {
+			return Err(Error::Config {
+				path: config_paths_str,
+				reason: format!("Profile '{profile}' not found. Add a [profiles.{profile}] section"),
+			});
+		}
+	}
+
 	let mut options: Option<AgentOptions> = None;
-	for item_options in all_options {
+	for item_options in all_options.into_iter().chain(all_profile_options) {
 		options = match options {
 			Some(options) => Some(options.merge(item_options)?),
 			None => Some(item_options),
@@ -127,6 +281,14 @@ pub fn load_and_merge_configs_agent_options(dir_context: &DirContext) -> Result<
 		return Err(Error::custom("No agent options found"));
 	};
 
+	// -- Resolve the references of the merged options
+	let mut options_value = serde_json::to_value(options)?;
+	interpolate_value(&mut options_value).map_err(|err| Error::Config {
+		path: config_paths_str,
+		reason: err.to_string(),
+	})?;
+	let options = AgentOptions::from_options_value(options_value)?;
+
 	Ok(options)
 }
 
@@ -238,6 +400,114 @@ mod tests {
 		Ok(())
 	}
 
+	#[test]
+	fn test_agent_locator_find_agent_extends() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let dir_context = runtime.dir_context();
+
+		// -- Exec
+		let agent = find_agent("agent-script/agent-extends-child.aip", dir_context)?;
+
+		// -- Check
+		// options merged parent then child
+		assert_eq!(agent.options_as_ref().model(), Some("gpt-4o-mini"));
+		assert_eq!(agent.options_as_ref().temperature(), Some(0.7));
+		// child prompt parts appended to the parent ones
+		let parts = agent.prompt_parts();
+		assert_eq!(parts.len(), 2);
+		assert_contains(&parts[0].content, "You are the base system.");
+		assert_contains(&parts[1].content, "The child instruction.");
+		// missing section inherited
+		assert_contains(
+			agent.output_script().ok_or("Should have output script")?,
+			"Base output for",
+		);
+		assert_contains(agent.file_path(), "agent-extends-child.aip");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_agent_locator_find_agent_extends_relative_to_agent_dir() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+
+		// -- Exec
+		let agent = find_agent(
+			"agent-script/sub-extends/agent-extends-nested.aip",
+			runtime.dir_context(),
+		)?;
+
+		// -- Check
+		// `extends = "../agent-extends-base.aip"` resolved from the agent dir (not the current dir)
+		assert_eq!(agent.options_as_ref().model(), Some("gpt-4o-mini"));
+		let parts = agent.prompt_parts();
+		assert_eq!(parts.len(), 1);
+		assert_contains(&parts[0].content, "The nested instruction.");
+		assert_contains(
+			agent.output_script().ok_or("Should have output script")?,
+			"Base output for",
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_agent_locator_find_agent_extends_cycle() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+
+		// -- Exec
+		let res = find_agent("agent-script/agent-extends-cycle.aip", runtime.dir_context());
+
+		// -- Check
+		let err = res.err().ok_or("Should fail with a cycle")?;
+		assert_contains(&err.to_string(), "Agent extends cycle detected");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_agent_locator_find_agent_with_profile() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let dir_context = runtime.dir_context();
+
+		// -- Exec
+		let agent = find_agent_with_profile("agent-script/agent-hello.aip", dir_context, Some("ci"))?;
+		let unknown_res = find_agent_with_profile("agent-script/agent-hello.aip", dir_context, Some("nope"));
+
+		// -- Check
+		assert_eq!(agent.options_as_ref().model(), Some("cost-saver"));
+		assert_eq!(agent.options_as_ref().temperature(), Some(0.0));
+		// model aliases from the default_options are still there
+		assert_eq!(agent.model_resolved().to_string(), "deepseek-chat");
+		let err = unknown_res.err().ok_or("Should fail for an unknown profile")?;
+		assert_contains(&err.to_string(), "Profile 'nope' not found");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_agent_locator_find_agent_with_profile_interpolation() -> Result<()> {
+		// -- Setup & Fixtures
+		// Note: The sandbox config has a `[profiles.fx-missing-env]` with a missing `${env:AIPACK_FX_MISSING_ENV}`
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let dir_context = runtime.dir_context();
+
+		// -- Exec
+		let agent = find_agent_with_profile("agent-script/agent-hello.aip", dir_context, Some("ci"))?;
+		let missing_res = find_agent_with_profile("agent-script/agent-hello.aip", dir_context, Some("fx-missing-env"));
+
+		// -- Check
+		assert_eq!(agent.options_as_ref().model(), Some("cost-saver"));
+		let err = missing_res.err().ok_or("Should fail for the selected profile")?;
+		assert_contains(&err.to_string(), "AIPACK_FX_MISSING_ENV");
+
+		Ok(())
+	}
+
 	// endregion: --- find_agent
 
 	// region:    --- possiple_aip_paths
@@ -1,11 +1,22 @@
-use crate::Result;
 use crate::hub::get_hub;
-use genai::chat::ChatOptions;
+use crate::support::interpolate::interpolate_value;
+use crate::{Error, Result};
+use genai::chat::{ChatOptions, ChatResponseFormat, JsonSpec, ReasoningEffort};
+use mlua::LuaSerdeExt as _;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use value_ext::JsonValueExt;
 
+/// The options that apply to the whole run, so cannot be overridden per input (see `from_input_options_value`)
+const RUN_LEVEL_OPTION_KEYS: &[&str] = &[
+	"input_concurrency",
+	"max_cost_usd",
+	"max_tokens_total",
+	"continue_on_error",
+	"stream",
+];
+
 /// Configuration for the Agent, defined in `.aipack/config.toml` and
 /// optionally overridden in the `# Config` section of the Command Agent Markdown.
 ///
@@ -20,21 +31,85 @@ pub struct AgentOptions {
 
 	temperature: Option<f64>,
 
+	// Genai chat options (sent to the provider when supported)
+	max_tokens: Option<u32>,
+	top_p: Option<f64>,
+	stop_sequences: Option<Vec<String>>,
+	// `low`, `medium`, or `high` (for the reasoning models)
+	reasoning_effort: Option<String>,
+
 	// Runtime settings
 	input_concurrency: Option<usize>,
 
+	// When true, the AI response is streamed to the hub as it comes
+	stream: Option<bool>,
+
+	// Max number of AI turns per input when the output returns `aipack.follow_up(..)`
+	max_turns: Option<usize>,
+
+	// JSON Schema of the expected AI response (structured output, validated, and parsed to `ai_response.json`)
+	output_schema: Option<Value>,
+
+	// Max number of retries of an AI call on transient errors (e.g., 429, 5xx)
+	retry_max: Option<usize>,
+
+	// Initial delay before the first retry (doubled on each following retry)
+	retry_backoff_ms: Option<u64>,
+
+	// Ordered models (or aliases) to fall back to when the model still fails after the retries
+	fallback_models: Option<Vec<String>>,
+
+	// Budget of the run (all inputs). Once reached, no new input is started.
+	max_cost_usd: Option<f64>,
+	max_tokens_total: Option<u64>,
+
+	// When true, the AI responses are cached in `.aipack/.cache/` (keyed on model, chat options, and messages)
+	cache: Option<bool>,
+
+	// When true, a failed input does not stop the run (errors are given to the `# After All` stage)
+	continue_on_error: Option<bool>,
+
+	// The parent agent (e.g., `ns@pack/base-agent`) whose sections are inherited when missing in this agent.
+	// Note: Per agent file, so not inherited on merge (same for `extends_prompt`)
+	extends: Option<String>,
+
+	// How the prompt parts of this agent are combined with the parent ones: `replace` (default), `prepend`, or `append`
+	extends_prompt: Option<String>,
+
 	model_aliases: Option<ModelAliases>,
 }
 
 // region:    --- Froms
 
-impl From<&AgentOptions> for ChatOptions {
-	fn from(agent_options: &AgentOptions) -> Self {
+impl TryFrom<&AgentOptions> for ChatOptions {
+	type Error = Error;
+
+	fn try_from(agent_options: &AgentOptions) -> Result<Self> {
 		let mut chat_options = ChatOptions::default();
 		if let Some(temp) = agent_options.temperature() {
 			chat_options.temperature = Some(temp);
 		}
-		chat_options
+		if let Some(max_tokens) = agent_options.max_tokens() {
+			chat_options.max_tokens = Some(max_tokens);
+		}
+		if let Some(top_p) = agent_options.top_p() {
+			chat_options.top_p = Some(top_p);
+		}
+		if let Some(stop_sequences) = agent_options.stop_sequences() {
+			chat_options.stop_sequences = stop_sequences.to_vec();
+		}
+		if let Some(reasoning_effort) = agent_options.reasoning_effort() {
+			let reasoning_effort = ReasoningEffort::from_lower_str(reasoning_effort).ok_or_else(|| {
+				Error::custom(format!(
+					"reasoning_effort '{reasoning_effort}' is invalid. Must be 'low', 'medium', or 'high'"
+				))
+			})?;
+			chat_options.reasoning_effort = Some(reasoning_effort);
+		}
+		if let Some(schema) = agent_options.output_schema() {
+			chat_options.response_format = Some(ChatResponseFormat::JsonSpec(JsonSpec::new("output", schema.clone())));
+		}
+		Ok(chat_options)
 	}
 }
 
@@ -117,10 +192,16 @@ impl AgentOptions {
 	pub fn resolve_model(&self) -> Option<&str> {
 		let model = self.model.as_deref()?;
 
-		match self.model_aliases {
-			Some(_) => self.get_model_for_alias(model).or(Some(model)),
-			None => Some(model),
-		}
+		Some(self.resolve_model_alias(model))
+	}
+
+	/// Returns the resolved fallback models (empty if none)
+	pub fn resolve_fallback_models(&self) -> Vec<&str> {
+		self.fallback_models
+			.iter()
+			.flatten()
+			.map(|model| self.resolve_model_alias(model))
+			.collect()
 	}
 
 	pub fn input_concurrency(&self) -> Option<usize> {
@@ -131,7 +212,74 @@ impl AgentOptions {
 		self.temperature
 	}
 
-	#[allow(unused)]
+	pub fn max_tokens(&self) -> Option<u32> {
+		self.max_tokens
+	}
+
+	pub fn top_p(&self) -> Option<f64> {
+		self.top_p
+	}
+
+	pub fn stop_sequences(&self) -> Option<&[String]> {
+		self.stop_sequences.as_deref()
+	}
+
+	pub fn reasoning_effort(&self) -> Option<&str> {
+		self.reasoning_effort.as_deref()
+	}
+
+	pub fn stream(&self) -> Option<bool> {
+		self.stream
+	}
+
+	pub fn max_turns(&self) -> Option<usize> {
+		self.max_turns
+	}
+
+	pub fn output_schema(&self) -> Option<&Value> {
+		self.output_schema.as_ref()
+	}
+
+	pub fn retry_max(&self) -> Option<usize> {
+		self.retry_max
+	}
+
+	pub fn retry_backoff_ms(&self) -> Option<u64> {
+		self.retry_backoff_ms
+	}
+
+	pub fn fallback_models(&self) -> Option<&[String]> {
+		self.fallback_models.as_deref()
+	}
+
+	pub fn max_cost_usd(&self) -> Option<f64> {
+		self.max_cost_usd
+	}
+
+	pub fn max_tokens_total(&self) -> Option<u64> {
+		self.max_tokens_total
+	}
+
+	pub fn cache(&self) -> Option<bool> {
+		self.cache
+	}
+
+	pub fn continue_on_error(&self) -> Option<bool> {
+		self.continue_on_error
+	}
+
+	pub fn extends(&self) -> Option<&str> {
+		self.extends.as_deref()
+	}
+
+	pub fn extends_prompt(&self) -> Option<&str> {
+		self.extends_prompt.as_deref()
+	}
+
+	fn resolve_model_alias<'a>(&'a self, model: &'a str) -> &'a str {
+		self.get_model_for_alias(model).unwrap_or(model)
+	}
+
 	fn get_model_for_alias(&self, alias: &str) -> Option<&str> {
 		self.model_aliases
 			.as_ref()
@@ -162,6 +310,29 @@ impl AgentOptions {
 		Ok(options)
 	}
 
+	/// Creates a new `AgentOptions` from the options returned by a script (e.g., `aipack.before_all_response`),
+	/// with the `${env:VAR}` and `${keyring:name}` references resolved (like the `# Options` section).
+	pub fn from_script_options_value(mut value: Value) -> Result<AgentOptions> {
+		interpolate_value(&mut value)?;
+		Self::from_options_value(value)
+	}
+
+	/// Same as `from_script_options_value`, for the options of a single input (`aipack.data_response` of the Data stage).
+	///
+	/// Note: The run level options (see `RUN_LEVEL_OPTION_KEYS`) are an error, as they cannot apply per input.
+	pub fn from_input_options_value(value: Value) -> Result<AgentOptions> {
+		if let Some(key) = value
+			.as_object()
+			.and_then(|obj| RUN_LEVEL_OPTION_KEYS.iter().find(|key| obj.contains_key(**key)))
+		{
+			return Err(Error::custom(format!(
+				"The '{key}' option applies to the whole run, so it cannot be set per input (in the Data stage `aipack.data_response` options). \
+Set it in the agent '# Options' (or in the Before All `aipack.before_all_response` options)."
+			)));
+		}
+		Self::from_script_options_value(value)
+	}
+
 	/// Merge the current options with a new options value, returning the merged `AgentOptions`.
 	///
 	/// Note: This will consume both, avoiding any new allocation
@@ -175,7 +346,23 @@ impl AgentOptions {
 			legacy: options_ov.legacy, // only take the value of the legacy
 			model: options_ov.model.or(self.model),
 			temperature: options_ov.temperature.or(self.temperature),
+			max_tokens: options_ov.max_tokens.or(self.max_tokens),
+			top_p: options_ov.top_p.or(self.top_p),
+			stop_sequences: options_ov.stop_sequences.or(self.stop_sequences),
+			reasoning_effort: options_ov.reasoning_effort.or(self.reasoning_effort),
 			input_concurrency: options_ov.input_concurrency.or(self.input_concurrency),
+			stream: options_ov.stream.or(self.stream),
+			max_turns: options_ov.max_turns.or(self.max_turns),
+			output_schema: options_ov.output_schema.or(self.output_schema),
+			retry_max: options_ov.retry_max.or(self.retry_max),
+			retry_backoff_ms: options_ov.retry_backoff_ms.or(self.retry_backoff_ms),
+			fallback_models: options_ov.fallback_models.or(self.fallback_models),
+			max_cost_usd: options_ov.max_cost_usd.or(self.max_cost_usd),
+			max_tokens_total: options_ov.max_tokens_total.or(self.max_tokens_total),
+			cache: options_ov.cache.or(self.cache),
+			continue_on_error: options_ov.continue_on_error.or(self.continue_on_error),
+			extends: options_ov.extends,
+			extends_prompt: options_ov.extends_prompt,
 			model_aliases,
 		})
 	}
@@ -190,7 +377,23 @@ impl AgentOptions {
 			legacy: options_ov.legacy, // only take the value of the legacy
 			model: options_ov.model.or(self.model.clone()),
 			temperature: options_ov.temperature.or(self.temperature),
+			max_tokens: options_ov.max_tokens.or(self.max_tokens),
+			top_p: options_ov.top_p.or(self.top_p),
+			stop_sequences: options_ov.stop_sequences.or(self.stop_sequences.clone()),
+			reasoning_effort: options_ov.reasoning_effort.or(self.reasoning_effort.clone()),
 			input_concurrency: options_ov.input_concurrency.or(self.input_concurrency),
+			stream: options_ov.stream.or(self.stream),
+			max_turns: options_ov.max_turns.or(self.max_turns),
+			output_schema: options_ov.output_schema.or(self.output_schema.clone()),
+			retry_max: options_ov.retry_max.or(self.retry_max),
+			retry_backoff_ms: options_ov.retry_backoff_ms.or(self.retry_backoff_ms),
+			fallback_models: options_ov.fallback_models.or(self.fallback_models.clone()),
+			max_cost_usd: options_ov.max_cost_usd.or(self.max_cost_usd),
+			max_tokens_total: options_ov.max_tokens_total.or(self.max_tokens_total),
+			cache: options_ov.cache.or(self.cache),
+			continue_on_error: options_ov.continue_on_error.or(self.continue_on_error),
+			extends: options_ov.extends,
+			extends_prompt: options_ov.extends_prompt,
 			model_aliases,
 		})
 	}
@@ -204,7 +407,24 @@ impl mlua::IntoLua for &AgentOptions {
 		table.set("model", self.model())?;
 		table.set("resolved_model", self.resolve_model())?;
 		table.set("temperature", self.temperature)?;
+		table.set("max_tokens", self.max_tokens)?;
+		table.set("top_p", self.top_p)?;
+		table.set("stop_sequences", self.stop_sequences.clone())?;
+		table.set("reasoning_effort", self.reasoning_effort.clone())?;
 		table.set("input_concurrency", self.input_concurrency)?;
+		table.set("stream", self.stream)?;
+		table.set("max_turns", self.max_turns)?;
+		let output_schema = self.output_schema.as_ref().map(|v| lua.to_value(v)).transpose()?;
+		table.set("output_schema", output_schema)?;
+		table.set("retry_max", self.retry_max)?;
+		table.set("retry_backoff_ms", self.retry_backoff_ms)?;
+		table.set("fallback_models", self.fallback_models.clone())?;
+		table.set("max_cost_usd", self.max_cost_usd)?;
+		table.set("max_tokens_total", self.max_tokens_total)?;
+		table.set("cache", self.cache)?;
+		table.set("continue_on_error", self.continue_on_error)?;
+		table.set("extends", self.extends.clone())?;
+		table.set("extends_prompt", self.extends_prompt.clone())?;
 
 		let model_aliases = self.model_aliases.as_ref();
 		table.set("model_aliases", model_aliases)?;
@@ -218,7 +438,24 @@ impl mlua::FromLua for AgentOptions {
 		if let mlua::Value::Table(table) = value {
 			let model = table.get::<Option<String>>("model")?;
 			let temperature = table.get::<Option<f64>>("temperature")?;
+			let max_tokens = table.get::<Option<u32>>("max_tokens")?;
+			let top_p = table.get::<Option<f64>>("top_p")?;
+			let stop_sequences = table.get::<Option<Vec<String>>>("stop_sequences")?;
+			let reasoning_effort = table.get::<Option<String>>("reasoning_effort")?;
 			let input_concurrency = table.get::<Option<usize>>("input_concurrency")?;
+			let stream = table.get::<Option<bool>>("stream")?;
+			let max_turns = table.get::<Option<usize>>("max_turns")?;
+			let output_schema = table.get::<Option<mlua::Value>>("output_schema")?;
+			let output_schema = output_schema.map(|v| lua.from_value::<Value>(v)).transpose()?;
+			let retry_max = table.get::<Option<usize>>("retry_max")?;
+			let retry_backoff_ms = table.get::<Option<u64>>("retry_backoff_ms")?;
+			let fallback_models = table.get::<Option<Vec<String>>>("fallback_models")?;
+			let max_cost_usd = table.get::<Option<f64>>("max_cost_usd")?;
+			let max_tokens_total = table.get::<Option<u64>>("max_tokens_total")?;
+			let cache = table.get::<Option<bool>>("cache")?;
+			let continue_on_error = table.get::<Option<bool>>("continue_on_error")?;
+			let extends = table.get::<Option<String>>("extends")?;
+			let extends_prompt = table.get::<Option<String>>("extends_prompt")?;
 
 			// --
 			let model_aliases = table.get::<Option<mlua::Value>>("model_aliases")?;
@@ -228,7 +465,23 @@ impl mlua::FromLua for AgentOptions {
 				legacy: false,
 				model,
 				temperature,
+				max_tokens,
+				top_p,
+				stop_sequences,
+				reasoning_effort,
 				input_concurrency,
+				stream,
+				max_turns,
+				output_schema,
+				retry_max,
+				retry_backoff_ms,
+				fallback_models,
+				max_cost_usd,
+				max_tokens_total,
+				cache,
+				continue_on_error,
+				extends,
+				extends_prompt,
 				model_aliases,
 			};
 
@@ -298,7 +551,23 @@ To update your config.toml:
 			legacy: true,
 			model,
 			temperature,
+			max_tokens: None,
+			top_p: None,
+			stop_sequences: None,
+			reasoning_effort: None,
 			input_concurrency,
+			stream: None,
+			max_turns: None,
+			output_schema: None,
+			retry_max: None,
+			retry_backoff_ms: None,
+			fallback_models: None,
+			max_cost_usd: None,
+			max_tokens_total: None,
+			cache: None,
+			continue_on_error: None,
+			extends: None,
+			extends_prompt: None,
 			model_aliases: None,
 		})
 	}
@@ -315,7 +584,23 @@ impl AgentOptions {
 			legacy: false,
 			model: Some(model_name.into()),
 			temperature: None,
+			max_tokens: None,
+			top_p: None,
+			stop_sequences: None,
+			reasoning_effort: None,
 			input_concurrency: None,
+			stream: None,
+			max_turns: None,
+			output_schema: None,
+			retry_max: None,
+			retry_backoff_ms: None,
+			fallback_models: None,
+			max_cost_usd: None,
+			max_tokens_total: None,
+			cache: None,
+			continue_on_error: None,
+			extends: None,
+			extends_prompt: None,
 			model_aliases: None,
 		}
 	}
@@ -330,6 +615,7 @@ mod tests {
 	use super::*;
 	use crate::support::tomls::parse_toml;
 	use mlua::{FromLua, IntoLua};
+	use serde_json::json;
 
 	#[test]
 	fn test_options_current_with_aliases() -> Result<()> {
@@ -395,6 +681,7 @@ return {
 			r#"
 	model = "gpt-4o-mini"
 	temperature = 0.3
+	stream = true
 	model_aliases = { small = "flash-001" }		
 		"#,
 		)?;
@@ -407,12 +694,126 @@ return {
 		let options_table = options_lua.as_table().ok_or("Should be a table")?;
 		assert_eq!(&options_table.get::<String>("model")?, "gpt-4o-mini");
 		assert_eq!(options_table.get::<f64>("temperature")?, 0.3);
+		assert!(options_table.get::<bool>("stream")?, "stream should be true");
 		let aliases_table = options_table.get::<mlua::Value>("model_aliases")?;
 		let aliases_table = aliases_table.as_table().ok_or("model_aliases should be table")?;
 		assert_eq!(&aliases_table.get::<String>("small")?, "flash-001");
 
 		Ok(())
 	}
+
+	#[test]
+	fn test_options_output_schema() -> Result<()> {
+		// -- Setup & Fixtures
+		let options = parse_toml(
+			r#"
+	model = "gpt-4o-mini"
+	output_schema = { type = "object", properties = { title = { type = "string" } }, required = ["title"] }
+		"#,
+		)?;
+		let options = AgentOptions::from_options_value(options)?;
+
+		// -- Exec
+		let chat_options = ChatOptions::try_from(&options)?;
+
+		// -- Check
+		let schema = options.output_schema().ok_or("Should have output_schema")?;
+		assert_eq!(schema.x_get_str("/properties/title/type")?, "string");
+		let Some(ChatResponseFormat::JsonSpec(json_spec)) = chat_options.response_format else {
+			return Err("Should have a JsonSpec response_format".into());
+		};
+		assert_eq!(&json_spec.schema, schema);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_options_fallback_models_resolve() -> Result<()> {
+		// -- Setup & Fixtures
+		let options = parse_toml(
+			r#"
+	model = "small"
+	retry_max = 3
+	fallback_models = ["big", "gpt-4o"]
+	model_aliases = { small = "gpt-4o-mini", big = "claude-3-7-sonnet-latest" }
+		"#,
+		)?;
+		let options = AgentOptions::from_options_value(options)?;
+
+		// -- Exec
+		let fallback_models = options.resolve_fallback_models();
+
+		// -- Check
+		assert_eq!(options.resolve_model(), Some("gpt-4o-mini"));
+		assert_eq!(options.retry_max(), Some(3));
+		assert_eq!(fallback_models, vec!["claude-3-7-sonnet-latest", "gpt-4o"]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_options_chat_options_merge() -> Result<()> {
+		// -- Setup & Fixtures
+		let base = AgentOptions::from_options_value(parse_toml(
+			r#"
+	model = "gpt-4o-mini"
+	max_tokens = 1000
+	top_p = 0.9
+	stop_sequences = ["END"]
+		"#,
+		)?)?;
+		let options_ov = AgentOptions::from_options_value(parse_toml(
+			r#"
+	max_tokens = 2000
+	reasoning_effort = "high"
+		"#,
+		)?)?;
+
+		// -- Exec
+		let options = base.merge_new(options_ov)?;
+		let chat_options = ChatOptions::try_from(&options)?;
+
+		// -- Check
+		assert_eq!(chat_options.max_tokens, Some(2000));
+		assert_eq!(chat_options.top_p, Some(0.9));
+		assert_eq!(chat_options.stop_sequences, vec!["END".to_string()]);
+		assert!(matches!(chat_options.reasoning_effort, Some(ReasoningEffort::High)));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_options_from_input_options_value() -> Result<()> {
+		// -- Setup & Fixtures
+		let home = std::env::var("HOME")?;
+
+		// -- Exec
+		let options = AgentOptions::from_input_options_value(json!({"model": "${env:HOME}", "temperature": 0.3}))?;
+		let run_level_res = AgentOptions::from_input_options_value(json!({"model": "gpt-4o", "max_cost_usd": 1.0}));
+
+		// -- Check
+		assert_eq!(options.model(), Some(home.as_str()));
+		assert_eq!(options.temperature(), Some(0.3));
+		let err = run_level_res.err().ok_or("Should fail for a run level option")?;
+		assert!(err.to_string().contains("The 'max_cost_usd' option applies to the whole run"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_options_reasoning_effort_invalid() -> Result<()> {
+		// -- Setup & Fixtures
+		let options = AgentOptions::from_options_value(parse_toml(r#"reasoning_effort = "max""#)?)?;
+
+		// -- Exec
+		let res = ChatOptions::try_from(&options);
+
+		// -- Check
+		let err = res.err().ok_or("Should fail for an invalid reasoning_effort")?;
+		assert!(err.to_string().contains("reasoning_effort 'max' is invalid"));
+
+		Ok(())
+	}
 }
 
 // endregion: --- Tests
@@ -0,0 +1,32 @@
+use genai::chat::Tool;
+use serde_json::Value;
+
+/// A tool declared in the `# Tools` section of the agent file.
+///
+/// Each tool is a `## tool_name` sub heading, with an optional description (text below the heading),
+/// an optional ```` ```json ```` block for the parameters JSON schema, and a required ```` ```lua ```` block for the handler.
+#[derive(Debug, Clone)]
+pub struct AgentTool {
+	pub name: String,
+	pub description: Option<String>,
+	pub schema: Option<Value>,
+	/// The Lua handler script (will have `args` in scope)
+	pub script: String,
+}
+
+// region:    --- Froms
+
+impl From<&AgentTool> for Tool {
+	fn from(agent_tool: &AgentTool) -> Self {
+		let mut tool = Tool::new(agent_tool.name.clone());
+		if let Some(description) = agent_tool.description.as_ref() {
+			tool = tool.with_description(description.clone());
+		}
+		if let Some(schema) = agent_tool.schema.as_ref() {
+			tool = tool.with_schema(schema.clone());
+		}
+		tool
+	}
+}
+
+// endregion: --- Froms
@@ -42,6 +42,18 @@ pub enum CliCommand {
 
 	/// Install an aipack file
 	Install(InstallArgs),
+
+	/// Check an agent file (sections, code blocks, options, lua and handlebars syntax) without running it
+	Check(CheckArgs),
+
+	/// Manage the API keys of the `~/.aipack-base/credentials.toml` store (`aip key set OPENAI_API_KEY`)
+	Key(KeyArgs),
+
+	/// Apply the file changes recorded by a `--dry plan` run (`aip apply` for the latest, or `aip apply <run-id>`)
+	Apply(ApplyArgs),
+
+	/// Restore the files written by a run (`aip undo` for the latest, or `aip undo <run-id>`)
+	Undo(UndoArgs),
 }
 
 /// Custom function
@@ -58,6 +70,10 @@ impl CliCommand {
 			CliCommand::List(_) => false,
 			CliCommand::Pack(_) => false,
 			CliCommand::Install(_) => false,
+			CliCommand::Check(_) => false,
+			CliCommand::Key(_) => false,
+			CliCommand::Apply(_) => false,
+			CliCommand::Undo(_) => false,
 		}
 	}
 }
@@ -96,13 +112,50 @@ pub struct RunArgs {
 	#[arg(short = 'o', long = "open")]
 	pub open: bool,
 
-	/// Dry mode, takes either 'req' or 'res'
-	#[arg(long = "dry", value_parser = ["req", "res"])]
+	/// Dry mode, takes either 'req', 'res', or 'plan' (file writes recorded and shown as diffs, see `aip apply`)
+	#[arg(long = "dry", value_parser = ["req", "res", "plan"])]
 	pub dry_mode: Option<String>,
 
 	/// Non-interactive mode (one-shot execution)
 	#[arg(long = "not-interactive", alias = "ni")]
 	pub not_interactive: bool,
+
+	/// Stage the file writes, and review (accept or reject) each change at the end of the run
+	#[arg(long = "review")]
+	pub review: bool,
+
+	/// With `--review`, write all the staged changes without prompting (for the non-interactive mode)
+	#[arg(long = "auto-approve")]
+	pub auto_approve: bool,
+
+	/// Write the end-of-run summary report as JSON to this file path (e.g., `--report report.json`)
+	#[arg(long = "report")]
+	pub report: Option<String>,
+
+	/// Do not use the AI response cache (when the agent has the `cache = true` option)
+	#[arg(long = "no-cache")]
+	pub no_cache: bool,
+
+	/// Ignore the cached AI responses, but update the cache with the new ones
+	#[arg(long = "refresh-cache")]
+	pub refresh_cache: bool,
+
+	/// Continue the run when an input fails, and report the errors at the end
+	#[arg(long = "keep-going")]
+	pub keep_going: bool,
+
+	/// Record the completed inputs in the run journal, to resume the run if interrupted (with `--resume <run-id>`)
+	#[arg(long = "journal")]
+	pub journal: bool,
+
+	/// Resume an interrupted run, skipping the inputs already completed (run id from `.aipack/.runs/`)
+	#[arg(long = "resume")]
+	pub resume: Option<String>,
+
+	/// The config profile to layer on top of the `[default_options]` (e.g., `--profile ci` for `[profiles.ci]`)
+	/// If absent, the `AIPACK_PROFILE` env variable is used (when set)
+	#[arg(long = "profile")]
+	pub profile: Option<String>,
 }
 
 /// Arguments for the `pack` subcommand
@@ -126,6 +179,44 @@ pub struct InstallArgs {
 	pub aipack_ref: String,
 }
 
+/// Arguments for the `check` subcommand
+#[derive(Parser, Debug)]
+pub struct CheckArgs {
+	/// The name of the agent (same as `aip run`), e.g., `demo@proof` or `path/to/agent.aip`
+	pub cmd_agent_name: String,
+}
+
+/// Arguments for the `key` subcommand
+#[derive(Parser, Debug)]
+pub struct KeyArgs {
+	#[command(subcommand)]
+	pub cmd: KeyCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum KeyCommand {
+	/// Set the value of a key (read from stdin), e.g., `aip key set OPENAI_API_KEY`
+	Set { key_name: String },
+	/// List the key names (not the values)
+	List,
+	/// Remove a key
+	Remove { key_name: String },
+}
+
+/// Arguments for the `apply` subcommand
+#[derive(Parser, Debug)]
+pub struct ApplyArgs {
+	/// The run id of the plan to apply (defaults to the latest run with a plan)
+	pub run_id: Option<String>,
+}
+
+/// Arguments for the `undo` subcommand
+#[derive(Parser, Debug)]
+pub struct UndoArgs {
+	/// The run id to undo (defaults to the latest run with file backups, not already undone)
+	pub run_id: Option<String>,
+}
+
 /// Arguments for the `run` subcommand
 #[derive(Parser, Debug)]
 pub struct ListArgs {
@@ -173,6 +264,10 @@ impl From<CliCommand> for ExecCommand {
 			CliCommand::List(list_args) => ExecCommand::List(list_args),
 			CliCommand::Pack(pack_args) => ExecCommand::Pack(pack_args),
 			CliCommand::Install(install_args) => ExecCommand::Install(install_args),
+			CliCommand::Check(check_args) => ExecCommand::Check(check_args),
+			CliCommand::Key(key_args) => ExecCommand::Key(key_args),
+			CliCommand::Apply(apply_args) => ExecCommand::Apply(apply_args),
+			CliCommand::Undo(undo_args) => ExecCommand::Undo(undo_args),
 		}
 	}
 }
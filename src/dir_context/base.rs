@@ -1,4 +1,4 @@
-use super::AipackPaths;
+use super::{AipackPaths, WritePolicy};
 use crate::Result;
 use crate::support::files::current_dir;
 use simple_fs::SPath;
@@ -20,6 +20,9 @@ pub struct DirContext {
 
 	/// This is workspace `.aipack/`
 	aipack_paths: AipackPaths,
+
+	/// The `[write_policy]` of the config files (checked by `resolve_write_path`)
+	write_policy: WritePolicy,
 }
 
 /// Constructor/Loader
@@ -33,17 +36,21 @@ impl DirContext {
 	/// Note: Only the test function will provide a mock current_dir
 	fn from_aipack_dir_and_current_dir(aipack_dir: AipackPaths, current_dir: SPath) -> Result<Self> {
 		let current_dir = current_dir.canonicalize()?;
+		let write_policy = load_write_policy(&aipack_dir)?;
 		Ok(Self {
 			current_dir,
 			aipack_paths: aipack_dir,
+			write_policy,
 		})
 	}
 
 	#[cfg(test)]
 	pub fn from_current_and_aipack_paths(current_dir: SPath, aipack_paths: AipackPaths) -> Result<Self> {
+		let write_policy = load_write_policy(&aipack_paths)?;
 		Ok(Self {
 			current_dir,
 			aipack_paths,
+			write_policy,
 		})
 	}
 }
@@ -62,6 +69,10 @@ impl DirContext {
 	pub fn wks_dir(&self) -> &SPath {
 		self.aipack_paths().wks_dir()
 	}
+
+	pub fn write_policy(&self) -> &WritePolicy {
+		&self.write_policy
+	}
 }
 
 /// Resolvers
@@ -79,4 +90,20 @@ impl DirContext {
 			}
 		}
 	}
+
+	/// Resolve the path of a file to be written (same as `resolve_path`),
+	/// and check it against the write policy (returns a `Error::WritePolicy` if blocked)
+	pub fn resolve_write_path(&self, path: impl AsRef<Path>, mode: PathResolver) -> Result<SPath> {
+		let path = self.resolve_path(path, mode)?;
+		self.write_policy.check(&path)
+	}
 }
+
+// region:    --- Support
+
+fn load_write_policy(aipack_paths: &AipackPaths) -> Result<WritePolicy> {
+	let config_paths = aipack_paths.get_wks_config_toml_paths()?;
+	WritePolicy::from_config_files(aipack_paths.wks_dir(), &config_paths)
+}
+
+// endregion: --- Support
@@ -4,9 +4,11 @@ mod aipack_paths;
 mod base;
 mod pack_dir;
 mod path_consts;
+mod write_policy;
 
 pub use aipack_paths::*;
 pub use base::*;
 pub use pack_dir::*;
+pub use write_policy::*;
 
 // endregion: --- Modules
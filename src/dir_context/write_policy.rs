@@ -0,0 +1,303 @@
+use crate::support::tomls::parse_toml;
+use crate::{Error, Result};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use simple_fs::{SPath, read_to_string};
+use std::path::{Component, Path, PathBuf};
+
+const DEFAULT_ALLOWED_ROOTS: &[&str] = &["."];
+const DEFAULT_DENIED_GLOBS: &[&str] = &[".git/**"];
+
+/// The write policy of the Lua file functions (`utils.file.save`, `.append`, `.ensure_exists`, and `utils.git.restore`)
+/// from the `[write_policy]` of the config files (the workspace config overrides the base one, per property).
+///
+/// ```toml
+/// [write_policy]
+/// allowed_roots = ["."]          # relative to the workspace dir (or absolute)
+/// denied_globs  = [".git/**"]    # relative to the workspace dir
+/// read_only     = false
+/// ```
+///
+/// Note: The paths are checked after the lexical normalization of the `..`, and with the symlinks of their
+///       existing parent dirs resolved (so that a symlink cannot escape the allowed roots).
+#[derive(Debug, Clone)]
+pub struct WritePolicy {
+	wks_dir: SPath,
+	/// The resolved allowed roots (see `resolve_path`)
+	allowed_roots: Vec<PathBuf>,
+	denied_globs: Vec<String>,
+	denied_set: GlobSet,
+	read_only: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WritePolicyConfig {
+	allowed_roots: Option<Vec<String>>,
+	denied_globs: Option<Vec<String>>,
+	read_only: Option<bool>,
+}
+
+/// Constructors
+impl WritePolicy {
+	/// Load the `[write_policy]` of the config files (in order, later ones override)
+	pub fn from_config_files(wks_dir: &SPath, config_paths: &[SPath]) -> Result<Self> {
+		let mut config = WritePolicyConfig::default();
+
+		for config_path in config_paths {
+			if !config_path.exists() {
+				continue;
+			}
+			let config_value = parse_toml(&read_to_string(config_path)?)?;
+			let Some(policy_value) = config_value.get("write_policy").cloned() else {
+				continue;
+			};
+			let file_config: WritePolicyConfig = serde_json::from_value(policy_value).map_err(|err| Error::Config {
+				path: config_path.to_string(),
+				reason: format!("Invalid [write_policy]. Cause: {err}"),
+			})?;
+
+			if file_config.allowed_roots.is_some() {
+				config.allowed_roots = file_config.allowed_roots;
+			}
+			if file_config.denied_globs.is_some() {
+				config.denied_globs = file_config.denied_globs;
+			}
+			if file_config.read_only.is_some() {
+				config.read_only = file_config.read_only;
+			}
+		}
+
+		let to_strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+		Self::new(
+			wks_dir,
+			config.allowed_roots.unwrap_or_else(|| to_strings(DEFAULT_ALLOWED_ROOTS)),
+			config.denied_globs.unwrap_or_else(|| to_strings(DEFAULT_DENIED_GLOBS)),
+			config.read_only.unwrap_or(false),
+		)
+	}
+
+	pub fn new(
+		wks_dir: &SPath,
+		allowed_roots: Vec<String>,
+		denied_globs: Vec<String>,
+		read_only: bool,
+	) -> Result<Self> {
+		let allowed_roots = allowed_roots
+			.iter()
+			.map(|root| resolve_path(&normalize_path(&wks_dir.path().join(root))))
+			.collect();
+
+		let mut set_builder = GlobSetBuilder::new();
+		for glob in denied_globs.iter() {
+			set_builder.add(new_glob(glob)?);
+		}
+		let denied_set = set_builder
+			.build()
+			.map_err(|err| Error::custom(format!("Invalid [write_policy] denied_globs. Cause: {err}")))?;
+
+		Ok(Self {
+			wks_dir: wks_dir.clone(),
+			allowed_roots,
+			denied_globs,
+			denied_set,
+			read_only,
+		})
+	}
+}
+
+impl WritePolicy {
+	/// Check that the (resolved) path can be written.
+	/// Returns the normalized path, or a `Error::WritePolicy` with the rule that blocked it.
+	///
+	/// Note: The allowed roots are checked on the path with its symlinks resolved (the file written),
+	///       and the denied globs on both the normalized and the symlinks resolved paths.
+	pub fn check(&self, path: &SPath) -> Result<SPath> {
+		let normalized = normalize_path(path.path());
+		let resolved = resolve_path(&normalized);
+		let blocked = |rule: String| Error::WritePolicy {
+			path: path.to_string(),
+			rule,
+		};
+
+		if self.read_only {
+			return Err(blocked("read_only = true".to_string()));
+		}
+
+		if !self.allowed_roots.iter().any(|root| resolved.starts_with(root)) {
+			let roots = self
+				.allowed_roots
+				.iter()
+				.map(|root| format!("'{}'", root.to_string_lossy()))
+				.collect::<Vec<_>>()
+				.join(", ");
+			return Err(blocked(format!("allowed_roots (not under {roots})")));
+		}
+
+		// The denied globs are relative to the workspace dir (absolute for the paths outside of it)
+		let wks_dir = normalize_path(self.wks_dir.path());
+		let resolved_wks_dir = resolve_path(&wks_dir);
+		let match_paths = [
+			normalized.strip_prefix(&wks_dir).unwrap_or(&normalized),
+			resolved.strip_prefix(&resolved_wks_dir).unwrap_or(&resolved),
+		];
+		for match_path in match_paths {
+			if let Some(idx) = self.denied_set.matches(match_path).first() {
+				return Err(blocked(format!("denied_globs '{}'", self.denied_globs[*idx])));
+			}
+		}
+
+		Ok(SPath::from_path(normalized)?)
+	}
+}
+
+// region:    --- Support
+
+fn new_glob(glob: &str) -> Result<Glob> {
+	GlobBuilder::new(glob)
+		.literal_separator(true)
+		.build()
+		.map_err(|err| Error::custom(format!("Invalid [write_policy] denied_globs '{glob}'. Cause: {err}")))
+}
+
+/// Lexical normalization (removes the `.` and resolves the `..`), without touching the file system
+fn normalize_path(path: &Path) -> PathBuf {
+	let mut res = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::CurDir => (),
+			Component::ParentDir => {
+				if !res.pop() {
+					res.push("..");
+				}
+			}
+			other => res.push(other.as_os_str()),
+		}
+	}
+	res
+}
+
+/// Resolve the symlinks of the (normalized) path, by canonicalizing its longest existing ancestor
+/// (the missing components are appended as is, since they will be created as regular dirs/files)
+fn resolve_path(normalized: &Path) -> PathBuf {
+	for ancestor in normalized.ancestors() {
+		let Ok(canonical) = std::fs::canonicalize(ancestor) else {
+			continue;
+		};
+		// Note: The ancestor is a prefix of the path, so the strip cannot fail
+		let rest = normalized.strip_prefix(ancestor).unwrap_or(Path::new(""));
+		return if rest.as_os_str().is_empty() {
+			canonical
+		} else {
+			canonical.join(rest)
+		};
+	}
+	normalized.to_path_buf()
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::assert_contains;
+
+	#[test]
+	fn test_write_policy_check() -> Result<()> {
+		// -- Setup & Fixtures
+		let wks_dir = SPath::new("/fx/wks")?;
+		let policy = WritePolicy::new(
+			&wks_dir,
+			vec![".".to_string(), "/fx/shared".to_string()],
+			vec![".git/**".to_string(), "**/*.lock".to_string()],
+			false,
+		)?;
+
+		// -- Exec & Check
+		assert_eq!(
+			policy.check(&SPath::new("/fx/wks/src/./main.rs")?)?.to_str(),
+			"/fx/wks/src/main.rs"
+		);
+		assert_eq!(
+			policy.check(&SPath::new("/fx/shared/notes.md")?)?.to_str(),
+			"/fx/shared/notes.md"
+		);
+
+		let err = policy
+			.check(&SPath::new("/fx/wks/../other/file.txt")?)
+			.err()
+			.ok_or("Should be outside")?;
+		assert_contains(&err.to_string(), "allowed_roots");
+
+		let err = policy
+			.check(&SPath::new("/fx/wks/.git/config")?)
+			.err()
+			.ok_or("Should be denied")?;
+		assert_contains(&err.to_string(), "denied_globs '.git/**'");
+
+		let err = policy
+			.check(&SPath::new("/fx/wks/sub/Cargo.lock")?)
+			.err()
+			.ok_or("Should be denied")?;
+		assert_contains(&err.to_string(), "denied_globs '**/*.lock'");
+
+		Ok(())
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_write_policy_check_symlink_escape() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_dir = std::env::temp_dir().join(format!("aipack-test-write-policy-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&fx_dir);
+		std::fs::create_dir_all(fx_dir.join("wks/sub"))?;
+		std::fs::create_dir_all(fx_dir.join("wks/.git"))?;
+		std::fs::create_dir_all(fx_dir.join("outside"))?;
+		std::os::unix::fs::symlink(fx_dir.join("outside"), fx_dir.join("wks/link"))?;
+		std::os::unix::fs::symlink(fx_dir.join("wks/.git"), fx_dir.join("wks/git-link"))?;
+		let wks_dir = SPath::from_path(fx_dir.join("wks"))?;
+		let policy = WritePolicy::new(&wks_dir, vec![".".to_string()], vec![".git/**".to_string()], false)?;
+
+		// -- Exec
+		let res_ok = policy.check(&wks_dir.join_str("sub/new-dir/file.txt"));
+		let res_link = policy.check(&wks_dir.join_str("link/file.txt"));
+		let res_link_new = policy.check(&wks_dir.join_str("link/new-dir/file.txt"));
+		let res_git_link = policy.check(&wks_dir.join_str("git-link/config"));
+
+		// -- Clean
+		let _ = std::fs::remove_dir_all(&fx_dir);
+
+		// -- Check
+		assert_eq!(res_ok?.to_str(), wks_dir.join_str("sub/new-dir/file.txt").to_str());
+		let err = res_link.err().ok_or("Should not escape with the symlink")?;
+		assert_contains(&err.to_string(), "allowed_roots");
+		let err = res_link_new.err().ok_or("Should not escape with the symlink")?;
+		assert_contains(&err.to_string(), "allowed_roots");
+		let err = res_git_link.err().ok_or("Should be denied with the symlink")?;
+		assert_contains(&err.to_string(), "denied_globs '.git/**'");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_write_policy_check_read_only() -> Result<()> {
+		// -- Setup & Fixtures
+		let wks_dir = SPath::new("/fx/wks")?;
+		let policy = WritePolicy::new(&wks_dir, vec![".".to_string()], Vec::new(), true)?;
+
+		// -- Exec
+		let res = policy.check(&SPath::new("/fx/wks/README.md")?);
+
+		// -- Check
+		let err = res.err().ok_or("Should be read only")?;
+		assert_contains(&err.to_string(), "read_only = true");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
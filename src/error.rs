@@ -24,6 +24,26 @@ pub enum Error {
 		reason: String,
 	},
 
+	// -- Write Policy
+	#[display("Write blocked by the write policy (path: {path})\n  rule: {rule}")]
+	WritePolicy {
+		path: String,
+		rule: String,
+	},
+
+	// -- Cmd
+	#[display("Command blocked by the cmd policy (command: {cmd})\n  rule: {rule}")]
+	CmdNotAllowed {
+		cmd: String,
+		rule: String,
+	},
+
+	#[display("Command timed out after {timeout_ms}ms and was killed (command: {cmd})")]
+	CmdTimeout {
+		cmd: String,
+		timeout_ms: u64,
+	},
+
 	// -- Pack
 	InvalidPackIdentity {
 		origin_path: String,
@@ -53,6 +73,12 @@ pub enum Error {
 		cause: String,
 	},
 
+	#[display("AI response does not match the output_schema\n  cause: {cause}\n  response: {snippet}")]
+	OutputSchemaValidation {
+		cause: String,
+		snippet: String,
+	},
+
 	// -- TokioSync
 	TokioTryCurrent(TryCurrentError),
 
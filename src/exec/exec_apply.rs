@@ -0,0 +1,32 @@
+use crate::Result;
+use crate::cli::ApplyArgs;
+use crate::dir_context::DirContext;
+use crate::hub::get_hub;
+use crate::run::apply_plan;
+
+/// Exec for the Apply command
+/// Applies the plan of a `--dry plan` run (the changes of the files modified since the plan are not applied)
+pub async fn exec_apply(dir_context: DirContext, apply_args: ApplyArgs) -> Result<()> {
+	let hub = get_hub();
+
+	let report = apply_plan(&dir_context, apply_args.run_id.as_deref())?;
+
+	let mut msg = format!("==== Apply plan of run: {}\n", report.run_id);
+	for path in report.applied.iter() {
+		msg.push_str(&format!("-> Applied:  {path}\n"));
+	}
+	for (path, reason) in report.conflicts.iter() {
+		msg.push_str(&format!("-! Conflict: {path} ({reason})\n"));
+	}
+	msg.push_str(&format!(
+		"\n{} applied, {} conflict(s)",
+		report.applied.len(),
+		report.conflicts.len()
+	));
+	if !report.applied.is_empty() {
+		msg.push_str(&format!("\n   To undo it: aip undo {}", report.run_id));
+	}
+	hub.publish(msg).await;
+
+	Ok(())
+}
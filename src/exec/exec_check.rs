@@ -0,0 +1,81 @@
+use crate::agent::{check_agent_content, find_agent_file};
+use crate::cli::CheckArgs;
+use crate::dir_context::DirContext;
+use crate::hub::get_hub;
+use crate::{Error, Result};
+use simple_fs::read_to_string;
+
+/// Exec for the Check command
+/// Reports the diagnostics of the agent file (without running it)
+/// Returns an error (so, a non-zero exit code) when the agent is not found or has issues
+pub async fn exec_check(dir_context: DirContext, check_args: CheckArgs) -> Result<()> {
+	let hub = get_hub();
+
+	let (agent_path, _agent_ref) = find_agent_file(&check_args.cmd_agent_name, &dir_context)?;
+
+	let content = read_to_string(&agent_path)?;
+	let diags = check_agent_content(&content);
+
+	if diags.is_empty() {
+		hub.publish(format!("\nNo issues found in '{agent_path}'")).await;
+		return Ok(());
+	}
+
+	let mut msg = format!("\n{} issue(s) found in '{agent_path}':\n", diags.len());
+	for diag in diags.iter() {
+		msg.push_str(&format!("\n{agent_path}:{}: {}", diag.line, diag.message));
+	}
+	hub.publish(msg).await;
+
+	Err(Error::custom(format!(
+		"Agent check failed, {} issue(s) found in '{agent_path}'",
+		diags.len()
+	)))
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::assert_contains;
+	use crate::run::Runtime;
+	use clap::Parser;
+
+	#[tokio::test]
+	async fn test_exec_check_issues_err() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let dir_context = runtime.dir_context().clone();
+		let fx_dir = dir_context.wks_dir().join_str(".tmp/test_exec_check_issues_err");
+		simple_fs::ensure_dir(&fx_dir)?;
+		std::fs::write(
+			fx_dir.join_str("agent-ok.aip").path(),
+			"# Data\n\n```lua\nreturn 1\n```\n",
+		)?;
+		std::fs::write(
+			fx_dir.join_str("agent-bad.aip").path(),
+			"# Ouput\n\n```lua\nreturn 1\n```\n",
+		)?;
+		let ok_args = CheckArgs::try_parse_from(["check", "./.tmp/test_exec_check_issues_err/agent-ok.aip"])?;
+		let bad_args = CheckArgs::try_parse_from(["check", "./.tmp/test_exec_check_issues_err/agent-bad.aip"])?;
+		let missing_args = CheckArgs::try_parse_from(["check", "./.tmp/test_exec_check_issues_err/nope.aip"])?;
+
+		// -- Exec
+		let ok_res = exec_check(dir_context.clone(), ok_args).await;
+		let bad_res = exec_check(dir_context.clone(), bad_args).await;
+		let missing_res = exec_check(dir_context, missing_args).await;
+
+		// -- Check
+		assert!(ok_res.is_ok(), "should have no issues: {ok_res:?}");
+		let err = bad_res.err().ok_or("Should fail with issues")?;
+		assert_contains(&err.to_string(), "1 issue(s) found");
+		assert!(missing_res.is_err(), "should fail when the agent is not found");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
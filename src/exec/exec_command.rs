@@ -2,7 +2,9 @@
 //! Note: For now, the content of the variant of the ExecCommand often contain the CliArgs,
 //!       but this will eventual change to have it's own
 
-use crate::cli::{InitArgs, ListArgs, NewArgs, PackArgs, RunArgs, InstallArgs};
+use crate::cli::{
+	ApplyArgs, CheckArgs, InitArgs, InstallArgs, KeyArgs, ListArgs, NewArgs, PackArgs, RunArgs, UndoArgs,
+};
 
 /// This is the Executor Command that needs to be performed
 /// NOTE: This is not the `ExecStateEvent` which is sent to the hub.
@@ -16,6 +18,10 @@ pub enum ExecCommand {
 	List(ListArgs),
 	Pack(PackArgs),
 	Install(InstallArgs),
+	Check(CheckArgs),
+	Key(KeyArgs),
+	Apply(ApplyArgs),
+	Undo(UndoArgs),
 	Redo,
 	OpenAgent,
 }
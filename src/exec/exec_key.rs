@@ -0,0 +1,60 @@
+use crate::cli::{KeyArgs, KeyCommand};
+use crate::hub::get_hub;
+use crate::support::cred_store::CredStore;
+use crate::{Error, Result};
+use std::io::{self, IsTerminal as _};
+
+/// Exec for the Key command
+/// Manages the keys of the `~/.aipack-base/credentials.toml` store (the values are never printed)
+pub async fn exec_key(key_args: KeyArgs) -> Result<()> {
+	let hub = get_hub();
+	let store = CredStore::from_base_dir()?;
+
+	match key_args.cmd {
+		KeyCommand::Set { key_name } => {
+			let value = prompt_key_value(&key_name)?;
+			store.set(&key_name, &value)?;
+			hub.publish(format!("Key '{key_name}' saved in the credentials store")).await;
+		}
+		KeyCommand::List => {
+			let names = store.list()?;
+			if names.is_empty() {
+				hub.publish("No keys in the credentials store (add one with `aip key set <KEY_NAME>`)")
+					.await;
+			} else {
+				let names = names.iter().map(|n| format!("- {n}")).collect::<Vec<_>>().join("\n");
+				hub.publish(format!("Keys in the credentials store:\n{names}")).await;
+			}
+		}
+		KeyCommand::Remove { key_name } => {
+			if store.remove(&key_name)? {
+				hub.publish(format!("Key '{key_name}' removed from the credentials store"))
+					.await;
+			} else {
+				hub.publish(format!("Key '{key_name}' not found in the credentials store"))
+					.await;
+			}
+		}
+	}
+
+	Ok(())
+}
+
+/// Read the key value from stdin (works with a pipe, e.g., `echo $KEY | aip key set OPENAI_API_KEY`)
+/// - On a terminal, the value is not echoed
+fn prompt_key_value(key_name: &str) -> Result<String> {
+	let input = if io::stdin().is_terminal() {
+		rpassword::prompt_password(format!("Enter the value for '{key_name}' (not displayed): "))?
+	} else {
+		let mut input = String::new();
+		io::stdin().read_line(&mut input)?;
+		input
+	};
+
+	let value = input.trim().to_string();
+	if value.is_empty() {
+		return Err(Error::custom("Value cannot be empty."));
+	}
+
+	Ok(value)
+}
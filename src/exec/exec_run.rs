@@ -1,5 +1,5 @@
 use super::support::open_vscode;
-use crate::agent::{Agent, find_agent};
+use crate::agent::{Agent, find_agent_with_profile};
 use crate::cli::RunArgs;
 use crate::dir_context::DirContext;
 use crate::hub::{HubEvent, get_hub}; // Importing get_hub
@@ -59,22 +59,26 @@ pub async fn exec_run(run_args: RunArgs, dir_context: DirContext) -> Result<Arc<
 pub async fn exec_run_first(run_args: RunArgs, dir_context: DirContext) -> Result<RunRedoCtx> {
 	let hub = get_hub();
 
-	let cmd_agent_name = &run_args.cmd_agent_name;
+	let cmd_agent_name = run_args.cmd_agent_name.clone();
 
 	let runtime = Runtime::new(dir_context)?;
 
-	let agent = find_agent(cmd_agent_name, runtime.dir_context())?;
-
 	let run_options = RunCommandOptions::new(run_args)?;
 
+	let agent = find_agent_with_profile(&cmd_agent_name, runtime.dir_context(), run_options.profile())?;
+
 	if run_options.base_run_config().open() {
 		open_vscode(agent.file_path()).await;
 	}
 
-	match do_run(&run_options, &runtime, &agent).await {
-		Ok(_) => (),
-		Err(err) => hub.publish(format!("ERROR: {}", err)).await,
-	};
+	if let Err(err) = do_run(&run_options, &runtime, &agent).await {
+		// Note: When not interactive, the run error is the command result (so the process exits with a non-zero code)
+		//       Otherwise, it is published, and the run can be redone.
+		if !run_options.base_run_config().interactive() {
+			return Err(err);
+		}
+		hub.publish(format!("ERROR: {}", err)).await;
+	}
 
 	Ok(RunRedoCtx {
 		runtime,
@@ -95,7 +99,7 @@ pub async fn exec_run_redo(run_redo_ctx: &RunRedoCtx) -> Option<RunRedoCtx> {
 	} = run_redo_ctx;
 
 	// make sure to reload the agent
-	let agent = match find_agent(agent.name(), runtime.dir_context()) {
+	let agent = match find_agent_with_profile(agent.name(), runtime.dir_context(), run_options.profile()) {
 		Ok(agent) => agent,
 		Err(err) => {
 			hub.publish(err).await;
@@ -196,7 +200,7 @@ async fn do_run(run_command_options: &RunCommandOptions, runtime: &Runtime, agen
 		None
 	};
 
-	run_command_agent(
+	let run_res = run_command_agent(
 		runtime,
 		agent.clone(),
 		inputs,
@@ -205,5 +209,57 @@ async fn do_run(run_command_options: &RunCommandOptions, runtime: &Runtime, agen
 	)
 	.await?;
 
+	// -- When continue on error, the run fails at the end if some inputs failed
+	if !run_res.errors.is_empty() {
+		let labels = run_res.errors.iter().map(|e| e.label.as_str()).collect::<Vec<_>>().join(", ");
+		return Err(Error::custom(format!(
+			"{} input(s) failed: {labels}",
+			run_res.errors.len()
+		)));
+	}
+
 	Ok(())
 }
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::assert_contains;
+	use clap::Parser;
+
+	#[tokio::test]
+	async fn test_exec_run_first_failed_inputs_err() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let dir_context = runtime.dir_context().clone();
+		let fx_agent = dir_context
+			.wks_dir()
+			.join_str(".tmp/test_exec_run_first_failed_inputs_err/agent-fail.aip");
+		simple_fs::ensure_file_dir(&fx_agent)?;
+		std::fs::write(fx_agent.path(), "# Data\n\n```lua\nerror(\"input failed\")\n```\n")?;
+		let run_args = RunArgs::try_parse_from([
+			"run",
+			"./.tmp/test_exec_run_first_failed_inputs_err/agent-fail.aip",
+			"-i",
+			"one",
+			"--keep-going",
+			"--ni",
+		])?;
+
+		// -- Exec
+		let res = exec_run_first(run_args, dir_context).await;
+
+		// -- Check
+		// Note: Not interactive, so the error is returned (for the non-zero exit code)
+		let err = res.err().ok_or("failed inputs should fail the run")?;
+		assert_contains(&err.to_string(), "1 input(s) failed");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
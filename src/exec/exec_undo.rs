@@ -0,0 +1,33 @@
+use crate::Result;
+use crate::cli::UndoArgs;
+use crate::dir_context::DirContext;
+use crate::hub::get_hub;
+use crate::run::undo_run;
+
+/// Exec for the Undo command
+/// Restores the files written by a run (the files modified since the run are not restored)
+pub async fn exec_undo(dir_context: DirContext, undo_args: UndoArgs) -> Result<()> {
+	let hub = get_hub();
+
+	let report = undo_run(&dir_context, undo_args.run_id.as_deref())?;
+
+	let mut msg = format!("==== Undo run: {}\n", report.run_id);
+	for path in report.restored.iter() {
+		msg.push_str(&format!("-> Restored: {path}\n"));
+	}
+	for path in report.removed.iter() {
+		msg.push_str(&format!("-> Removed:  {path}\n"));
+	}
+	for (path, reason) in report.conflicts.iter() {
+		msg.push_str(&format!("-! Conflict: {path} ({reason})\n"));
+	}
+	msg.push_str(&format!(
+		"\n{} restored, {} removed, {} conflict(s)",
+		report.restored.len(),
+		report.removed.len(),
+		report.conflicts.len()
+	));
+	hub.publish(msg).await;
+
+	Ok(())
+}
@@ -4,7 +4,10 @@
 use crate::agent::Agent;
 use crate::exec::exec_command::ExecCommand;
 use crate::exec::support::open_vscode;
-use crate::exec::{ExecEvent, RunRedoCtx, exec_install, exec_list, exec_new, exec_pack, exec_run, exec_run_redo};
+use crate::exec::{
+	ExecEvent, RunRedoCtx, exec_apply, exec_check, exec_install, exec_key, exec_list, exec_new, exec_pack, exec_run,
+	exec_run_redo, exec_undo,
+};
 use crate::hub::get_hub;
 use crate::init::{init_base, init_wks};
 use crate::{Error, Result};
@@ -98,6 +101,14 @@ impl Executor {
 
 				ExecCommand::Install(install_args) => exec_install(init_wks(None, false).await?, install_args).await?,
 
+				ExecCommand::Check(check_args) => exec_check(init_wks(None, false).await?, check_args).await?,
+
+				ExecCommand::Key(key_args) => exec_key(key_args).await?,
+
+				ExecCommand::Apply(apply_args) => exec_apply(init_wks(None, false).await?, apply_args).await?,
+
+				ExecCommand::Undo(undo_args) => exec_undo(init_wks(None, false).await?, undo_args).await?,
+
 				ExecCommand::RunCommandAgent(run_args) => {
 					hub.publish(ExecEvent::RunStart).await;
 					let redo = exec_run(run_args, init_wks(None, false).await?).await?;
@@ -1,17 +1,25 @@
 // region:    --- Modules
 
+mod exec_apply;
+mod exec_check;
+mod exec_install;
+mod exec_key;
 mod exec_list;
 mod exec_new;
 mod exec_pack;
 mod exec_run;
-mod exec_install;
+mod exec_undo;
 mod support;
 
+use exec_apply::*;
+use exec_check::*;
+use exec_install::*;
+use exec_key::*;
 use exec_list::*;
 use exec_new::*;
 use exec_pack::*;
 use exec_run::*;
-use exec_install::*;
+use exec_undo::*;
 
 mod exec_command;
 mod exec_event;
@@ -20,5 +28,7 @@ mod executor;
 pub use exec_command::*;
 pub use exec_event::*;
 pub use executor::*;
+// Note: Also used by the run review (open the change in the editor)
+pub use support::open_vscode;
 
 // endregion: --- Modules
@@ -1,5 +1,6 @@
 use crate::Error;
 use crate::exec::ExecEvent;
+use crate::run::ReviewRequest;
 use derive_more::derive::From;
 use std::sync::Arc;
 
@@ -26,6 +27,14 @@ pub enum HubEvent {
 	// -- Sent by the lua engine "print override"
 	LuaPrint(Arc<str>),
 
+	// -- Sent by the run when the ai response is streamed (`stream = true` option)
+	// Note: The chunks are raw text, without any new line added.
+	AiStreamChunk(Arc<str>),
+
+	// -- Sent by the run when a staged file change needs a user decision (`--review`)
+	// The tui displays the diff, and answers with `ReviewRequest::decide`.
+	Review(Arc<ReviewRequest>),
+
 	// -- Action event
 	// for now, the watches send and event to the hub,
 	// which will trigger the app to send it to the executor.
@@ -26,6 +26,7 @@ use crate::tui::TuiApp;
 use clap::{Parser, crate_version};
 use error::{Error, Result};
 use std::time::Duration;
+use tokio::sync::oneshot;
 
 pub static VERSION: &str = crate_version!();
 
@@ -39,9 +40,12 @@ async fn main() -> Result<()> {
 	// -- Start executor
 	let mut executor = Executor::new();
 	let executor_tx = executor.command_tx();
+	// Note: The executor failure is sent before the Quit, so it is received once the UI is done
+	let (exec_failed_tx, mut exec_failed_rx) = oneshot::channel::<()>();
 	// TODO: Probably want to move the spwn inside executor.start
 	tokio::spawn(async move {
 		if let Err(err) = executor.start().await {
+			let _ = exec_failed_tx.send(());
 			let hub = get_hub();
 			hub.publish(HubEvent::Error { error: err.into() }).await;
 			hub.publish(HubEvent::Quit).await;
@@ -61,5 +65,10 @@ async fn main() -> Result<()> {
 	tokio::time::sleep(Duration::from_millis(100)).await;
 	println!("\n     ---- Until next one, happy coding! ----");
 
+	// -- Exit with a non-zero code when the executor failed (e.g., some inputs of a non-interactive run failed)
+	if exec_failed_rx.try_recv().is_ok() {
+		std::process::exit(1);
+	}
+
 	Ok(())
 }
@@ -0,0 +1,272 @@
+use crate::dir_context::DirContext;
+use crate::support::tomls::parse_toml;
+use crate::{Error, Result};
+use serde::Deserialize;
+use simple_fs::read_to_string;
+use std::path::Path;
+
+/// The policy of `utils.cmd.exec` from the `[cmd_policy]` of the config files
+/// (the workspace config overrides the base one, per property).
+///
+/// ```toml
+/// [cmd_policy]
+/// allow      = ["git", "cargo"]  # when set, only these commands can be executed
+/// deny       = ["rm"]            # these commands can never be executed (checked first)
+/// timeout_ms = 60000             # default timeout (none if absent)
+/// ```
+///
+/// Notes:
+/// - The commands are matched on their file name (e.g., `/bin/rm` matches `rm`), not on their arguments,
+///   without the `.exe` extension (e.g., `rm.exe` matches `rm`), and case-insensitively on Windows.
+///   So a command launcher (e.g., `sh -c "rm ..."`, `env rm ...`) could run a denied command.
+///   For this reason, when `deny` is set, the launchers (see `LAUNCHER_CMDS`) are denied as well,
+///   unless they are explicitly in `allow`.
+/// - `deny` is a best effort guard, the `allow` list is the recommended way to restrict the commands.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CmdPolicy {
+	allow: Option<Vec<String>>,
+	deny: Option<Vec<String>>,
+	timeout_ms: Option<u64>,
+}
+
+/// The commands that can run other commands (denied when `deny` is set, unless in `allow`)
+const LAUNCHER_CMDS: &[&str] = &[
+	"sh",
+	"bash",
+	"zsh",
+	"dash",
+	"ksh",
+	"csh",
+	"tcsh",
+	"fish",
+	"env",
+	"xargs",
+	"sudo",
+	"doas",
+	"nohup",
+	"nice",
+	"timeout",
+	"busybox",
+	"cmd",
+	"powershell",
+	"pwsh",
+];
+
+/// Constructors
+impl CmdPolicy {
+	#[cfg(test)]
+	pub fn new(allow: Option<Vec<String>>, deny: Option<Vec<String>>, timeout_ms: Option<u64>) -> Self {
+		Self {
+			allow,
+			deny,
+			timeout_ms,
+		}
+	}
+}
+
+/// Getters
+impl CmdPolicy {
+	/// The default timeout of the commands (when not set in the `utils.cmd.exec` options)
+	pub fn timeout_ms(&self) -> Option<u64> {
+		self.timeout_ms
+	}
+}
+
+impl CmdPolicy {
+	/// Check that the command can be executed.
+	/// Returns a `Error::CmdNotAllowed` with the rule that blocked it.
+	pub fn check(&self, cmd_name: &str) -> Result<()> {
+		self.check_with_case(cmd_name, cfg!(windows))
+	}
+
+	/// - `ignore_case` to match the command names case-insensitively (as on Windows)
+	fn check_with_case(&self, cmd_name: &str, ignore_case: bool) -> Result<()> {
+		let file_name = Path::new(cmd_name)
+			.file_name()
+			.and_then(|name| name.to_str())
+			.unwrap_or(cmd_name);
+		let name = normalize_cmd_name(file_name, ignore_case);
+		let matches = |names: &[String]| names.iter().any(|other| normalize_cmd_name(other, ignore_case) == name);
+		let blocked = |rule: String| Error::CmdNotAllowed {
+			cmd: cmd_name.to_string(),
+			rule,
+		};
+
+		let in_allow = self.allow.as_ref().is_some_and(|allow| matches(allow));
+
+		if let Some(deny) = self.deny.as_ref() {
+			if matches(deny) {
+				return Err(blocked(format!("deny '{name}'")));
+			}
+			if !in_allow && LAUNCHER_CMDS.contains(&name.as_str()) {
+				return Err(blocked(format!(
+					"deny (the command launcher '{name}' can run denied commands, add it to allow to use it)"
+				)));
+			}
+		}
+
+		if let Some(allow) = self.allow.as_ref() {
+			if !in_allow {
+				return Err(blocked(format!("allow (not in [{}])", allow.join(", "))));
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// The command name to match, without the `.exe` extension (any case), and lower case if `ignore_case`
+fn normalize_cmd_name(name: &str, ignore_case: bool) -> String {
+	let name = match name.len().checked_sub(4) {
+		Some(idx) if name.is_char_boundary(idx) && name[idx..].eq_ignore_ascii_case(".exe") => &name[..idx],
+		_ => name,
+	};
+	if ignore_case {
+		name.to_lowercase()
+	} else {
+		name.to_string()
+	}
+}
+
+/// Load the `[cmd_policy]` of the config files (in order, later ones override per property)
+pub fn load_cmd_policy(dir_context: &DirContext) -> Result<CmdPolicy> {
+	let mut policy = CmdPolicy::default();
+
+	for config_path in dir_context.aipack_paths().get_wks_config_toml_paths()? {
+		if !config_path.exists() {
+			continue;
+		}
+		let config_value = parse_toml(&read_to_string(&config_path)?)?;
+		let Some(policy_value) = config_value.get("cmd_policy").cloned() else {
+			continue;
+		};
+		let file_policy: CmdPolicy = serde_json::from_value(policy_value).map_err(|err| Error::Config {
+			path: config_path.to_string(),
+			reason: format!("Invalid [cmd_policy]. Cause: {err}"),
+		})?;
+
+		if file_policy.allow.is_some() {
+			policy.allow = file_policy.allow;
+		}
+		if file_policy.deny.is_some() {
+			policy.deny = file_policy.deny;
+		}
+		if file_policy.timeout_ms.is_some() {
+			policy.timeout_ms = file_policy.timeout_ms;
+		}
+	}
+
+	Ok(policy)
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::assert_contains;
+
+	#[test]
+	fn test_cmd_policy_check() -> Result<()> {
+		// -- Setup & Fixtures
+		let policy = CmdPolicy::new(
+			Some(vec!["git".to_string(), "rm".to_string()]),
+			Some(vec!["rm".to_string()]),
+			None,
+		);
+
+		// -- Exec & Check
+		policy.check("git")?;
+		policy.check("/usr/bin/git")?;
+
+		let err = policy.check("/bin/rm").err().ok_or("Should be denied")?;
+		assert_contains(&err.to_string(), "deny 'rm'");
+
+		let err = policy.check("curl").err().ok_or("Should not be allowed")?;
+		assert_contains(&err.to_string(), "allow (not in [git, rm])");
+
+		CmdPolicy::default().check("curl")?;
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_cmd_policy_check_launchers() -> Result<()> {
+		// -- Setup & Fixtures
+		let deny_only = CmdPolicy::new(None, Some(vec!["rm".to_string()]), None);
+		let deny_allow_sh = CmdPolicy::new(
+			Some(vec!["sh".to_string(), "git".to_string()]),
+			Some(vec!["rm".to_string()]),
+			None,
+		);
+
+		// -- Exec & Check
+		let err = deny_only.check("sh").err().ok_or("sh should be denied")?;
+		assert_contains(&err.to_string(), "command launcher 'sh'");
+		let err = deny_only.check("/usr/bin/env").err().ok_or("env should be denied")?;
+		assert_contains(&err.to_string(), "command launcher 'env'");
+		deny_only.check("git")?;
+
+		deny_allow_sh.check("sh")?;
+		let err = deny_allow_sh.check("bash").err().ok_or("bash should be denied")?;
+		assert_contains(&err.to_string(), "command launcher 'bash'");
+
+		// no deny, no launcher restriction
+		CmdPolicy::default().check("sh")?;
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_cmd_policy_check_exe_suffix() -> Result<()> {
+		// -- Setup & Fixtures
+		let policy = CmdPolicy::new(
+			Some(vec!["git".to_string(), "rm".to_string()]),
+			Some(vec!["rm".to_string()]),
+			None,
+		);
+
+		// -- Exec & Check
+		let err = policy.check("rm.exe").err().ok_or("rm.exe should be denied")?;
+		assert_contains(&err.to_string(), "deny 'rm'");
+		let err = policy.check("C:/bin/rm.EXE").err().ok_or("rm.EXE should be denied")?;
+		assert_contains(&err.to_string(), "deny 'rm'");
+		policy.check("git.exe")?;
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_cmd_policy_check_ignore_case() -> Result<()> {
+		// -- Setup & Fixtures
+		let policy = CmdPolicy::new(
+			Some(vec!["Git".to_string(), "rm".to_string()]),
+			Some(vec!["rm".to_string()]),
+			None,
+		);
+
+		// -- Exec & Check
+		// Note: As on Windows
+		let err = policy.check_with_case("RM", true).err().ok_or("RM should be denied")?;
+		assert_contains(&err.to_string(), "deny 'rm'");
+		let err = policy.check_with_case("Rm.Exe", true).err().ok_or("Rm.Exe should be denied")?;
+		assert_contains(&err.to_string(), "deny 'rm'");
+		let err = policy
+			.check_with_case("CMD.EXE", true)
+			.err()
+			.ok_or("CMD.EXE should be denied")?;
+		assert_contains(&err.to_string(), "command launcher 'cmd'");
+		policy.check_with_case("GIT.exe", true)?;
+
+		// case-sensitive otherwise
+		let err = policy.check_with_case("RM", false).err().ok_or("RM should not be allowed")?;
+		assert_contains(&err.to_string(), "allow (not in [Git, rm])");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -1,14 +1,211 @@
 //! Module about AI support functions.
 
-use crate::Result;
+use crate::dir_context::DirContext;
 use crate::support::cred::get_or_prompt_api_key;
+use crate::support::cred_store::CredStore;
+use crate::support::interpolate::interpolate_value;
+use crate::support::tomls::parse_toml;
+use crate::{Error, Result};
+use genai::adapter::AdapterKind;
 use genai::chat::ChatOptions;
-use genai::resolver::AuthData;
-use genai::{Client, ModelIden};
+use genai::resolver::{AuthData, Endpoint};
+use genai::{Client, ModelIden, ServiceTarget};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use simple_fs::read_to_string;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
-pub fn get_genai_client() -> Result<genai::Client> {
+/// The separator between the provider name and the model name (e.g., `myproxy::gpt-4o`)
+const PROVIDER_MODEL_SEP: &str = "::";
+
+// region:    --- ProviderConfig
+
+/// A custom provider endpoint, from the `[providers.<name>]` of the `config.toml`
+///
+/// ```toml
+/// [providers.myproxy]
+/// base_url     = "https://llm-proxy.company.com/v1/"
+/// adapter_kind = "openai"                  # (default "openai", for OpenAI-compatible endpoints)
+/// key_env      = "MYPROXY_API_KEY"         # or `api_key = "${keyring:myproxy}"`
+/// headers      = { "X-Team" = "platform" }
+/// ```
+///
+/// Used with the model names `myproxy::gpt-4o`.
+///
+/// Note: The `${env:VAR}` and `${keyring:name}` references are resolved when the provider is used
+///       (so an unused provider with a missing reference does not fail the runs).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ProviderConfig {
+	base_url: String,
+	adapter_kind: Option<String>,
+	/// The env variable name of the api key
+	key_env: Option<String>,
+	/// The api key (can be interpolated, e.g., `${keyring:name}`)
+	api_key: Option<String>,
+	headers: Option<HashMap<String, String>>,
+}
+
+impl ProviderConfig {
+	/// Returns the provider config with the `${env:VAR}` and `${keyring:name}` references resolved
+	fn interpolated(&self, provider_name: &str) -> Result<ProviderConfig> {
+		let mut value = serde_json::to_value(self)?;
+		interpolate_value(&mut value)
+			.map_err(|err| Error::custom(format!("Invalid [providers.{provider_name}]. Cause: {err}")))?;
+		Ok(serde_json::from_value(value)?)
+	}
+
+	fn adapter_kind(&self) -> Result<AdapterKind> {
+		let adapter_kind = match self.adapter_kind.as_deref().unwrap_or("openai").to_lowercase().as_str() {
+			"openai" => AdapterKind::OpenAI,
+			"ollama" => AdapterKind::Ollama,
+			"anthropic" => AdapterKind::Anthropic,
+			"cohere" => AdapterKind::Cohere,
+			"gemini" => AdapterKind::Gemini,
+			"groq" => AdapterKind::Groq,
+			"xai" => AdapterKind::Xai,
+			"deepseek" => AdapterKind::DeepSeek,
+			other => return Err(format!("adapter_kind '{other}' is not supported").into()),
+		};
+		Ok(adapter_kind)
+	}
+
+	/// The base url, always ending with `/` (as the adapters append the service path to it)
+	fn endpoint(&self) -> Endpoint {
+		if self.base_url.ends_with('/') {
+			Endpoint::from_owned(self.base_url.clone())
+		} else {
+			Endpoint::from_owned(format!("{}/", self.base_url))
+		}
+	}
+
+	fn auth(&self, provider_name: &str) -> Result<AuthData> {
+		if let Some(api_key) = self.api_key.as_ref() {
+			return Ok(AuthData::from_single(api_key.clone()));
+		}
+		match self.key_env.as_deref() {
+			Some(key_env) => {
+				let key = match std::env::var(key_env).ok() {
+					Some(key) => Some(key),
+					None => CredStore::from_base_dir()?.get(key_env)?,
+				};
+				key.map(AuthData::from_single).ok_or_else(|| {
+					Error::custom(format!(
+						"Environment variable '{key_env}' (key_env of provider '{provider_name}') is not set (or 'aip key set {key_env}')."
+					))
+				})
+			}
+			// e.g., local vLLM without auth
+			None => Ok(AuthData::from_single("")),
+		}
+	}
+
+	fn header_map(&self) -> Result<Option<HeaderMap>> {
+		let Some(headers) = self.headers.as_ref().filter(|h| !h.is_empty()) else {
+			return Ok(None);
+		};
+
+		let mut header_map = HeaderMap::new();
+		for (name, value) in headers {
+			let name = HeaderName::from_bytes(name.as_bytes())
+				.map_err(|err| Error::custom(format!("Invalid header name '{name}'. Cause: {err}")))?;
+			// Note: The value is not in the error message, as it might be a secret
+			let value = HeaderValue::from_str(value)
+				.map_err(|_| Error::custom(format!("Invalid header value for '{name}'")))?;
+			header_map.insert(name, value);
+		}
+
+		Ok(Some(header_map))
+	}
+}
+
+/// Loads the `[providers.<name>]` of the config files (the workspace config overrides the base config by name)
+pub fn load_providers_config(dir_context: &DirContext) -> Result<HashMap<String, ProviderConfig>> {
+	let mut providers = HashMap::new();
+
+	for config_path in dir_context.aipack_paths().get_wks_config_toml_paths()? {
+		if !config_path.exists() {
+			continue;
+		}
+		let config_value = parse_toml(&read_to_string(&config_path)?)?;
+		let Some(providers_value) = config_value.get("providers").cloned() else {
+			continue;
+		};
+
+		let to_config_err = |reason: String| Error::Config {
+			path: config_path.to_string(),
+			reason,
+		};
+		let config_providers: HashMap<String, ProviderConfig> = serde_json::from_value(providers_value)
+			.map_err(|err| to_config_err(format!("Invalid [providers]. Cause: {err}")))?;
+		for (name, provider) in config_providers.iter() {
+			provider
+				.adapter_kind()
+				.map_err(|err| to_config_err(format!("Invalid [providers.{name}]. Cause: {err}")))?;
+		}
+
+		providers.extend(config_providers);
+	}
+
+	Ok(providers)
+}
+
+// endregion: --- ProviderConfig
+
+// region:    --- GenaiClients
+
+/// The genai clients of the runtime.
+///
+/// - The default client routes the `<provider>::<model>` names to the `[providers.<provider>]` endpoints
+/// - The providers with `headers` have their own client (as the headers are set on the http client),
+///   created on first use
+#[derive(Debug, Clone)]
+pub struct GenaiClients {
+	default: Client,
+	providers: Arc<HashMap<String, ProviderConfig>>,
+	by_provider: Arc<Mutex<HashMap<String, Client>>>,
+}
+
+impl GenaiClients {
+	/// Returns the client for this model name (the provider client when `<provider>::<model>` with headers)
+	pub fn for_model(&self, model: &str) -> Result<Client> {
+		let Some((provider_name, provider)) = model
+			.split_once(PROVIDER_MODEL_SEP)
+			.and_then(|(provider_name, _)| Some((provider_name, self.providers.get(provider_name)?)))
+		else {
+			return Ok(self.default.clone());
+		};
+
+		let mut by_provider = self.by_provider.lock().unwrap_or_else(|err| err.into_inner());
+		if let Some(client) = by_provider.get(provider_name) {
+			return Ok(client.clone());
+		}
+
+		let client = match provider.interpolated(provider_name)?.header_map()? {
+			Some(header_map) => new_genai_client(self.providers.clone(), Some(header_map))?,
+			None => self.default.clone(),
+		};
+		by_provider.insert(provider_name.to_string(), client.clone());
+
+		Ok(client)
+	}
+}
+
+pub fn get_genai_clients(providers: HashMap<String, ProviderConfig>) -> Result<GenaiClients> {
+	let providers = Arc::new(providers);
+
+	let default = new_genai_client(providers.clone(), None)?;
+
+	Ok(GenaiClients {
+		default,
+		providers,
+		by_provider: Default::default(),
+	})
+}
+
+fn new_genai_client(providers: Arc<HashMap<String, ProviderConfig>>, headers: Option<HeaderMap>) -> Result<Client> {
 	let options = ChatOptions::default().with_normalize_reasoning_content(true);
-	let client = Client::builder()
+	let mut builder = Client::builder()
 		.with_chat_options(options)
 		.with_auth_resolver_fn(|model: ModelIden| {
 			// -- Get the key_name, if none, then, could be ollama, so return None
@@ -20,6 +217,15 @@ pub fn get_genai_client() -> Result<genai::Client> {
 			let key_from_env = std::env::var(key_name).ok();
 
 			if let Some(key) = key_from_env {
+				return Ok(Some(AuthData::from_single(key)));
+			}
+
+			// -- Then, try the `~/.aipack-base/credentials.toml` store (`aip key set <KEY_NAME>`)
+			let key_from_store = CredStore::from_base_dir()
+				.and_then(|store| store.get(key_name))
+				.map_err(|err| genai::resolver::Error::Custom(err.to_string()))?;
+
+			if let Some(key) = key_from_store {
 				Ok(Some(AuthData::from_single(key)))
 			}
 			// -- Otherwise, get it with keyring
@@ -39,12 +245,164 @@ pub fn get_genai_client() -> Result<genai::Client> {
 				}
 				#[cfg(not(target_os = "macos"))]
 				{
-					let msg = format!("Environment variable '{key_name}' make sure to set it for this terminal.");
+					let msg = format!(
+						"Environment variable '{key_name}' not found. Set it for this terminal, or store it with 'aip key set {key_name}'."
+					);
 					Err(genai::resolver::Error::Custom(msg.to_string()))
 				}
 			}
 		})
-		.build();
+		.with_service_target_resolver_fn(move |service_target: ServiceTarget| {
+			resolve_provider_target(&providers, service_target)
+				.map_err(|err| genai::resolver::Error::Custom(err.to_string()))
+		});
+
+	if let Some(headers) = headers {
+		let reqwest_client = reqwest::Client::builder()
+			.default_headers(headers)
+			.build()
+			.map_err(|err| Error::custom(format!("Cannot build the http client. Cause: {err}")))?;
+		builder = builder.with_reqwest(reqwest_client);
+	}
+
+	Ok(builder.build())
+}
+
+/// Route the `<provider>::<model>` model names to the provider endpoint (other models are unchanged)
+///
+/// Note: genai resolves `myproxy::gpt-4o` as an Ollama model (fallback), so the adapter kind, auth, and endpoint
+///       are all replaced here.
+fn resolve_provider_target(
+	providers: &HashMap<String, ProviderConfig>,
+	service_target: ServiceTarget,
+) -> Result<ServiceTarget> {
+	let Some((provider_name, model_name)) = service_target.model.model_name.split_once(PROVIDER_MODEL_SEP) else {
+		return Ok(service_target);
+	};
+
+	let provider = providers.get(provider_name).ok_or_else(|| {
+		Error::custom(format!(
+			"Provider '{provider_name}' (from model '{}') not found. Add a [providers.{provider_name}] in the config.toml",
+			service_target.model.model_name
+		))
+	})?;
+	let provider = provider.interpolated(provider_name)?;
+
+	Ok(ServiceTarget {
+		endpoint: provider.endpoint(),
+		auth: provider.auth(provider_name)?,
+		model: ModelIden::new(provider.adapter_kind()?, model_name),
+	})
+}
+
+// endregion: --- GenaiClients
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::{assert_contains, read_http_request};
+	use genai::chat::{ChatMessage, ChatRequest};
+	use serde_json::json;
+	use tokio::io::AsyncWriteExt;
+	use tokio::net::TcpListener;
 
-	Ok(client)
+	#[tokio::test]
+	async fn test_genai_clients_provider_mock_server() -> Result<()> {
+		// -- Setup & Fixtures
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let addr = listener.local_addr()?;
+		let server = tokio::spawn(async move {
+			let (mut socket, _) = listener.accept().await?;
+			let request = read_http_request(&mut socket).await?;
+			let body = json!({
+				"id": "chatcmpl-1",
+				"object": "chat.completion",
+				"model": "my-model",
+				"choices": [{"index": 0, "message": {"role": "assistant", "content": "Hello from mock"}, "finish_reason": "stop"}],
+				"usage": {"prompt_tokens": 3, "completion_tokens": 4, "total_tokens": 7}
+			})
+			.to_string();
+			let response = format!(
+				"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+				body.len()
+			);
+			socket.write_all(response.as_bytes()).await?;
+			Ok::<String, std::io::Error>(request)
+		});
+
+		let providers: HashMap<String, ProviderConfig> = serde_json::from_value(json!({
+			"mock": {
+				"base_url": format!("http://{addr}/v1"),
+				"api_key": "fx-key",
+				"headers": {"X-Team": "platform"}
+			}
+		}))?;
+		let clients = get_genai_clients(providers)?;
+
+		// -- Exec
+		let chat_res = clients
+			.for_model("mock::my-model")?
+			.exec_chat(
+				"mock::my-model",
+				ChatRequest::from_messages(vec![ChatMessage::user("Hi")]),
+				None,
+			)
+			.await?;
+		let request = server.await??.to_lowercase();
+
+		// -- Check
+		assert_eq!(chat_res.content_text_as_str(), Some("Hello from mock"));
+		assert_contains(&request, "post /v1/chat/completions");
+		assert_contains(&request, "authorization: bearer fx-key");
+		assert_contains(&request, "x-team: platform");
+		assert_contains(&request, r#""model":"my-model""#);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_genai_clients_provider_not_found() -> Result<()> {
+		// -- Setup & Fixtures
+		let clients = get_genai_clients(HashMap::new())?;
+
+		// -- Exec
+		let res = clients.for_model("nope::gpt-4o")?.resolve_service_target("nope::gpt-4o");
+
+		// -- Check
+		let err = res.err().ok_or("Should fail for an unknown provider")?;
+		assert_contains(&err.to_string(), "Provider 'nope'");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_genai_clients_provider_interpolated_on_use() -> Result<()> {
+		// -- Setup & Fixtures
+		let providers: HashMap<String, ProviderConfig> = serde_json::from_value(json!({
+			"fx-missing": {
+				"base_url": "http://localhost/v1",
+				"api_key": "${env:AIPACK_FX_MISSING_ENV}",
+				"headers": {"X-Team": "${env:AIPACK_FX_MISSING_ENV}"}
+			}
+		}))?;
+
+		// -- Exec
+		let clients = get_genai_clients(providers)?;
+		let other_res = clients.for_model("gpt-4o-mini");
+		let missing_res = clients.for_model("fx-missing::my-model");
+
+		// -- Check
+		assert!(other_res.is_ok(), "unused provider should not fail");
+		let err = missing_res.err().ok_or("Should fail when the provider is used")?;
+		assert_contains(&err.to_string(), "[providers.fx-missing]");
+		assert_contains(&err.to_string(), "AIPACK_FX_MISSING_ENV");
+
+		Ok(())
+	}
 }
+
+// endregion: --- Tests
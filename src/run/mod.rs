@@ -1,13 +1,27 @@
 // region:    --- Modules
 mod literals;
+mod run_attachment;
+mod run_backup;
+mod run_budget;
+mod run_cache;
 mod run_input;
+mod run_journal;
+mod run_report;
+mod run_tool;
 
+mod cmd_policy;
 mod genai_client;
+mod run_changes;
+mod run_review;
 mod run_command;
 mod run_options;
 mod runtime;
 
+pub use cmd_policy::*;
+pub use run_backup::*;
 pub use genai_client::*;
+pub use run_changes::*;
+pub use run_review::*;
 pub use run_command::*;
 pub use run_options::*;
 pub use runtime::*;
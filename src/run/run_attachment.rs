@@ -0,0 +1,138 @@
+use crate::Result;
+use crate::dir_context::{DirContext, PathResolver};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use genai::chat::ContentPart;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// An attachment (image or binary file) to send with a prompt message, as multi-part content.
+///
+/// - From the `# Data` stage: `aipack.data_response({data = ..., attachments = {"shot.png", {path = "doc.pdf"}}})`
+/// - From a prompt part: `{{attach "shot.png"}}` (or `{{attach input.path content_type="image/png"}}`)
+///
+/// The path is relative to the workspace dir (like `utils.file.load`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Attachment {
+	Path(String),
+	Descriptor { path: String, content_type: Option<String> },
+}
+
+/// Constructors
+impl Attachment {
+	pub fn new(path: impl Into<String>, content_type: Option<String>) -> Self {
+		Attachment::Descriptor {
+			path: path.into(),
+			content_type,
+		}
+	}
+
+	/// Parse the `attachments` of a `aipack.data_response(..)` (a single attachment or a list)
+	pub fn list_from_value(value: Value) -> Result<Vec<Attachment>> {
+		let attachments = match value {
+			Value::Null => Ok(Vec::new()),
+			Value::Array(values) => values
+				.into_iter()
+				.map(serde_json::from_value)
+				.collect::<core::result::Result<Vec<Attachment>, _>>(),
+			other => serde_json::from_value(other).map(|attachment| vec![attachment]),
+		}
+		.map_err(|err| {
+			format!(
+				"Attachments must be a path string, a {{path, content_type?}} table, or a list of them. Cause: {err}"
+			)
+		})?;
+
+		Ok(attachments)
+	}
+}
+
+/// Getters
+impl Attachment {
+	pub fn path(&self) -> &str {
+		match self {
+			Attachment::Path(path) => path,
+			Attachment::Descriptor { path, .. } => path,
+		}
+	}
+
+	/// The explicit content type, or the one derived from the file extension
+	pub fn content_type(&self) -> Result<String> {
+		if let Attachment::Descriptor {
+			content_type: Some(content_type),
+			..
+		} = self
+		{
+			return Ok(content_type.to_string());
+		}
+
+		let path = self.path();
+		let ext = path.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).unwrap_or_default();
+		let content_type = match ext.as_str() {
+			"png" => "image/png",
+			"jpg" | "jpeg" => "image/jpeg",
+			"gif" => "image/gif",
+			"webp" => "image/webp",
+			"pdf" => "application/pdf",
+			_ => {
+				return Err(format!(
+					"Attachment '{path}' has an unknown content type. Use {{path = \"{path}\", content_type = \"...\"}}"
+				)
+				.into());
+			}
+		};
+
+		Ok(content_type.to_string())
+	}
+}
+
+impl Attachment {
+	/// Load the file from disk and returns the base64 content part for the chat message.
+	///
+	/// Note: genai sends all the attachments as image parts, so the non image types (e.g., `application/pdf`)
+	///       only work with the providers accepting them this way (e.g., Gemini).
+	pub fn load_content_part(&self, dir_context: &DirContext) -> Result<ContentPart> {
+		let content_type = self.content_type()?;
+		let file = dir_context.resolve_path(self.path(), PathResolver::WksDir)?;
+		let bytes = std::fs::read(file.path())
+			.map_err(|err| format!("Cannot read attachment '{}'. Cause: {err}", self.path()))?;
+
+		Ok(ContentPart::from_image_base64(content_type, BASE64.encode(bytes)))
+	}
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::assert_contains;
+	use serde_json::json;
+
+	#[test]
+	fn test_attachment_list_from_value() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_value = json!(["shot.png", {"path": "doc.bin", "content_type": "application/pdf"}]);
+
+		// -- Exec
+		let attachments = Attachment::list_from_value(fx_value)?;
+		let single = Attachment::list_from_value(json!("photo.JPG"))?;
+		let unknown = Attachment::list_from_value(json!("notes.txt"))?;
+
+		// -- Check
+		assert_eq!(attachments.len(), 2);
+		assert_eq!(attachments[0].content_type()?, "image/png");
+		assert_eq!(attachments[1].path(), "doc.bin");
+		assert_eq!(attachments[1].content_type()?, "application/pdf");
+		assert_eq!(single[0].content_type()?, "image/jpeg");
+		let err = unknown[0].content_type().err().ok_or("Should fail for .txt")?;
+		assert_contains(&err.to_string(), "unknown content type");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,280 @@
+use crate::dir_context::DirContext;
+use crate::run::run_journal::{latest_run_id_with_file, run_dir};
+use crate::run::to_display_path;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use simple_fs::{SPath, ensure_dir, ensure_file_dir};
+
+const BACKUP_DIR: &str = "backup";
+const BACKUP_MANIFEST_FILE: &str = "backup.json";
+const BACKUP_UNDONE_FILE: &str = "backup-undone.json";
+
+// region:    --- RunBackup
+
+/// The backup of the files written during a run, in `.aipack/.runs/<run-id>/backup/`
+///
+/// - The content before the first write of the run is copied once per file (`<num>-<file_name>`)
+/// - The `backup.json` manifest lists the files, with the hash of the content last written by the run
+///   (to detect on `aip undo` that a file changed since the run)
+#[derive(Debug)]
+pub struct RunBackup {
+	backup_dir: SPath,
+	entries: Vec<BackupEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupEntry {
+	/// Absolute path
+	path: String,
+	/// The backup file name (in the backup dir). None when the file did not exist before the run.
+	backup_file: Option<String>,
+	/// The hash of the content written by the run (None until written)
+	written_hash: Option<String>,
+}
+
+/// Constructors
+impl RunBackup {
+	/// The backup of a run dir (loads the existing manifest, e.g., on resume)
+	pub fn load_or_new(run_dir: &SPath) -> Result<Self> {
+		let backup_dir = run_dir.join_str(BACKUP_DIR);
+		let manifest_file = backup_dir.join_str(BACKUP_MANIFEST_FILE);
+		let entries = if manifest_file.exists() {
+			serde_json::from_str(&std::fs::read_to_string(manifest_file.path())?)?
+		} else {
+			Vec::new()
+		};
+
+		Ok(Self { backup_dir, entries })
+	}
+}
+
+impl RunBackup {
+	/// Snapshot the file content before its first write of the run (nothing to do on the next writes)
+	pub fn before_write(&mut self, path: &SPath) -> Result<()> {
+		if self.entries.iter().any(|entry| entry.path == path.to_str()) {
+			return Ok(());
+		}
+
+		let backup_file = if path.is_file() {
+			let file_name = format!("{:04}-{}", self.entries.len() + 1, path.name());
+			ensure_dir(&self.backup_dir)?;
+			std::fs::copy(path.path(), self.backup_dir.join_str(&file_name).path())?;
+			Some(file_name)
+		} else {
+			None
+		};
+
+		self.entries.push(BackupEntry {
+			path: path.to_string(),
+			backup_file,
+			written_hash: None,
+		});
+
+		Ok(())
+	}
+
+	/// Record the hash of the written content, and save the manifest
+	pub fn after_write(&mut self, path: &SPath) -> Result<()> {
+		let written_hash = read_hash(path)?;
+		if let Some(entry) = self.entries.iter_mut().find(|entry| entry.path == path.to_str()) {
+			entry.written_hash = written_hash;
+		}
+
+		ensure_dir(&self.backup_dir)?;
+		std::fs::write(
+			self.backup_dir.join_str(BACKUP_MANIFEST_FILE).path(),
+			serde_json::to_string_pretty(&self.entries)?,
+		)?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- RunBackup
+
+// region:    --- Undo
+
+/// The result of the `undo_run`
+#[derive(Debug, Default)]
+pub struct UndoReport {
+	pub run_id: String,
+	/// The display paths of the files restored to their content before the run
+	pub restored: Vec<String>,
+	/// The display paths of the files created by the run (deleted)
+	pub removed: Vec<String>,
+	/// The display paths of the files not restored (changed since the run), with the reason
+	pub conflicts: Vec<(String, String)>,
+}
+
+/// Restore the files written by a run (the latest not undone run with a backup if `run_id` is None).
+///
+/// A file is not restored (conflict) if it changed since the run.
+/// When there is no conflict, the backup is marked as undone (so the next `aip undo` goes to the previous run).
+pub fn undo_run(dir_context: &DirContext, run_id: Option<&str>) -> Result<UndoReport> {
+	let manifest_rel = format!("{BACKUP_DIR}/{BACKUP_MANIFEST_FILE}");
+	let run_id = match run_id {
+		Some(run_id) => run_id.to_string(),
+		None => latest_run_id_with_file(dir_context, &manifest_rel)?.ok_or("No run with file backups to undo found")?,
+	};
+	let backup_dir = run_dir(dir_context, &run_id).join_str(BACKUP_DIR);
+	let manifest_file = backup_dir.join_str(BACKUP_MANIFEST_FILE);
+	if !manifest_file.exists() {
+		return Err(Error::custom(format!(
+			"No file backups found for run '{run_id}' (at '{manifest_file}')"
+		)));
+	}
+	let entries: Vec<BackupEntry> = serde_json::from_str(&std::fs::read_to_string(manifest_file.path())?)?;
+
+	let mut report = UndoReport {
+		run_id,
+		..Default::default()
+	};
+	for entry in entries {
+		let display_path = to_display_path(dir_context, &entry.path);
+		let path = SPath::new(entry.path.as_str())?;
+		let backup_file = entry.backup_file.as_deref().map(|name| backup_dir.join_str(name));
+		let current_hash = read_hash(&path)?;
+
+		if current_hash != entry.written_hash {
+			// Note: Already restored (e.g., undo after a conflict) is not a conflict
+			let backup_hash = match backup_file.as_ref() {
+				Some(backup_file) => read_hash(backup_file)?,
+				None => None,
+			};
+			if current_hash != backup_hash {
+				report.conflicts.push((display_path, "file changed since the run".to_string()));
+			}
+			continue;
+		}
+
+		match backup_file {
+			Some(backup_file) => {
+				ensure_file_dir(&path)?;
+				std::fs::copy(backup_file.path(), path.path())?;
+				report.restored.push(display_path);
+			}
+			None => {
+				if path.exists() {
+					std::fs::remove_file(path.path())?;
+				}
+				report.removed.push(display_path);
+			}
+		}
+	}
+
+	if report.conflicts.is_empty() {
+		std::fs::rename(manifest_file.path(), backup_dir.join_str(BACKUP_UNDONE_FILE).path())?;
+	}
+
+	Ok(report)
+}
+
+// endregion: --- Undo
+
+// region:    --- Support
+
+/// True if the run dir has a file backup not undone yet (so `aip undo <run-id>` can still use it)
+pub(super) fn has_backup_to_undo(run_dir: &SPath) -> bool {
+	run_dir.join_str(BACKUP_DIR).join_str(BACKUP_MANIFEST_FILE).exists()
+}
+
+/// The hash of the file content (None if the file does not exist)
+fn read_hash(path: &SPath) -> Result<Option<String>> {
+	match std::fs::read(path.path()) {
+		Ok(content) => {
+			let hash = Sha1::digest(&content);
+			Ok(Some(hash.iter().map(|b| format!("{b:02x}")).collect()))
+		}
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+		Err(err) => Err(Error::custom(format!("Cannot read '{path}'. Cause: {err}"))),
+	}
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::run::{FileChanges, Runtime, WriteMode};
+
+	#[test]
+	fn test_run_backup_undo_run() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let dir_context = runtime.dir_context();
+		let fx_dir = dir_context.wks_dir().join_str(".tmp/test_run_backup_undo_run");
+		let fx_existing = fx_dir.join_str("existing.txt");
+		let fx_new = fx_dir.join_str("new.txt");
+		let fx_changed = fx_dir.join_str("changed.txt");
+		simple_fs::ensure_dir(&fx_dir)?;
+		std::fs::write(fx_existing.path(), "original content")?;
+		std::fs::write(fx_changed.path(), "original changed")?;
+		let fx_run_dir = run_dir(dir_context, "test-run-backup-undo-run");
+		let changes = FileChanges::default();
+		changes.start(WriteMode::Direct, Some(RunBackup::load_or_new(&fx_run_dir)?));
+		changes.save(&fx_existing, "first write")?;
+		changes.append(&fx_existing, " + second write")?;
+		changes.save(&fx_new, "new content")?;
+		changes.save(&fx_changed, "run content")?;
+		// changed by the user after the run
+		std::fs::write(fx_changed.path(), "user content")?;
+
+		// -- Exec
+		let report = undo_run(dir_context, Some("test-run-backup-undo-run"))?;
+
+		// -- Check
+		assert_eq!(std::fs::read_to_string(fx_existing.path())?, "original content");
+		assert!(!fx_new.exists(), "new file should be removed");
+		assert_eq!(std::fs::read_to_string(fx_changed.path())?, "user content");
+		assert_eq!(
+			report.restored,
+			vec![".tmp/test_run_backup_undo_run/existing.txt".to_string()]
+		);
+		assert_eq!(
+			report.removed,
+			vec![".tmp/test_run_backup_undo_run/new.txt".to_string()]
+		);
+		assert_eq!(report.conflicts.len(), 1);
+		assert_eq!(report.conflicts[0].1, "file changed since the run");
+
+		// -- Clean
+		std::fs::remove_dir_all(fx_run_dir.path())?;
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_run_backup_undo_run_twice() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let dir_context = runtime.dir_context();
+		let fx_file = dir_context.wks_dir().join_str(".tmp/test_run_backup_undo_run_twice/file.txt");
+		simple_fs::ensure_file_dir(&fx_file)?;
+		std::fs::write(fx_file.path(), "original content")?;
+		let fx_run_dir = run_dir(dir_context, "test-run-backup-undo-run-twice");
+		let changes = FileChanges::default();
+		changes.start(WriteMode::Direct, Some(RunBackup::load_or_new(&fx_run_dir)?));
+		changes.save(&fx_file, "run content")?;
+
+		// -- Exec
+		let report = undo_run(dir_context, Some("test-run-backup-undo-run-twice"))?;
+		let res_again = undo_run(dir_context, Some("test-run-backup-undo-run-twice"));
+
+		// -- Check
+		assert_eq!(report.restored.len(), 1);
+		assert_eq!(std::fs::read_to_string(fx_file.path())?, "original content");
+		assert!(res_again.is_err(), "should not undo twice");
+
+		// -- Clean
+		std::fs::remove_dir_all(fx_run_dir.path())?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,90 @@
+use crate::agent::AgentOptions;
+use crate::run::run_report::RunReport;
+
+/// The budget of a run (across all of its inputs), from the `max_cost_usd` and `max_tokens_total` agent options.
+///
+/// Note: The spent is the totals of the `RunReport` of the run.
+#[derive(Debug, Clone, Default)]
+pub struct RunBudget {
+	max_cost_usd: Option<f64>,
+	max_tokens_total: Option<u64>,
+}
+
+/// Constructors
+impl RunBudget {
+	pub fn from_agent_options(options: &AgentOptions) -> Self {
+		RunBudget {
+			max_cost_usd: options.max_cost_usd(),
+			max_tokens_total: options.max_tokens_total(),
+		}
+	}
+}
+
+/// Getters
+impl RunBudget {
+	pub fn has_limits(&self) -> bool {
+		self.max_cost_usd.is_some() || self.max_tokens_total.is_some()
+	}
+
+	/// Returns true if one of the limits is reached
+	pub fn is_exceeded(&self, run_report: &RunReport) -> bool {
+		let cost_exceeded = self.max_cost_usd.is_some_and(|max| run_report.total_price_usd() >= max);
+		let tokens_exceeded = self.max_tokens_total.is_some_and(|max| run_report.total_tokens() >= max);
+		cost_exceeded || tokens_exceeded
+	}
+
+	/// Returns the spent vs. budget summary (e.g., `Cost: ~$0.0123 of $1 | Tokens: 1200 of 50000`)
+	pub fn summary(&self, run_report: &RunReport) -> String {
+		let cost = match self.max_cost_usd {
+			Some(max) => format!("Cost: ~${} of ${max}", run_report.total_price_usd()),
+			None => format!("Cost: ~${}", run_report.total_price_usd()),
+		};
+		let tokens = match self.max_tokens_total {
+			Some(max) => format!("Tokens: {} of {max}", run_report.total_tokens()),
+			None => format!("Tokens: {}", run_report.total_tokens()),
+		};
+		format!("{cost} | {tokens}")
+	}
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::run::run_input::AiResponse;
+	use crate::support::tomls::parse_toml;
+
+	#[test]
+	fn test_run_budget_exceeded() -> Result<()> {
+		// -- Setup & Fixtures
+		let options = parse_toml(
+			r#"
+	model = "gpt-4o-mini"
+	max_cost_usd = 0.01
+	max_tokens_total = 1000
+		"#,
+		)?;
+		let options = AgentOptions::from_options_value(options)?;
+		let budget = RunBudget::from_agent_options(&options);
+
+		let run_report = RunReport::new();
+		let input_reporter = run_report.new_input_reporter(0, "input-01");
+
+		// -- Exec & Check
+		input_reporter.add_ai_response(&AiResponse::new_for_test(200, 100, 0.004));
+		assert!(!budget.is_exceeded(&run_report), "should not be exceeded yet");
+		input_reporter.add_ai_response(&AiResponse::new_for_test(200, 100, 0.007));
+		assert!(budget.is_exceeded(&run_report), "cost should be exceeded");
+		assert_eq!(
+			budget.summary(&run_report),
+			"Cost: ~$0.011 of $0.01 | Tokens: 600 of 1000"
+		);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,138 @@
+use crate::Result;
+use crate::dir_context::DirContext;
+use genai::ModelName;
+use genai::adapter::AdapterKind;
+use genai::chat::{ChatMessage, ChatOptions};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use simple_fs::{SPath, ensure_dir};
+
+const CACHE_DIR: &str = ".cache";
+
+/// The on-disk cache of the AI responses, in the workspace `.aipack/.cache/` directory.
+///
+/// The key is the hash of the resolved model, the chat options, and the rendered chat messages,
+/// so any change of those is a cache miss.
+#[derive(Debug, Clone)]
+pub struct AiCache {
+	dir: SPath,
+}
+
+/// The AI response as stored in the cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedAiResponse {
+	pub model_name: ModelName,
+	pub adapter_kind: AdapterKind,
+	pub content: Option<String>,
+	pub reasoning_content: Option<String>,
+}
+
+/// Constructors
+impl AiCache {
+	pub fn new(dir_context: &DirContext) -> Self {
+		let dir = dir_context.aipack_paths().wks_aipack_dir().join_str(CACHE_DIR);
+		AiCache { dir }
+	}
+}
+
+impl AiCache {
+	/// Returns the cache key (sha1 hex) for this AI request
+	pub fn key(model: &str, chat_options: &ChatOptions, chat_messages: &[ChatMessage]) -> Result<String> {
+		let key_value = json!({
+			"model": model,
+			"chat_options": chat_options,
+			"chat_messages": chat_messages,
+		});
+		let key_content = serde_json::to_string(&key_value)?;
+
+		let hash = Sha1::digest(key_content.as_bytes());
+		let key = hash.iter().map(|b| format!("{b:02x}")).collect::<String>();
+
+		Ok(key)
+	}
+
+	/// Returns the cached AI response for this key, if any
+	///
+	/// Note: An unreadable cache entry (e.g., older format) is treated as a miss.
+	pub fn get(&self, key: &str) -> Option<CachedAiResponse> {
+		let content = std::fs::read_to_string(self.entry_path(key).path()).ok()?;
+		serde_json::from_str(&content).ok()
+	}
+
+	pub fn save(&self, key: &str, cached_ai_response: &CachedAiResponse) -> Result<()> {
+		ensure_dir(&self.dir)?;
+		let content = serde_json::to_string_pretty(cached_ai_response)?;
+		std::fs::write(self.entry_path(key).path(), content)?;
+		Ok(())
+	}
+
+	fn entry_path(&self, key: &str) -> SPath {
+		self.dir.join_str(&format!("{key}.json"))
+	}
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::run::Runtime;
+
+	#[test]
+	fn test_run_cache_key() -> Result<()> {
+		// -- Setup & Fixtures
+		let messages = vec![ChatMessage::user("Hello")];
+		let options = ChatOptions::default();
+
+		// -- Exec
+		let key = AiCache::key("gpt-4o-mini", &options, &messages)?;
+		let same_key = AiCache::key("gpt-4o-mini", &options, &messages)?;
+		let other_model_key = AiCache::key("gpt-4o", &options, &messages)?;
+		let other_options_key = AiCache::key("gpt-4o-mini", &options.clone().with_temperature(0.5), &messages)?;
+
+		// -- Check
+		assert_eq!(key.len(), 40);
+		assert_eq!(key, same_key);
+		assert_ne!(key, other_model_key);
+		assert_ne!(key, other_options_key);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_run_cache_save_get() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let cache = AiCache::new(runtime.dir_context());
+		let key = AiCache::key(
+			"gpt-4o-mini",
+			&ChatOptions::default(),
+			&[ChatMessage::user("test_run_cache")],
+		)?;
+		let cached = CachedAiResponse {
+			model_name: "gpt-4o-mini".into(),
+			adapter_kind: AdapterKind::OpenAI,
+			content: Some("Hello from cache".to_string()),
+			reasoning_content: None,
+		};
+
+		// -- Exec
+		cache.save(&key, &cached)?;
+		let res = cache.get(&key).ok_or("Should have a cache entry")?;
+
+		// -- Check
+		assert_eq!(res.content.as_deref(), Some("Hello from cache"));
+		assert!(cache.get("not-a-key").is_none(), "should be a cache miss");
+
+		// -- Clean
+		std::fs::remove_file(cache.entry_path(&key).path())?;
+		let _ = std::fs::remove_dir(cache.dir.path()); // only if empty
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,427 @@
+use crate::dir_context::DirContext;
+use crate::run::RunBackup;
+use crate::run::run_journal::{latest_run_id_with_file, run_dir};
+use crate::support::files;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use simple_fs::{SPath, ensure_dir, ensure_file_dir};
+use std::io::Write as _;
+use std::sync::{Arc, Mutex};
+
+const PLAN_FILE: &str = "plan.json";
+
+// region:    --- WriteMode
+
+/// How the `utils.file.save/append/ensure_exists` (and `utils.git.restore`) writes are performed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum WriteMode {
+	/// Written to disk
+	#[default]
+	Direct,
+	/// Recorded as a change set (nothing written), with `--dry plan`
+	Plan,
+	/// Staged, and written once accepted, with `--review`
+	Review,
+}
+
+impl WriteMode {
+	/// True when the writes are recorded (not written to disk)
+	pub fn is_staged(self) -> bool {
+		matches!(self, WriteMode::Plan | WriteMode::Review)
+	}
+}
+
+// endregion: --- WriteMode
+
+// region:    --- FileChange
+
+/// A recorded file change (with the content before the first write of the run)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChange {
+	/// Absolute path
+	path: String,
+	/// None when the file did not exist
+	old_content: Option<String>,
+	new_content: String,
+}
+
+/// Getters
+impl FileChange {
+	pub fn path(&self) -> &str {
+		&self.path
+	}
+
+	pub fn old_content(&self) -> Option<&str> {
+		self.old_content.as_deref()
+	}
+
+	pub fn new_content(&self) -> &str {
+		&self.new_content
+	}
+}
+
+impl FileChange {
+	/// The path relative to the workspace dir (absolute if outside)
+	pub fn display_path(&self, dir_context: &DirContext) -> String {
+		to_display_path(dir_context, &self.path)
+	}
+
+	/// Returns the change with this new content (e.g., edited during the review)
+	pub fn with_new_content(mut self, new_content: String) -> Self {
+		self.new_content = new_content;
+		self
+	}
+
+	/// Write the new content to the file (creating the parent dirs as needed)
+	pub fn write(&self) -> Result<()> {
+		let path = SPath::new(self.path.as_str())?;
+		ensure_file_dir(&path)?;
+		std::fs::write(path.path(), &self.new_content)?;
+		Ok(())
+	}
+
+	/// The unified diff of the change (`/dev/null` as the old path for the new files)
+	pub fn to_unified_diff(&self, dir_context: &DirContext) -> String {
+		let display_path = self.display_path(dir_context);
+		let old_header = match self.old_content {
+			Some(_) => format!("a/{display_path}"),
+			None => "/dev/null".to_string(),
+		};
+		let old_content = self.old_content.as_deref().unwrap_or_default();
+
+		TextDiff::from_lines(old_content, &self.new_content)
+			.unified_diff()
+			.context_radius(3)
+			.header(&old_header, &format!("b/{display_path}"))
+			.to_string()
+	}
+}
+
+/// The path relative to the workspace dir (absolute if outside)
+pub fn to_display_path(dir_context: &DirContext, path: &str) -> String {
+	SPath::new(path)
+		.ok()
+		.and_then(|spath| spath.diff(dir_context.wks_dir()).ok())
+		.filter(|spath| !spath.to_str().starts_with(".."))
+		.map(|spath| spath.to_string())
+		.unwrap_or_else(|| path.to_string())
+}
+
+// endregion: --- FileChange
+
+// region:    --- FileChanges
+
+/// The file writes of the Lua `utils.file.*` functions, performed according to the `WriteMode`.
+///
+/// In `WriteMode::Plan` and `WriteMode::Review`, the writes are recorded (in order of first write), and the reads of
+/// `exists` / `is_empty` / `append` see the recorded content.
+///
+/// In `WriteMode::Direct` (and for the accepted changes of the review), the previous content of the written files
+/// is snapshotted in the `RunBackup` of the run (when set).
+///
+/// Note: Cheap to clone (Arc inner), shared by the `RuntimeContext`.
+#[derive(Debug, Clone, Default)]
+pub struct FileChanges {
+	inner: Arc<Mutex<FileChangesInner>>,
+}
+
+#[derive(Debug, Default)]
+struct FileChangesInner {
+	mode: WriteMode,
+	changes: Vec<FileChange>,
+	backup: Option<RunBackup>,
+}
+
+impl FileChanges {
+	/// Start a new run with this mode and backup (clears the recorded changes)
+	pub fn start(&self, mode: WriteMode, backup: Option<RunBackup>) {
+		let mut inner = self.lock();
+		inner.mode = mode;
+		inner.changes.clear();
+		inner.backup = backup;
+	}
+
+	pub fn mode(&self) -> WriteMode {
+		self.lock().mode
+	}
+
+	/// Take the recorded changes (the list is empty after)
+	pub fn take_changes(&self) -> Vec<FileChange> {
+		std::mem::take(&mut self.lock().changes)
+	}
+}
+
+/// Writers
+impl FileChanges {
+	pub fn save(&self, path: &SPath, content: &str) -> Result<()> {
+		match self.mode() {
+			WriteMode::Direct => self.write_direct(path, || {
+				ensure_file_dir(path)?;
+				std::fs::write(path.path(), content)?;
+				Ok(())
+			})?,
+			WriteMode::Plan | WriteMode::Review => self.record(path, content.to_string())?,
+		}
+		Ok(())
+	}
+
+	pub fn append(&self, path: &SPath, content: &str) -> Result<()> {
+		match self.mode() {
+			WriteMode::Direct => self.write_direct(path, || {
+				ensure_file_dir(path)?;
+				let mut file = std::fs::OpenOptions::new().append(true).create(true).open(path.path())?;
+				file.write_all(content.as_bytes())?;
+				Ok(())
+			})?,
+			WriteMode::Plan | WriteMode::Review => {
+				let current = self.content(path)?.unwrap_or_default();
+				self.record(path, format!("{current}{content}"))?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Write a staged change to disk (e.g., accepted in the review), with its backup
+	pub fn write_change(&self, change: &FileChange) -> Result<()> {
+		self.write_direct(&SPath::new(change.path())?, || change.write())
+	}
+}
+
+/// Readers (seeing the recorded changes)
+impl FileChanges {
+	pub fn exists(&self, path: &SPath) -> bool {
+		self.recorded_content(path).is_some() || path.exists()
+	}
+
+	pub fn is_empty(&self, path: &SPath) -> Result<bool> {
+		match self.recorded_content(path) {
+			Some(content) => Ok(content.is_empty()),
+			None => files::is_file_empty(path),
+		}
+	}
+
+	/// The recorded content, or the disk content (None if the file does not exist)
+	fn content(&self, path: &SPath) -> Result<Option<String>> {
+		match self.recorded_content(path) {
+			Some(content) => Ok(Some(content)),
+			None => read_optional(path),
+		}
+	}
+}
+
+// region:    --- Support
+
+impl FileChanges {
+	fn lock(&self) -> std::sync::MutexGuard<'_, FileChangesInner> {
+		self.inner.lock().unwrap_or_else(|err| err.into_inner())
+	}
+
+	/// Perform the write, with the backup before and after (the lock is held, for concurrent writes of the same file)
+	fn write_direct(&self, path: &SPath, write_fn: impl FnOnce() -> Result<()>) -> Result<()> {
+		let mut inner = self.lock();
+		if let Some(backup) = inner.backup.as_mut() {
+			backup.before_write(path)?;
+		}
+		write_fn()?;
+		if let Some(backup) = inner.backup.as_mut() {
+			backup.after_write(path)?;
+		}
+		Ok(())
+	}
+
+	fn recorded_content(&self, path: &SPath) -> Option<String> {
+		let inner = self.lock();
+		inner
+			.changes
+			.iter()
+			.find(|change| change.path == path.to_str())
+			.map(|change| change.new_content.to_string())
+	}
+
+	fn record(&self, path: &SPath, new_content: String) -> Result<()> {
+		// Note: The old content is read before the lock (only used if it is the first write)
+		let disk_content = read_optional(path)?;
+
+		let mut inner = self.lock();
+		match inner.changes.iter_mut().find(|change| change.path == path.to_str()) {
+			Some(change) => change.new_content = new_content,
+			None => inner.changes.push(FileChange {
+				path: path.to_string(),
+				old_content: disk_content,
+				new_content,
+			}),
+		}
+
+		Ok(())
+	}
+}
+
+fn read_optional(path: &SPath) -> Result<Option<String>> {
+	match std::fs::read_to_string(path.path()) {
+		Ok(content) => Ok(Some(content)),
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+		Err(err) => Err(Error::custom(format!("Cannot read '{path}'. Cause: {err}"))),
+	}
+}
+
+// endregion: --- Support
+
+// endregion: --- FileChanges
+
+// region:    --- Plan
+
+/// Save the plan changes in the `.aipack/.runs/<run-id>/plan.json` (to be applied with `aip apply`)
+pub fn save_plan(run_dir: &SPath, changes: &[FileChange]) -> Result<SPath> {
+	let plan_file = run_dir.join_str(PLAN_FILE);
+	ensure_dir(run_dir)?;
+	std::fs::write(plan_file.path(), serde_json::to_string_pretty(changes)?)?;
+	Ok(plan_file)
+}
+
+/// The result of the `apply_plan`
+#[derive(Debug, Default)]
+pub struct ApplyReport {
+	pub run_id: String,
+	/// The display paths of the applied changes
+	pub applied: Vec<String>,
+	/// The display paths of the changes not applied (the file changed since the plan), with the reason
+	pub conflicts: Vec<(String, String)>,
+}
+
+/// Apply the plan of a run (the latest run with a plan if `run_id` is None).
+///
+/// A change is not applied (conflict) if the file changed since the plan, or if the write policy blocks it.
+/// The applied changes are backed up in the run dir of the plan, like the direct writes (for `aip undo <run-id>`).
+pub fn apply_plan(dir_context: &DirContext, run_id: Option<&str>) -> Result<ApplyReport> {
+	let run_id = match run_id {
+		Some(run_id) => run_id.to_string(),
+		None => latest_run_id_with_file(dir_context, PLAN_FILE)?
+			.ok_or("No run with a plan found (run an agent with `--dry plan` first)")?,
+	};
+	let run_dir = run_dir(dir_context, &run_id);
+	let plan_file = run_dir.join_str(PLAN_FILE);
+	if !plan_file.exists() {
+		return Err(Error::custom(format!(
+			"No plan found for run '{run_id}' (at '{plan_file}')"
+		)));
+	}
+	let changes: Vec<FileChange> = serde_json::from_str(&std::fs::read_to_string(plan_file.path())?)?;
+
+	// Note: Written through the file changes, for their backup
+	let file_changes = FileChanges::default();
+	file_changes.start(WriteMode::Direct, Some(RunBackup::load_or_new(&run_dir)?));
+
+	let mut report = ApplyReport {
+		run_id,
+		..Default::default()
+	};
+	for change in changes {
+		let display_path = change.display_path(dir_context);
+		let path = match dir_context.write_policy().check(&SPath::new(change.path())?) {
+			Ok(path) => path,
+			Err(err) => {
+				report.conflicts.push((display_path, err.to_string()));
+				continue;
+			}
+		};
+		if read_optional(&path)?.as_deref() != change.old_content() {
+			report.conflicts.push((display_path, "file changed since the plan".to_string()));
+			continue;
+		}
+		file_changes.write_change(&change)?;
+		report.applied.push(display_path);
+	}
+
+	Ok(report)
+}
+
+// endregion: --- Plan
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::assert_contains;
+	use crate::run::{Runtime, undo_run};
+
+	#[test]
+	fn test_file_changes_plan_record_and_diff() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let dir_context = runtime.dir_context();
+		let fx_existing = dir_context.wks_dir().join_str("file-01.txt");
+		let fx_new = dir_context.wks_dir().join_str(".tmp/test_file_changes_plan/new.txt");
+		let changes = FileChanges::default();
+		changes.start(WriteMode::Plan, None);
+
+		// -- Exec
+		changes.save(&fx_existing, "changed content\n")?;
+		changes.append(&fx_new, "line one\n")?;
+		changes.append(&fx_new, "line two\n")?;
+		let new_exists = changes.exists(&fx_new);
+		let recorded = changes.take_changes();
+
+		// -- Check
+		assert!(new_exists, "new file should exist in the plan");
+		assert!(!fx_new.exists(), "new file should not be written");
+		assert_eq!(recorded.len(), 2);
+		assert_eq!(recorded[1].new_content(), "line one\nline two\n");
+		let diff = recorded[0].to_unified_diff(dir_context);
+		assert_contains(&diff, "--- a/file-01.txt");
+		assert_contains(&diff, "+changed content");
+		let diff = recorded[1].to_unified_diff(dir_context);
+		assert_contains(&diff, "--- /dev/null");
+		assert_contains(&diff, "+++ b/.tmp/test_file_changes_plan/new.txt");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_file_changes_apply_plan() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let dir_context = runtime.dir_context();
+		let fx_file = dir_context.wks_dir().join_str(".tmp/test_file_changes_apply_plan/new.txt");
+		let fx_run_dir = run_dir(dir_context, "test-file-changes-apply-plan");
+		// Note: Clean the previous test run (e.g., failed before its clean)
+		let _ = std::fs::remove_file(fx_file.path());
+		let _ = std::fs::remove_dir_all(fx_run_dir.path());
+		simple_fs::ensure_dir(&fx_run_dir)?;
+		let changes = FileChanges::default();
+		changes.start(WriteMode::Plan, None);
+		changes.save(&fx_file, "planned content")?;
+		save_plan(&fx_run_dir, &changes.take_changes())?;
+
+		// -- Exec
+		let report = apply_plan(dir_context, Some("test-file-changes-apply-plan"))?;
+		let report_again = apply_plan(dir_context, Some("test-file-changes-apply-plan"))?;
+		let applied_content = std::fs::read_to_string(fx_file.path())?;
+		let undo_report = undo_run(dir_context, Some("test-file-changes-apply-plan"))?;
+
+		// -- Check
+		assert_eq!(
+			report.applied,
+			vec![".tmp/test_file_changes_apply_plan/new.txt".to_string()]
+		);
+		assert_eq!(applied_content, "planned content");
+		assert!(report_again.applied.is_empty(), "should not apply twice");
+		assert_eq!(report_again.conflicts[0].1, "file changed since the plan");
+		// the apply can be undone (the new file is removed)
+		assert_eq!(
+			undo_report.removed,
+			vec![".tmp/test_file_changes_apply_plan/new.txt".to_string()]
+		);
+		assert!(!fx_file.exists(), "applied file should be removed by the undo");
+
+		// -- Clean
+		std::fs::remove_dir_all(fx_run_dir.path())?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
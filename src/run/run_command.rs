@@ -2,22 +2,27 @@ use crate::agent::{Agent, AgentOptions, AgentRef};
 use crate::dir_context::DirContext;
 use crate::hub::get_hub;
 use crate::run::literals::Literals;
+use crate::run::run_budget::RunBudget;
 use crate::run::run_input::{RunAgentInputResponse, run_agent_input};
-use crate::run::{RunBaseOptions, Runtime};
+use crate::run::run_journal::RunJournal;
+use crate::run::run_report::{InputError, RunReport};
+use crate::run::{DryMode, RunBackup, RunBaseOptions, Runtime, WriteMode, review_changes, save_plan};
 use crate::script::{AipackCustom, BeforeAllResponse, FromValue};
 use crate::{Error, Result};
 use serde::Serialize;
 use serde_json::Value;
 use simple_fs::SPath;
-use tokio::task::JoinSet;
+use tokio::task::{JoinError, JoinSet};
 use value_ext::JsonValueExt;
 
-const DEFAULT_CONCURRENCY: usize = 1;
+pub(super) const DEFAULT_CONCURRENCY: usize = 1;
 
 #[derive(Debug, Serialize, Default)]
 pub struct RunCommandResponse {
 	pub outputs: Option<Vec<Value>>,
 	pub after_all: Option<Value>,
+	/// The failed inputs (when continue on error)
+	pub errors: Vec<InputError>,
 }
 
 /// Return the display path
@@ -46,6 +51,30 @@ pub async fn run_command_agent(
 
 	let literals = Literals::from_dir_context_and_agent_path(runtime.dir_context(), &agent)?;
 
+	// -- Create (or load) the run journal (before the first writes, for their backup)
+	let run_journal = match run_base_options.resume() {
+		Some(run_id) => Some(RunJournal::load(runtime.dir_context(), run_id, agent.file_path())?),
+		None if run_base_options.journal() || run_base_options.keep_run_files() => Some(RunJournal::create(
+			runtime.dir_context(),
+			agent.file_path(),
+			run_base_options.journal(),
+		)?),
+		None => None,
+	};
+
+	// -- Start the file changes (the writes are recorded with `--dry plan`, and staged with `--review`)
+	let write_mode = match run_base_options.dry_mode() {
+		DryMode::Plan => WriteMode::Plan,
+		_ if run_base_options.review() => WriteMode::Review,
+		_ => WriteMode::Direct,
+	};
+	// Note: The previous content of the written files is backed up in the run dir (for `aip undo`)
+	let run_backup = match (write_mode, run_journal.as_ref()) {
+		(WriteMode::Plan, _) | (_, None) => None,
+		(_, Some(run_journal)) => Some(RunBackup::load_or_new(run_journal.run_dir())?),
+	};
+	runtime.file_changes().start(write_mode, run_backup);
+
 	// -- Run the before all
 	let BeforeAllResponse {
 		inputs,
@@ -82,6 +111,13 @@ pub async fn run_command_agent(
 				options,
 			},
 
+			FromValue::AipackCustom(other) => {
+				return Err(Error::custom(format!(
+					"Aipack custom '{}' not supported at the Before All stage",
+					other.as_ref()
+				)));
+			}
+
 			// just plane value
 			FromValue::OriginalValue(value) => BeforeAllResponse {
 				inputs,
@@ -103,7 +139,7 @@ pub async fn run_command_agent(
 	let before_all = before_all.unwrap_or_default();
 	let agent: Agent = match options_to_merge {
 		Some(options_to_merge) => {
-			let options_to_merge: AgentOptions = serde_json::from_value(options_to_merge)?;
+			let options_to_merge = AgentOptions::from_script_options_value(options_to_merge)?;
 			let options_ov = agent.options_as_ref().merge_new(options_to_merge)?;
 			agent.new_merge(options_ov)?
 		}
@@ -151,10 +187,54 @@ pub async fn run_command_agent(
 		};
 
 	// -- Run the inputs
+	let run_budget = RunBudget::from_agent_options(agent.options_as_ref());
+	let run_report = RunReport::new();
+	let continue_on_error = run_base_options.keep_going() || agent.options().continue_on_error().unwrap_or(false);
+	if let Some(run_journal) = run_journal.as_ref().filter(|run_journal| run_journal.records_inputs()) {
+		let run_id = run_journal.run_id();
+		hub.publish(format!("           run id: {run_id} (to resume: --resume {run_id})"))
+			.await;
+	}
+
 	let mut join_set = JoinSet::new();
 	let mut in_progress = 0;
 	for (input_idx, input) in inputs.clone().into_iter().enumerate() {
+		// -- If resumed and already completed, reuse the stored output
+		let input_hash = match run_journal.as_ref().filter(|run_journal| run_journal.records_inputs()) {
+			Some(_) => Some(RunJournal::input_hash(&input)?),
+			None => None,
+		};
+		if let (Some(run_journal), Some(input_hash)) = (run_journal.as_ref(), input_hash.as_deref()) {
+			if let Some(output) = run_journal.completed_output(input_hash) {
+				let label = get_input_label(&input).unwrap_or_else(|| format!("input index: {input_idx}"));
+				hub.publish(format!("-> Skipping input already completed in run journal: {label}"))
+					.await;
+				if let Some(outputs_vec) = &mut captured_outputs {
+					outputs_vec.push((input_idx, output.clone()));
+				}
+				continue;
+			}
+		}
+
+		// If the budget is exceeded, no new input is started (the in progress ones will finish)
+		if run_budget.is_exceeded(&run_report) {
+			let skipped = inputs.len() - input_idx;
+			hub.publish(format!(
+				"-! Budget exceeded ({}). Skipping the {skipped} remaining input(s).",
+				run_budget.summary(&run_report)
+			))
+			.await;
+			// Note: Null outputs for the skipped inputs, so that the outputs stay aligned with the inputs
+			if let Some(outputs_vec) = &mut captured_outputs {
+				outputs_vec.extend((input_idx..inputs.len()).map(|idx| (idx, Value::Null)));
+			}
+			break;
+		}
+
+		let label = get_input_label(&input).unwrap_or_else(|| format!("input index: {input_idx}"));
 		let runtime_clone = runtime.clone();
+		let run_report_clone = run_report.clone();
+		let run_journal_clone = run_journal.clone();
 		let agent_clone = agent.clone();
 		let before_all_clone = before_all.clone();
 		let literals = literals.clone();
@@ -162,42 +242,65 @@ pub async fn run_command_agent(
 		let base_run_config_clone = run_base_options.clone();
 
 		// Spawn tasks up to the concurrency limit
+		// Note: The task returns the input_idx with the result, so that errors can be attributed to their input
 		join_set.spawn(async move {
-			// Execute the command agent (this will perform do Data, Instruction, and Output stages)
-			let run_input_response = run_command_agent_input(
-				input_idx,
-				&runtime_clone,
-				&agent_clone,
-				before_all_clone,
-				input,
-				&literals,
-				&base_run_config_clone,
-			)
-			.await?;
+			let output_res = async {
+				// Execute the command agent (this will perform do Data, Instruction, and Output stages)
+				let run_input_response = run_command_agent_input(
+					input_idx,
+					&runtime_clone,
+					&agent_clone,
+					before_all_clone,
+					input,
+					&literals,
+					&base_run_config_clone,
+					&run_report_clone,
+				)
+				.await?;
+
+				// Process the output
+				let run_input_value = run_input_response.map(|v| v.into_value()).unwrap_or_default();
+				let output = match AipackCustom::from_value(run_input_value)? {
+					// if it is a skip, we skip
+					FromValue::AipackCustom(AipackCustom::Skip { reason }) => {
+						let reason_msg = reason.map(|reason| format!(" (Reason: {reason})")).unwrap_or_default();
+						hub.publish(format!("-! Aipack Skip input at Output stage{reason_msg}")).await;
+						run_report_clone.input_reporter(input_idx).set_skipped();
+						Value::Null
+					}
 
-			// Process the output
-			let run_input_value = run_input_response.map(|v| v.into_value()).unwrap_or_default();
-			let output = match AipackCustom::from_value(run_input_value)? {
-				// if it is a skip, we skip
-				FromValue::AipackCustom(AipackCustom::Skip { reason }) => {
-					let reason_msg = reason.map(|reason| format!(" (Reason: {reason})")).unwrap_or_default();
-					hub.publish(format!("-! Aipack Skip input at Output stage{reason_msg}")).await;
-					Value::Null
-				}
+					// Any other AipackCustom is not supported at output stage
+					FromValue::AipackCustom(other) => {
+						return Err(Error::custom(format!(
+							"Aipack custom '{}' not supported at the Output stage",
+							other.as_ref()
+						)));
+					}
 
-				// Any other AipackCustom is not supported at output stage
-				FromValue::AipackCustom(other) => {
-					return Err(Error::custom(format!(
-						"Aipack custom '{}' not supported at the Output stage",
-						other.as_ref()
-					)));
-				}
+					// Plain value passthrough
+					FromValue::OriginalValue(value) => value,
+				};
 
-				// Plain value passthrough
-				FromValue::OriginalValue(value) => value,
-			};
+				Ok(output)
+			}
+			.await;
+
+			match (output_res.as_ref(), run_journal_clone, input_hash) {
+				(Err(err), _, _) => run_report_clone.input_reporter(input_idx).set_error(err),
+				// -- Record the completed input in the journal (for an eventual resume)
+				// Note: When the file writes are staged, only recorded once they are applied (after the review)
+				(Ok(output), Some(run_journal), Some(input_hash)) if write_mode.is_staged() => {
+					run_journal.defer(input_idx, &label, &input_hash, output);
+				}
+				(Ok(output), Some(run_journal), Some(input_hash)) => {
+					if let Err(err) = run_journal.append(input_idx, &label, &input_hash, output) {
+						hub.publish(format!("-! Cannot write run journal. Cause: {err}")).await;
+					}
+				}
+				_ => (),
+			}
 
-			Ok((input_idx, output))
+			(input_idx, output_res)
 		});
 
 		in_progress += 1;
@@ -206,14 +309,10 @@ pub async fn run_command_agent(
 		if in_progress >= concurrency {
 			if let Some(res) = join_set.join_next().await {
 				in_progress -= 1;
-				match res {
-					Ok(Ok((input_idx, output))) => {
-						if let Some(outputs_vec) = &mut captured_outputs {
-							outputs_vec.push((input_idx, output));
-						}
-					}
-					Ok(Err(e)) => return Err(e),
-					Err(e) => return Err(Error::custom(format!("Error while running input. Cause {e}"))),
+				if let Err(err) = process_input_res(res, &mut captured_outputs, continue_on_error).await {
+					publish_run_report(&run_report, &run_budget, run_base_options).await?;
+					end_staged_changes_on_error(runtime, write_mode, run_journal.as_ref(), run_base_options).await;
+					return Err(err);
 				}
 			}
 		}
@@ -223,18 +322,17 @@ pub async fn run_command_agent(
 	while in_progress > 0 {
 		if let Some(res) = join_set.join_next().await {
 			in_progress -= 1;
-			match res {
-				Ok(Ok((input_idx, output))) => {
-					if let Some(outputs_vec) = &mut captured_outputs {
-						outputs_vec.push((input_idx, output));
-					}
-				}
-				Ok(Err(e)) => return Err(e),
-				Err(e) => return Err(Error::custom(format!("Error while remaining input. Cause {e}"))),
+			if let Err(err) = process_input_res(res, &mut captured_outputs, continue_on_error).await {
+				publish_run_report(&run_report, &run_budget, run_base_options).await?;
+				end_staged_changes_on_error(runtime, write_mode, run_journal.as_ref(), run_base_options).await;
+				return Err(err);
 			}
 		}
 	}
 
+	publish_run_report(&run_report, &run_budget, run_base_options).await?;
+	let errors = run_report.input_errors();
+
 	// -- Post-process outputs
 	let outputs = if let Some(mut captured_outputs) = captured_outputs {
 		captured_outputs.sort_by_key(|(idx, _)| *idx);
@@ -258,6 +356,8 @@ pub async fn run_command_agent(
 		// Will be Value::Null if outputs were not collected
 		lua_scope.set("outputs", lua_engine.serde_to_lua_value(outputs_value)?)?;
 		lua_scope.set("before_all", lua_engine.serde_to_lua_value(before_all)?)?;
+		// Note: Always a list (empty when no errors), only populated when continue on error
+		lua_scope.set("errors", lua_engine.serde_to_lua_value(serde_json::to_value(&errors)?)?)?;
 		lua_scope.set("CTX", literals.to_lua(&lua_engine)?)?;
 		lua_scope.set("options", agent.options_as_ref())?;
 
@@ -267,11 +367,25 @@ pub async fn run_command_agent(
 		None
 	};
 
-	Ok(RunCommandResponse { after_all, outputs })
+	// -- Publish the plan (with `--dry plan`), or review the staged changes (with `--review`)
+	// Note: The journal is finished when all the inputs are completed (and recorded)
+	let all_recorded = end_staged_changes(runtime, write_mode, run_journal.as_ref(), run_base_options).await?;
+	let journal_finished = errors.is_empty() && all_recorded;
+
+	if let Some(run_journal) = run_journal.as_ref().filter(|_| journal_finished) {
+		run_journal.finish()?;
+	}
+
+	Ok(RunCommandResponse {
+		after_all,
+		outputs,
+		errors,
+	})
 }
 
 /// Run the command agent input for the run_command_agent_inputs
 /// Not public by design, should be only used in the context of run_command_agent_inputs
+#[allow(clippy::too_many_arguments)]
 async fn run_command_agent_input(
 	input_idx: usize,
 	runtime: &Runtime,
@@ -280,6 +394,7 @@ async fn run_command_agent_input(
 	input: impl Serialize,
 	literals: &Literals,
 	run_base_options: &RunBaseOptions,
+	run_report: &RunReport,
 ) -> Result<Option<RunAgentInputResponse>> {
 	let hub = get_hub();
 
@@ -291,7 +406,20 @@ async fn run_command_agent_input(
 	let label = get_input_label(&input).unwrap_or_else(|| format!("input index: {input_idx}"));
 	hub.publish(format!("\n==== Running input: {}", label)).await;
 
-	let run_response = run_agent_input(runtime, agent, before_all, &label, input, literals, run_base_options).await?;
+	let input_reporter = run_report.new_input_reporter(input_idx, &label);
+	let run_response = run_agent_input(
+		runtime,
+		agent,
+		before_all,
+		&label,
+		input,
+		literals,
+		run_base_options,
+		&input_reporter,
+	)
+	.await;
+	input_reporter.end();
+	let run_response = run_response.inspect_err(|err| input_reporter.set_error(err))?;
 
 	// if the response value is a String, then, print it
 	if let Some(response_txt) = run_response.as_ref().and_then(|r| r.as_str()) {
@@ -324,12 +452,158 @@ pub async fn run_command_agent_input_for_test(
 		input,
 		&literals,
 		run_base_options,
+		&RunReport::default(),
 	)
 	.await
 }
 
 // region:    --- Support
 
+/// Process the result of an input task, capturing the output.
+///
+/// Returns the error when the run must stop (i.e., input failed and not continue on error).
+/// When continue on error, the failed input output is `null` (it is recorded in the run report errors).
+async fn process_input_res(
+	res: core::result::Result<(usize, Result<Value>), JoinError>,
+	captured_outputs: &mut Option<Vec<(usize, Value)>>,
+	continue_on_error: bool,
+) -> Result<()> {
+	let (input_idx, output) = match res {
+		Ok((input_idx, Ok(output))) => (input_idx, output),
+		Ok((input_idx, Err(err))) if continue_on_error => {
+			get_hub()
+				.publish(format!(
+					"-! Input index {input_idx} failed (continue on error). Cause: {err}"
+				))
+				.await;
+			(input_idx, Value::Null)
+		}
+		Ok((_, Err(err))) => return Err(err),
+		Err(err) => return Err(Error::custom(format!("Error while running input. Cause {err}"))),
+	};
+
+	if let Some(outputs_vec) = captured_outputs {
+		outputs_vec.push((input_idx, output));
+	}
+
+	Ok(())
+}
+
+/// Publish the end-of-run summary (with the budget if any), and write the JSON report if `--report`
+async fn publish_run_report(
+	run_report: &RunReport,
+	run_budget: &RunBudget,
+	run_base_options: &RunBaseOptions,
+) -> Result<()> {
+	let hub = get_hub();
+
+	hub.publish(format!("\n{}", run_report.to_summary_table())).await;
+	if run_budget.has_limits() {
+		hub.publish(format!("Budget: {}", run_budget.summary(run_report))).await;
+	}
+
+	if let Some(report_path) = run_base_options.report() {
+		let report_path = SPath::new(report_path)?;
+		run_report.write_json(&report_path)?;
+		hub.publish(format!("-> Run report written to: {report_path}")).await;
+	}
+
+	Ok(())
+}
+
+/// Publish the plan (with `--dry plan`), or review the staged changes (with `--review`)
+///
+/// Returns false if some completed inputs were not recorded in the run journal (some changes not written)
+async fn end_staged_changes(
+	runtime: &Runtime,
+	write_mode: WriteMode,
+	run_journal: Option<&RunJournal>,
+	run_base_options: &RunBaseOptions,
+) -> Result<bool> {
+	let hub = get_hub();
+	let mut all_recorded = true;
+	match write_mode {
+		WriteMode::Plan => publish_plan(runtime, run_journal).await?,
+		WriteMode::Review => {
+			let changes = runtime.file_changes().take_changes();
+			let total = changes.len();
+			let written = review_changes(
+				runtime.dir_context(),
+				runtime.file_changes(),
+				changes,
+				run_base_options.auto_approve(),
+				run_base_options.interactive(),
+			)
+			.await?;
+			// Note: The inputs cannot be matched to their changes, so recorded only when all changes are written
+			if let Some(run_journal) = run_journal.filter(|run_journal| run_journal.records_inputs()) {
+				if written.len() == total {
+					run_journal.append_deferred()?;
+				} else {
+					all_recorded = false;
+					hub.publish("-! Run journal: inputs not recorded as completed (some changes were not written)")
+						.await;
+				}
+			}
+		}
+		WriteMode::Direct => (),
+	}
+
+	Ok(all_recorded)
+}
+
+/// When an input fails (and the run stops), still publish the plan or review the changes staged so far
+/// (an error of this step is published, as the input error is the one returned)
+async fn end_staged_changes_on_error(
+	runtime: &Runtime,
+	write_mode: WriteMode,
+	run_journal: Option<&RunJournal>,
+	run_base_options: &RunBaseOptions,
+) {
+	if !write_mode.is_staged() {
+		return;
+	}
+	let hub = get_hub();
+	hub.publish("-! Run stopped on an input error. The changes staged so far:")
+		.await;
+	if let Err(err) = end_staged_changes(runtime, write_mode, run_journal, run_base_options).await {
+		let discarded = runtime.file_changes().take_changes().len();
+		hub.publish(format!(
+			"-! Cannot end the staged changes ({discarded} staged change(s) discarded). Cause: {err}"
+		))
+		.await;
+	}
+}
+
+/// Publish the recorded file changes as unified diffs, and save the plan in the run dir (to be applied with `aip apply`)
+async fn publish_plan(runtime: &Runtime, run_journal: Option<&RunJournal>) -> Result<()> {
+	let hub = get_hub();
+	let dir_context = runtime.dir_context();
+	let changes = runtime.file_changes().take_changes();
+
+	if changes.is_empty() {
+		hub.publish("\n==== Plan: no file changes").await;
+		return Ok(());
+	}
+
+	hub.publish(format!("\n==== Plan: {} file change(s)\n", changes.len())).await;
+	for change in changes.iter() {
+		hub.publish(change.to_unified_diff(dir_context)).await;
+	}
+
+	if let Some(run_journal) = run_journal {
+		let plan_file = save_plan(run_journal.run_dir(), &changes)?;
+		let plan_file = plan_file.diff(dir_context.wks_dir()).unwrap_or(plan_file);
+		hub.publish(format!(
+			"-> Plan saved to: {plan_file}\n   To apply it: aip apply {}",
+			run_journal.run_id()
+		))
+		.await;
+	}
+
+	Ok(())
+}
+
 fn get_input_label(input: &Value) -> Option<String> {
 	const LABEL_KEYS: &[&str] = &["path", "name", "label", "_label"];
 	for &key in LABEL_KEYS {
@@ -342,10 +616,23 @@ fn get_input_label(input: &Value) -> Option<String> {
 
 fn get_genai_info(agent: &Agent) -> String {
 	let mut genai_infos: Vec<String> = vec![];
+	let options = agent.options_as_ref();
 
-	if let Some(temp) = agent.options().temperature() {
+	if let Some(temp) = options.temperature() {
 		genai_infos.push(format!("temperature: {temp}"));
 	}
+	if let Some(max_tokens) = options.max_tokens() {
+		genai_infos.push(format!("max_tokens: {max_tokens}"));
+	}
+	if let Some(top_p) = options.top_p() {
+		genai_infos.push(format!("top_p: {top_p}"));
+	}
+	if let Some(stop_sequences) = options.stop_sequences() {
+		genai_infos.push(format!("stop_sequences: {stop_sequences:?}"));
+	}
+	if let Some(reasoning_effort) = options.reasoning_effort() {
+		genai_infos.push(format!("reasoning_effort: {reasoning_effort}"));
+	}
 
 	if genai_infos.is_empty() {
 		"".to_string()
@@ -365,4 +652,8 @@ mod tests_run_agent_llm;
 #[path = "../_tests/tests_run_agent_script.rs"]
 mod tests_run_agent_script;
 
+#[cfg(test)]
+#[path = "../_tests/tests_run_agent_mock.rs"]
+mod tests_run_agent_mock;
+
 // endregion: --- Tests
@@ -1,28 +1,59 @@
-use crate::Result;
-use crate::agent::{Agent, PromptPart};
-use crate::hub::get_hub;
+use crate::agent::{Agent, AgentOptions, PromptPart, find_agent_relative_file};
+use crate::dir_context::DirContext;
+use crate::hub::{HubEvent, get_hub};
 use crate::pricing::price_it;
 use crate::run::literals::Literals;
-use crate::run::{DryMode, RunBaseOptions, Runtime};
-use crate::script::{AipackCustom, FromValue};
+use crate::run::run_attachment::Attachment;
+use crate::run::run_cache::{AiCache, CachedAiResponse};
+use crate::run::run_command::DEFAULT_CONCURRENCY;
+use crate::run::run_report::{InputReporter, InputStage, add_usage, round_price};
+use crate::run::run_tool::exec_tool_call;
+use crate::run::{DryMode, GenaiClients, RunBaseOptions, Runtime};
+use crate::script::{AipackCustom, DataResponse, FromValue};
 use crate::support::W;
-use crate::support::hbs::hbs_render;
-use crate::support::text::{format_duration, format_num};
-use genai::ModelName;
+use crate::support::hbs::{HbsAttach, HbsIncludeResolver, hbs_render_prompt};
+use crate::support::jsons::validate_json_schema;
+use crate::support::md::outer_block_content_or_raw;
+use crate::support::text::{format_duration, format_num, truncate_with_ellipsis};
+use crate::{Error, Result};
 use genai::adapter::AdapterKind;
-use genai::chat::{ChatMessage, ChatRequest, ChatResponse, MetaUsage};
-use mlua::IntoLua;
+use genai::chat::{
+	ChatMessage, ChatOptions, ChatRequest, ChatResponse, ChatRole, ChatStreamEvent, ChatStreamResponse, ContentPart,
+	MessageContent, MetaUsage, StreamEnd, Tool,
+};
+use genai::{Client, ModelName};
+use mlua::{IntoLua, LuaSerdeExt as _};
 use serde::Serialize;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::Arc;
 use tokio::time::Instant;
+use tokio_stream::StreamExt;
+
+/// The default max number of AI turns per input (when the `# Output` returns `aipack.follow_up(..)`)
+const DEFAULT_MAX_TURNS: usize = 5;
+
+/// The max number of tool calls rounds for one AI request (when the agent has `# Tools`)
+const MAX_TOOL_ROUNDS: usize = 10;
+
+/// The default max number of retries of an AI call on transient errors
+/// Note: No retry by default (opt-in with the `retry_max` option), to keep the previous behavior
+const DEFAULT_RETRY_MAX: usize = 0;
+
+/// The default initial delay before retrying (doubled on each retry)
+const DEFAULT_RETRY_BACKOFF_MS: u64 = 1000;
+
+/// The max delay between two retries
+const MAX_RETRY_BACKOFF_MS: u64 = 30_000;
 
 // region:    --- AiResponse
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AiResponse {
 	pub content: Option<String>,
 	pub reasoning_content: Option<String>,
+	/// The parsed and validated content when the agent has an `output_schema`
+	pub json: Option<Value>,
 	pub model_name: ModelName,
 	pub adapter_kind: AdapterKind,
 	pub usage: MetaUsage,
@@ -37,6 +68,8 @@ impl IntoLua for AiResponse {
 
 		table.set("content", self.content.into_lua(lua)?)?;
 		table.set("reasoning_content", self.reasoning_content.into_lua(lua)?)?;
+		let json = self.json.map(|json| lua.to_value(&json)).transpose()?;
+		table.set("json", json)?;
 		table.set("model_name", self.model_name.into_lua(lua)?)?;
 		table.set("adapter_kind", self.adapter_kind.as_str().into_lua(lua)?)?;
 		table.set("usage", W(&self.usage).into_lua(lua)?)?;
@@ -48,6 +81,30 @@ impl IntoLua for AiResponse {
 	}
 }
 
+/// Implementations for various test.
+#[cfg(test)]
+impl AiResponse {
+	/// Creates a new `AiResponse` with the specified usage and price. (for test)
+	pub fn new_for_test(prompt_tokens: i32, completion_tokens: i32, price_usd: f64) -> Self {
+		AiResponse {
+			content: None,
+			reasoning_content: None,
+			json: None,
+			model_name: ModelName::from("gpt-4o-mini"),
+			adapter_kind: AdapterKind::OpenAI,
+			usage: MetaUsage {
+				prompt_tokens: Some(prompt_tokens),
+				completion_tokens: Some(completion_tokens),
+				total_tokens: Some(prompt_tokens + completion_tokens),
+				..Default::default()
+			},
+			price_usd: Some(price_usd),
+			duration_sec: 1.5,
+			info: String::new(),
+		}
+	}
+}
+
 impl IntoLua for W<&MetaUsage> {
 	fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
 		let table = lua.create_table()?;
@@ -133,6 +190,7 @@ impl RunAgentInputResponse {
 /// - Render the prompt sections
 /// - Send the AI
 /// - Execute Output
+/// - Loop on AI + Output while the Output returns a `aipack.follow_up(..)` (up to `max_turns`)
 ///
 /// Note 1: For now, this will create a new Lua engine.
 ///         This is likely to stay as it creates a strong segregation between input execution
@@ -145,9 +203,9 @@ pub async fn run_agent_input(
 	input: Value,
 	literals: &Literals,
 	run_base_options: &RunBaseOptions,
+	input_reporter: &InputReporter,
 ) -> Result<Option<RunAgentInputResponse>> {
 	let hub = get_hub();
-	let client = runtime.genai_client();
 
 	// -- Build the scope
 	// Fix me: Probably need to get the engine from the arg
@@ -170,9 +228,16 @@ pub async fn run_agent_input(
 	};
 
 	// skip input if aipack action is sent
-	let data = match AipackCustom::from_value(data)? {
+	let (data, options_to_merge, data_attachments) = match AipackCustom::from_value(data)? {
 		// If it is not a AipackCustom the data is the orginal value
-		FromValue::OriginalValue(data) => data,
+		FromValue::OriginalValue(data) => (data, None, None),
+
+		// The data, and the options override and attachments for this input only
+		FromValue::AipackCustom(AipackCustom::DataResponse(DataResponse {
+			data,
+			options,
+			attachments,
+		})) => (data.unwrap_or_default(), options, attachments),
 
 		// If we have a skip, we can skip
 		FromValue::AipackCustom(AipackCustom::Skip { reason }) => {
@@ -180,6 +245,7 @@ pub async fn run_agent_input(
 
 			hub.publish(format!("-! Aipack Skip input at Data stage: {label}{reason_txt}"))
 				.await;
+			input_reporter.set_skipped();
 			return Ok(None);
 		}
 
@@ -192,22 +258,66 @@ pub async fn run_agent_input(
 		}
 	};
 
+	// -- Override the agent options for this input (from the Data stage `aipack.data_response`)
+	let agent_ov: Option<Agent> = match options_to_merge {
+		Some(options_to_merge) => {
+			let options_to_merge = AgentOptions::from_input_options_value(options_to_merge)?;
+			let options_ov = agent.options_as_ref().merge_new(options_to_merge)?;
+			Some(agent.new_merge(options_ov)?)
+		}
+		None => None,
+	};
+	let agent = agent_ov.as_ref().unwrap_or(agent);
+
+	input_reporter.set_stage(InputStage::Ai);
+
 	let data_scope = HashMap::from([("data".to_string(), data.clone())]);
 
 	// -- Execute genai if we have an instruction
 	let mut chat_messages: Vec<ChatMessage> = Vec::new();
 	let data_scope = serde_json::to_value(data_scope)?;
+	let include_resolver: HbsIncludeResolver = {
+		let agent = agent.clone();
+		let dir_context = runtime.dir_context().clone();
+		Arc::new(move |path: &str| find_agent_relative_file(path, &agent, &dir_context))
+	};
+	let dir_context = runtime.dir_context();
 	for prompt_part in agent.prompt_parts() {
 		let PromptPart { kind, content } = prompt_part;
-		let content = hbs_render(content, &data_scope)?;
+		let (content, attachments) = hbs_render_prompt(content, &data_scope, include_resolver.clone())?;
+		let attachments: Vec<Attachment> = attachments
+			.into_iter()
+			.map(|HbsAttach { path, content_type }| Attachment::new(path, content_type))
+			.collect();
 		// For now, only add if not empty
-		if !content.trim().is_empty() {
+		if !content.trim().is_empty() || !attachments.is_empty() {
 			chat_messages.push(ChatMessage {
 				role: kind.into(),
-				content: content.into(),
+				content: new_message_content(content, &attachments, dir_context)?,
 			})
 		}
 	}
+
+	// -- Add the Data stage attachments to the last user message
+	if let Some(data_attachments) = data_attachments {
+		let data_attachments = Attachment::list_from_value(data_attachments)?;
+		if !data_attachments.is_empty() {
+			let user_msg = chat_messages
+				.iter_mut()
+				.rev()
+				.find(|msg| matches!(msg.role, ChatRole::User))
+				.ok_or("aipack.data_response attachments require an # Instruction (user prompt part)")?;
+			let mut parts = match std::mem::replace(&mut user_msg.content, MessageContent::Parts(Vec::new())) {
+				MessageContent::Text(text) => vec![ContentPart::from_text(text)],
+				MessageContent::Parts(parts) => parts,
+				_ => Vec::new(),
+			};
+			for attachment in data_attachments.iter() {
+				parts.push(attachment.load_content_part(dir_context)?);
+			}
+			user_msg.content = MessageContent::from_parts(parts);
+		}
+	}
 	// let inst = hbs_render(agent.inst(), &data_scope)?;
 
 	let is_inst_empty = chat_messages.is_empty();
@@ -216,12 +326,8 @@ pub async fn run_agent_input(
 	if run_base_options.verbose() {
 		hub.publish("\n").await;
 		for msg in chat_messages.iter() {
-			hub.publish(format!(
-				"-- {}:\n{}",
-				msg.role,
-				msg.content.text_as_str().unwrap_or_default()
-			))
-			.await;
+			hub.publish(format!("-- {}:\n{}", msg.role, message_content_display(&msg.content)))
+				.await;
 		}
 	}
 
@@ -230,109 +336,565 @@ pub async fn run_agent_input(
 		return Ok(None);
 	}
 
-	// -- Now execute the instruction
-	let model_resolved = agent.model_resolved();
+	// -- Now execute the instruction (and the eventual follow-up turns)
+	let max_turns = agent.options().max_turns().unwrap_or(DEFAULT_MAX_TURNS);
+	let mut turn: usize = 1;
+
+	loop {
+		let ai_response: Option<AiResponse> = if !is_inst_empty {
+			input_reporter.set_stage(InputStage::Ai);
+			let ai_response = exec_ai(runtime, agent, literals, label, chat_messages.clone(), run_base_options).await?;
+			input_reporter.add_ai_response(&ai_response);
+			Some(ai_response)
+		}
+		// if we do not have an instruction, just return null
+		else {
+			hub.publish("-! No instruction, skipping genai.").await;
+			None
+		};
+
+		// -- if dry_mode res, we stop
+		if matches!(run_base_options.dry_mode(), DryMode::Res) {
+			return Ok(None);
+		}
 
-	let ai_response: Option<AiResponse> = if !is_inst_empty {
-		let chat_req = ChatRequest::from_messages(chat_messages);
+		// -- Exec output
+		let Some(output_script) = agent.output_script() else {
+			return Ok(ai_response.map(RunAgentInputResponse::AiReponse));
+		};
 
-		hub.publish(format!("-> Sending rendered instruction to {model_resolved} ..."))
-			.await;
+		input_reporter.set_stage(InputStage::Output);
 
-		let start = Instant::now();
-		let chat_res = client
-			.exec_chat(model_resolved, chat_req, Some(agent.genai_chat_options()))
-			.await?;
-		let duration = start.elapsed();
-		let duration_msg = format!("Duration: {}", format_duration(duration));
-		// this is for the duration in second with 3 digit for milli (for the AI Response)
-		let duration_sec = duration.as_secs_f64(); // Convert to f64
-		let duration_sec = (duration_sec * 1000.0).round() / 1000.0; // Round to 3 decimal places
-
-		let mut info = duration_msg;
-
-		let price_usd = get_price(&chat_res);
-		if let Some(price_usd) = price_usd {
-			info = format!("{info} | ~${price_usd}")
-		}
+		// Note: Needed for the eventual follow-up (or max turns), as the ai_response is moved to the lua scope
+		let last_ai_response = ai_response.clone();
 
-		let usage_msg = format_usage(&chat_res.usage);
-		info = format!("{info} | {usage_msg}");
+		let lua_engine = runtime.new_lua_engine()?;
+		let lua_scope = lua_engine.create_table()?;
+		lua_scope.set("input", lua_engine.serde_to_lua_value(input.clone())?)?;
+		lua_scope.set("data", lua_engine.serde_to_lua_value(data.clone())?)?;
+		lua_scope.set("before_all", lua_engine.serde_to_lua_value(before_all_result.clone())?)?;
+		lua_scope.set("ai_response", ai_response)?;
+		lua_scope.set("turn", turn)?;
+		lua_scope.set("CTX", literals.to_lua(&lua_engine)?)?;
+		lua_scope.set("options", agent.options_as_ref())?;
 
-		hub.publish(format!("<- ai_response content received - {info}")).await;
+		let lua_value = lua_engine.eval(output_script, Some(lua_scope), Some(&[agent_dir_str]))?;
+		let output_response = serde_json::to_value(lua_value)?;
 
-		let chat_res_mode_iden = chat_res.model_iden.clone();
-		let ChatResponse {
-			content,
-			reasoning_content,
-			usage,
-			..
-		} = chat_res;
+		// -- Check if the output asks for a follow-up turn
+		// Note: The other AipackCustom (e.g., Skip) are handled by the caller, so, we pass the original value
+		let FromValue::AipackCustom(AipackCustom::FollowUp { message }) =
+			AipackCustom::from_value(output_response.clone())?
+		else {
+			return Ok(Some(RunAgentInputResponse::OutputResponse(output_response)));
+		};
 
-		let ai_response_content = content.and_then(|c| c.text_into_string());
-		let ai_response_reasoning_content = reasoning_content;
+		if is_inst_empty {
+			return Err("-! Aipack FollowUp is not supported when the agent has no instruction".into());
+		}
 
-		if run_base_options.verbose() {
+		// Note: The last AI response is the response of the input (rather than dropping it)
+		if turn >= max_turns {
 			hub.publish(format!(
-				"\n-- AI Output (model: {} | adapter: {})\n\n{}\n",
-				chat_res_mode_iden.model_name,
-				chat_res_mode_iden.adapter_kind,
-				ai_response_content.as_deref().unwrap_or_default()
+				"-! Aipack FollowUp ignored, max turns reached ({max_turns}) for input: {label}. Returning the last AI response."
 			))
 			.await;
+			return Ok(last_ai_response.map(RunAgentInputResponse::AiReponse));
 		}
 
-		let info = format!(
-			"{info} | Model: {} | Adapter: {}",
-			chat_res_mode_iden.model_name, chat_res_mode_iden.adapter_kind,
-		);
+		hub.publish(format!("-> Aipack FollowUp (turn {} of max {max_turns})", turn + 1))
+			.await;
 
-		Some(AiResponse {
-			content: ai_response_content,
-			reasoning_content: ai_response_reasoning_content,
-			model_name: chat_res_mode_iden.model_name,
-			adapter_kind: chat_res_mode_iden.adapter_kind,
-			duration_sec,
-			price_usd,
-			usage,
-			info,
-		})
+		let ai_content = last_ai_response.and_then(|r| r.content);
+		chat_messages.push(ChatMessage::assistant(ai_content.unwrap_or_default()));
+		chat_messages.push(ChatMessage::user(message));
+		turn += 1;
 	}
-	// if we do not have an instruction, just return null
-	else {
-		hub.publish("-! No instruction, skipping genai.").await;
-		None
+}
+
+/// Send the chat messages to the AI and build the AiResponse
+///
+/// When the agent has `# Tools`, the tool calls are executed and sent back to the AI
+/// until it gives a final answer (up to `MAX_TOOL_ROUNDS`).
+/// Note: The price and usage are the sum of all of the AI calls.
+async fn exec_ai(
+	runtime: &Runtime,
+	agent: &Agent,
+	literals: &Literals,
+	label: &str,
+	chat_messages: Vec<ChatMessage>,
+	run_base_options: &RunBaseOptions,
+) -> Result<AiResponse> {
+	let hub = get_hub();
+	let clients = runtime.genai_clients();
+	let model_resolved = agent.model_resolved();
+
+	let tools: Vec<Tool> = agent.tools().iter().map(Tool::from).collect();
+	let has_tools = !tools.is_empty();
+	// Note: For now, no streaming when tools, as the tool calls are not captured by the stream
+	let stream = agent.options().stream().unwrap_or(false) && !has_tools;
+	let concurrency = agent.options().input_concurrency().unwrap_or(DEFAULT_CONCURRENCY);
+	let mut stream_publisher = stream.then(|| StreamPublisher::new(label, concurrency <= 1));
+
+	// The model followed by the fallback models
+	let models: Vec<ModelName> = std::iter::once(model_resolved.clone())
+		.chain(agent.options().resolve_fallback_models().into_iter().map(ModelName::from))
+		.collect();
+	let mut model_idx: usize = 0;
+
+	// -- Check the cache (when enabled)
+	// Note: No cache when tools, as the tool handlers need to be executed
+	let use_cache = agent.options().cache().unwrap_or(false) && !run_base_options.no_cache() && !has_tools;
+	let cache = use_cache.then(|| AiCache::new(runtime.dir_context()));
+	let cache_key = match cache {
+		Some(_) => Some(AiCache::key(
+			model_resolved,
+			agent.genai_chat_options(),
+			&chat_messages,
+		)?),
+		None => None,
 	};
+	if let (Some(cache), Some(cache_key), false) =
+		(cache.as_ref(), cache_key.as_deref(), run_base_options.refresh_cache())
+	{
+		if let Some(cached) = cache.get(cache_key) {
+			return ai_response_from_cache(agent, cached).await;
+		}
+	}
 
-	// -- if dry_mode res, we stop
-	if matches!(run_base_options.dry_mode(), DryMode::Res) {
-		return Ok(None);
+	let mut chat_req = ChatRequest::from_messages(chat_messages);
+	if has_tools {
+		chat_req = chat_req.with_tools(tools);
 	}
 
-	// -- Exec output
-	let res = if let Some(output_script) = agent.output_script() {
-		let lua_engine = runtime.new_lua_engine()?;
-		let lua_scope = lua_engine.create_table()?;
-		lua_scope.set("input", lua_engine.serde_to_lua_value(input)?)?;
-		lua_scope.set("data", lua_engine.serde_to_lua_value(data)?)?;
-		lua_scope.set("before_all", lua_engine.serde_to_lua_value(before_all_result)?)?;
-		lua_scope.set("ai_response", ai_response)?;
-		lua_scope.set("CTX", literals.to_lua(&lua_engine)?)?;
-		lua_scope.set("options", agent.options_as_ref())?;
+	hub.publish(format!("-> Sending rendered instruction to {model_resolved} ..."))
+		.await;
+
+	let start = Instant::now();
+	let mut tool_rounds: usize = 0;
+	let mut price_usd: Option<f64> = None;
+	let mut usage = MetaUsage::default();
+
+	let chat_res = loop {
+		let chat_res = exec_chat_with_fallback(
+			clients,
+			agent,
+			&models,
+			&mut model_idx,
+			&chat_req,
+			stream_publisher.as_mut(),
+		)
+		.await?;
+
+		if let Some(call_price_usd) = get_price(&chat_res) {
+			price_usd = Some(price_usd.unwrap_or_default() + call_price_usd);
+		}
+		add_usage(&mut usage, &chat_res.usage);
 
-		let lua_value = lua_engine.eval(output_script, Some(lua_scope), Some(&[agent_dir_str]))?;
-		let output_response = serde_json::to_value(lua_value)?;
+		// -- If not a tool calls response, we have the final response
+		if !has_tools || chat_res.tool_calls().is_none() {
+			break chat_res;
+		}
 
-		Some(RunAgentInputResponse::OutputResponse(output_response))
-	} else {
-		ai_response.map(RunAgentInputResponse::AiReponse)
+		tool_rounds += 1;
+		if tool_rounds > MAX_TOOL_ROUNDS {
+			return Err(format!("AI tool calls exceeded the max tool rounds ({MAX_TOOL_ROUNDS})").into());
+		}
+
+		let tool_calls = chat_res.into_tool_calls().unwrap_or_default();
+		chat_req = chat_req.append_message(tool_calls.clone());
+		for tool_call in tool_calls.iter() {
+			let tool_response = exec_tool_call(runtime, agent, literals, tool_call).await;
+			chat_req = chat_req.append_message(tool_response);
+		}
+	};
+	let duration = start.elapsed();
+	let duration_msg = format!("Duration: {}", format_duration(duration));
+	// this is for the duration in second with 3 digit for milli (for the AI Response)
+	let duration_sec = duration.as_secs_f64(); // Convert to f64
+	let duration_sec = (duration_sec * 1000.0).round() / 1000.0; // Round to 3 decimal places
+
+	let mut info = duration_msg;
+
+	let price_usd = price_usd.map(round_price);
+	if let Some(price_usd) = price_usd {
+		info = format!("{info} | ~${price_usd}")
+	}
+
+	let usage_msg = format_usage(&usage);
+	info = format!("{info} | {usage_msg}");
+	if tool_rounds > 0 {
+		info = format!("{info} | Tool rounds: {tool_rounds}");
+	}
+
+	hub.publish(format!("<- ai_response content received - {info}")).await;
+
+	let chat_res_mode_iden = chat_res.model_iden.clone();
+	let ChatResponse {
+		content,
+		reasoning_content,
+		..
+	} = chat_res;
+
+	let ai_response_content = content.and_then(|c| c.text_into_string());
+	let ai_response_reasoning_content = reasoning_content;
+
+	if run_base_options.verbose() {
+		hub.publish(format!(
+			"\n-- AI Output (model: {} | adapter: {})\n\n{}\n",
+			chat_res_mode_iden.model_name,
+			chat_res_mode_iden.adapter_kind,
+			ai_response_content.as_deref().unwrap_or_default()
+		))
+		.await;
+	}
+
+	let mut info = format!(
+		"{info} | Model: {} | Adapter: {}",
+		chat_res_mode_iden.model_name, chat_res_mode_iden.adapter_kind,
+	);
+	if model_idx > 0 {
+		info = format!("{info} | Fallback from: {model_resolved}");
+	}
+
+	let json = match agent.options().output_schema() {
+		Some(schema) => Some(parse_validate_output_json(
+			ai_response_content.as_deref().unwrap_or_default(),
+			schema,
+		)?),
+		None => None,
 	};
 
-	Ok(res)
+	// -- Save to the cache (when enabled)
+	if let (Some(cache), Some(cache_key)) = (cache.as_ref(), cache_key.as_deref()) {
+		let cached = CachedAiResponse {
+			model_name: chat_res_mode_iden.model_name.clone(),
+			adapter_kind: chat_res_mode_iden.adapter_kind,
+			content: ai_response_content.clone(),
+			reasoning_content: ai_response_reasoning_content.clone(),
+		};
+		if let Err(err) = cache.save(cache_key, &cached) {
+			hub.publish(format!("-! Cannot save AI response to cache. Cause: {err}")).await;
+		}
+	}
+
+	Ok(AiResponse {
+		content: ai_response_content,
+		reasoning_content: ai_response_reasoning_content,
+		json,
+		model_name: chat_res_mode_iden.model_name,
+		adapter_kind: chat_res_mode_iden.adapter_kind,
+		duration_sec,
+		price_usd,
+		usage,
+		info,
+	})
 }
 
 // region:    --- Support
 
+/// Build the AiResponse from a cache hit (no cost, no usage)
+async fn ai_response_from_cache(agent: &Agent, cached: CachedAiResponse) -> Result<AiResponse> {
+	let CachedAiResponse {
+		model_name,
+		adapter_kind,
+		content,
+		reasoning_content,
+	} = cached;
+
+	let info = format!("Cache hit | Model: {model_name} | Adapter: {adapter_kind}");
+	get_hub().publish(format!("<- ai_response content received - {info}")).await;
+
+	let json = match agent.options().output_schema() {
+		Some(schema) => Some(parse_validate_output_json(
+			content.as_deref().unwrap_or_default(),
+			schema,
+		)?),
+		None => None,
+	};
+
+	Ok(AiResponse {
+		content,
+		reasoning_content,
+		json,
+		model_name,
+		adapter_kind,
+		usage: MetaUsage::default(),
+		price_usd: None,
+		duration_sec: 0.,
+		info,
+	})
+}
+
+/// Execute the chat request with the current model (`models[*model_idx]`), retrying on transient errors
+/// with an exponential backoff, and then falling back to the next models.
+///
+/// Note: The `model_idx` is updated, so that the next calls (e.g., tool rounds) continue with the model that answered.
+async fn exec_chat_with_fallback(
+	clients: &GenaiClients,
+	agent: &Agent,
+	models: &[ModelName],
+	model_idx: &mut usize,
+	chat_req: &ChatRequest,
+	mut stream_publisher: Option<&mut StreamPublisher>,
+) -> Result<ChatResponse> {
+	let hub = get_hub();
+	let options = agent.options();
+	let retry_max = options.retry_max().unwrap_or(DEFAULT_RETRY_MAX);
+	let retry_backoff_ms = options.retry_backoff_ms().unwrap_or(DEFAULT_RETRY_BACKOFF_MS);
+
+	loop {
+		let model = &models[*model_idx];
+		let client = clients.for_model(model)?;
+		let mut attempt: usize = 0;
+
+		let err = loop {
+			let res = if let Some(stream_publisher) = stream_publisher.as_deref_mut() {
+				exec_chat_stream(
+					&client,
+					model,
+					chat_req.clone(),
+					agent.genai_chat_options(),
+					stream_publisher,
+				)
+				.await
+			} else {
+				client
+					.exec_chat(model, chat_req.clone(), Some(agent.genai_chat_options()))
+					.await
+					.map_err(Error::from)
+			};
+
+			let err = match res {
+				Ok(chat_res) => return Ok(chat_res),
+				Err(err) if is_transient_ai_error(&err) => err,
+				Err(err) => return Err(err),
+			};
+
+			if attempt >= retry_max {
+				break err;
+			}
+			attempt += 1;
+
+			let delay_ms = retry_delay_ms(retry_backoff_ms, attempt);
+			hub.publish(format!(
+				"-! AI call to {model} failed (retry {attempt}/{retry_max} in {delay_ms}ms). Cause: {err}"
+			))
+			.await;
+			tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
+		};
+
+		// -- Fallback to the next model, if any
+		let Some(next_model) = models.get(*model_idx + 1) else {
+			return Err(err);
+		};
+		hub.publish(format!(
+			"-! AI call to {model} failed. Falling back to {next_model}. Cause: {err}"
+		))
+		.await;
+		*model_idx += 1;
+	}
+}
+
+/// Build the message content, as multi-part content when there are attachments
+fn new_message_content(
+	content: String,
+	attachments: &[Attachment],
+	dir_context: &DirContext,
+) -> Result<MessageContent> {
+	if attachments.is_empty() {
+		return Ok(content.into());
+	}
+
+	let mut parts = vec![ContentPart::from_text(content)];
+	for attachment in attachments {
+		parts.push(attachment.load_content_part(dir_context)?);
+	}
+
+	Ok(MessageContent::from_parts(parts))
+}
+
+/// The message content for the verbose display (the attachments are not printed, just their content type)
+fn message_content_display(content: &MessageContent) -> String {
+	match content {
+		MessageContent::Parts(parts) => parts
+			.iter()
+			.map(|part| match part {
+				ContentPart::Text(text) => text.to_string(),
+				ContentPart::Image { content_type, .. } => format!("[attachment: {content_type}]"),
+			})
+			.collect::<Vec<_>>()
+			.join("\n"),
+		other => other.text_as_str().unwrap_or_default().to_string(),
+	}
+}
+
+/// Returns true if the error is worth a retry (rate limit, server errors, network issues)
+fn is_transient_ai_error(err: &Error) -> bool {
+	let Error::GenAI(genai_err) = err else {
+		return false;
+	};
+
+	match genai_err {
+		genai::Error::WebModelCall { webc_error, .. } | genai::Error::WebAdapterCall { webc_error, .. } => {
+			match webc_error {
+				genai::webc::Error::ResponseFailedStatus { status, .. } => {
+					status.as_u16() == 429 || status.is_server_error()
+				}
+				genai::webc::Error::Reqwest(_) => true,
+				_ => false,
+			}
+		}
+		genai::Error::WebStream { .. } => true,
+		// The stream errors (e.g., connection closed during the stream, or 429 / 5xx at the stream start)
+		genai::Error::ReqwestEventSource(es_err) => match es_err {
+			reqwest_eventsource::Error::Transport(_) => true,
+			reqwest_eventsource::Error::InvalidStatusCode(status, _) => {
+				status.as_u16() == 429 || status.is_server_error()
+			}
+			_ => false,
+		},
+		_ => false,
+	}
+}
+
+/// The exponential backoff delay for a given retry attempt (starting at 1)
+fn retry_delay_ms(retry_backoff_ms: u64, attempt: usize) -> u64 {
+	let factor = 2u64.saturating_pow(attempt.saturating_sub(1) as u32);
+	retry_backoff_ms.saturating_mul(factor).min(MAX_RETRY_BACKOFF_MS)
+}
+
+/// Parse the AI response content as json (allowing it to be wrapped in a markdown code block)
+/// and validate it against the agent `output_schema`.
+fn parse_validate_output_json(content: &str, schema: &Value) -> Result<Value> {
+	let snippet = || truncate_with_ellipsis(content, 300, "...").to_string();
+
+	let json_content = outer_block_content_or_raw(content);
+	let json: Value = serde_json::from_str(&json_content).map_err(|err| Error::OutputSchemaValidation {
+		cause: format!("Response is not valid json. {err}"),
+		snippet: snippet(),
+	})?;
+
+	validate_json_schema(&json, schema).map_err(|cause| Error::OutputSchemaValidation {
+		cause,
+		snippet: snippet(),
+	})?;
+
+	Ok(json)
+}
+
+/// Publishes the stream chunks of the AI calls of one input to the hub
+///
+/// - When the inputs run one at a time (`live`), the chunks are published as they arrive.
+/// - When the inputs run concurrently, the chunks are buffered, and published at the end of the stream
+///   (with the input label), so that the chunks of the different inputs do not mix.
+/// - Once chunks of an attempt were published, the next attempts (retry or fallback) are not published
+///   (their content is still in the AI response).
+struct StreamPublisher {
+	label: String,
+	live: bool,
+	published: bool,
+}
+
+impl StreamPublisher {
+	fn new(label: &str, live: bool) -> Self {
+		Self {
+			label: label.to_string(),
+			live,
+			published: false,
+		}
+	}
+}
+
+/// Execute the chat as a stream, publishing the content chunks to the hub (see `StreamPublisher`),
+/// and assemble the final `ChatResponse` so that the rest of the flow is the same as `exec_chat`.
+async fn exec_chat_stream(
+	client: &Client,
+	model: &str,
+	chat_req: ChatRequest,
+	chat_options: &ChatOptions,
+	stream_publisher: &mut StreamPublisher,
+) -> Result<ChatResponse> {
+	let hub = get_hub();
+	let publish = !stream_publisher.published;
+	let publish_live = publish && stream_publisher.live;
+
+	// Note: We need the captures to rebuild the ChatResponse at the end of the stream
+	let chat_options = chat_options
+		.clone()
+		.with_capture_content(true)
+		.with_capture_reasoning_content(true)
+		.with_capture_usage(true);
+
+	let ChatStreamResponse { mut stream, model_iden } =
+		client.exec_chat_stream(model, chat_req, Some(&chat_options)).await?;
+
+	// Fallback content in case the adapter does not capture it
+	let mut streamed_content = String::new();
+	let mut stream_end: Option<StreamEnd> = None;
+
+	while let Some(event) = stream.next().await {
+		let event = match event {
+			Ok(event) => event,
+			Err(err) => {
+				// Make sure the next hub message starts on a new line
+				if publish_live && !streamed_content.is_empty() {
+					hub.publish(HubEvent::AiStreamChunk("\n".into())).await;
+				}
+				return Err(err.into());
+			}
+		};
+		match event {
+			ChatStreamEvent::Start => (),
+			ChatStreamEvent::Chunk(chunk) => {
+				streamed_content.push_str(&chunk.content);
+				if publish_live {
+					stream_publisher.published = true;
+					hub.publish(HubEvent::AiStreamChunk(chunk.content.into())).await;
+				}
+			}
+			// NOTE: For now, the reasoning chunks are not displayed, just captured at the end
+			ChatStreamEvent::ReasoningChunk(_) => (),
+			ChatStreamEvent::End(end) => stream_end = Some(end),
+		}
+	}
+
+	if publish && !streamed_content.is_empty() {
+		if publish_live {
+			// Make sure the next hub message starts on a new line
+			hub.publish(HubEvent::AiStreamChunk("\n".into())).await;
+		} else {
+			stream_publisher.published = true;
+			let buffered = format!(
+				"-- AI stream for input: {}\n{streamed_content}\n",
+				stream_publisher.label
+			);
+			hub.publish(HubEvent::AiStreamChunk(buffered.into())).await;
+		}
+	}
+
+	let StreamEnd {
+		captured_usage,
+		captured_content,
+		captured_reasoning_content,
+	} = stream_end.unwrap_or_default();
+
+	let content = captured_content.or_else(|| {
+		if streamed_content.is_empty() {
+			None
+		} else {
+			Some(MessageContent::from_text(streamed_content))
+		}
+	});
+
+	Ok(ChatResponse {
+		content,
+		reasoning_content: captured_reasoning_content,
+		model_iden,
+		usage: captured_usage.unwrap_or_default(),
+	})
+}
+
 fn get_price(chat_res: &ChatResponse) -> Option<f64> {
 	let provider = chat_res.model_iden.adapter_kind.as_lower_str();
 	let model_name = &*chat_res.model_iden.model_name;
@@ -362,3 +924,50 @@ fn format_usage(usage: &MetaUsage) -> String {
 }
 
 // endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_run_input_retry_delay_ms() -> Result<()> {
+		// -- Exec & Check
+		assert_eq!(retry_delay_ms(1000, 1), 1000);
+		assert_eq!(retry_delay_ms(1000, 2), 2000);
+		assert_eq!(retry_delay_ms(1000, 3), 4000);
+		assert_eq!(retry_delay_ms(1000, 10), MAX_RETRY_BACKOFF_MS);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_run_input_is_transient_ai_error() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_err = |status: u16| -> Result<Error> {
+			Ok(Error::GenAI(genai::Error::WebAdapterCall {
+				adapter_kind: AdapterKind::OpenAI,
+				webc_error: genai::webc::Error::ResponseFailedStatus {
+					status: status.try_into()?,
+					body: String::new(),
+				},
+			}))
+		};
+
+		// -- Exec & Check
+		assert!(is_transient_ai_error(&fx_err(429)?), "429 should be transient");
+		assert!(is_transient_ai_error(&fx_err(503)?), "503 should be transient");
+		assert!(!is_transient_ai_error(&fx_err(401)?), "401 should not be transient");
+		assert!(
+			!is_transient_ai_error(&Error::custom("some error")),
+			"custom should not be transient"
+		);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
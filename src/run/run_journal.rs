@@ -0,0 +1,464 @@
+use crate::dir_context::DirContext;
+use crate::run::run_backup::has_backup_to_undo;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+use simple_fs::{SPath, ensure_dir};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+
+const RUNS_DIR: &str = ".runs";
+const RUN_META_FILE: &str = "run.json";
+const JOURNAL_FILE: &str = "journal.jsonl";
+/// The number of run dirs kept in `.aipack/.runs/` (the oldest are removed when a run is created)
+/// Note: The unfinished journals and the backups not undone are never removed, and not counted (see `prune_run_dirs`)
+const MAX_RUNS_KEPT: usize = 20;
+
+/// The checkpoint journal of a run, in `.aipack/.runs/<run-id>/`.
+///
+/// - `run.json` has the run metadata (agent path, creation time, and if the run finished)
+/// - `journal.jsonl` has one line per completed input (label, input hash, output)
+///
+/// On `--resume <run-id>`, the inputs already in the journal are not run again, and their stored output is reused.
+///
+/// The inputs are recorded only with `--journal` (or `--resume`). Otherwise, the run dir is only used for the plan
+/// and the file backups, and is created on first use.
+///
+/// Note: Cheap to clone (Arc inner), so that it can be shared with the input tasks.
+#[derive(Debug, Clone)]
+pub struct RunJournal {
+	run_id: String,
+	run_dir: SPath,
+	journal_file: SPath,
+	/// When false, the completed inputs are not recorded
+	record_inputs: bool,
+	/// The outputs of the completed inputs by input hash
+	completed: Arc<HashMap<String, Value>>,
+	/// The completed inputs waiting for their staged file writes to be applied (see `defer`)
+	deferred: Arc<Mutex<Vec<JournalEntry>>>,
+	write_lock: Arc<Mutex<()>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RunMeta {
+	agent_path: String,
+	created: String,
+	/// False until all the inputs are completed (an unfinished journal can be resumed, so it is never pruned)
+	#[serde(default)]
+	finished: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+	input_idx: usize,
+	label: String,
+	input_hash: String,
+	output: Value,
+}
+
+impl JournalEntry {
+	fn new(input_idx: usize, label: &str, input_hash: &str, output: &Value) -> Self {
+		Self {
+			input_idx,
+			label: label.to_string(),
+			input_hash: input_hash.to_string(),
+			output: output.clone(),
+		}
+	}
+}
+
+/// Constructors
+impl RunJournal {
+	/// Create a new run journal (with a new run id), and remove the oldest run dirs.
+	///
+	/// - `record_inputs` - record the completed inputs (the run dir is created now, with its `run.json`)
+	pub fn create(dir_context: &DirContext, agent_path: &str, record_inputs: bool) -> Result<Self> {
+		prune_run_dirs(&runs_dir(dir_context), MAX_RUNS_KEPT - 1)?;
+
+		let now = OffsetDateTime::now_utc();
+		let run_id = format!(
+			"{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+			now.year(),
+			now.month() as u8,
+			now.day(),
+			now.hour(),
+			now.minute(),
+			now.second(),
+			now.millisecond()
+		);
+
+		let run_dir = run_dir(dir_context, &run_id);
+		if record_inputs {
+			ensure_dir(&run_dir)?;
+			let meta = RunMeta {
+				agent_path: agent_path.to_string(),
+				created: now.unix_timestamp().to_string(),
+				finished: false,
+			};
+			save_meta(&run_dir, &meta)?;
+		}
+
+		Ok(RunJournal {
+			run_id,
+			journal_file: run_dir.join_str(JOURNAL_FILE),
+			run_dir,
+			record_inputs,
+			completed: Default::default(),
+			deferred: Default::default(),
+			write_lock: Default::default(),
+		})
+	}
+
+	/// Load an existing run journal to resume it
+	pub fn load(dir_context: &DirContext, run_id: &str, agent_path: &str) -> Result<Self> {
+		let run_dir = run_dir(dir_context, run_id);
+		let meta_file = run_dir.join_str(RUN_META_FILE);
+		if !meta_file.exists() {
+			return Err(Error::custom(format!(
+				"Cannot resume run '{run_id}'. No run found at '{run_dir}'"
+			)));
+		}
+
+		let mut meta = load_meta(&run_dir)?;
+		if meta.agent_path != agent_path {
+			return Err(Error::custom(format!(
+				"Cannot resume run '{run_id}'. It was for agent '{}' (not '{agent_path}')",
+				meta.agent_path
+			)));
+		}
+		// Note: Unfinished again until the resumed run completes
+		if meta.finished {
+			meta.finished = false;
+			save_meta(&run_dir, &meta)?;
+		}
+
+		let journal_file = run_dir.join_str(JOURNAL_FILE);
+		let mut completed = HashMap::new();
+		if journal_file.exists() {
+			let content = std::fs::read_to_string(journal_file.path())?;
+			// Note: An incomplete last line (e.g., interrupted write) is ignored
+			for entry in content
+				.lines()
+				.filter_map(|line| serde_json::from_str::<JournalEntry>(line).ok())
+			{
+				completed.insert(entry.input_hash, entry.output);
+			}
+		}
+
+		Ok(RunJournal {
+			run_id: run_id.to_string(),
+			run_dir,
+			journal_file,
+			record_inputs: true,
+			completed: Arc::new(completed),
+			deferred: Default::default(),
+			write_lock: Default::default(),
+		})
+	}
+}
+
+/// Getters
+impl RunJournal {
+	pub fn run_id(&self) -> &str {
+		&self.run_id
+	}
+
+	/// The `.aipack/.runs/<run-id>/` dir
+	pub fn run_dir(&self) -> &SPath {
+		&self.run_dir
+	}
+
+	/// True when the completed inputs are recorded (with `--journal` or `--resume`)
+	pub fn records_inputs(&self) -> bool {
+		self.record_inputs
+	}
+
+	/// Returns the stored output if this input was already completed
+	pub fn completed_output(&self, input_hash: &str) -> Option<&Value> {
+		self.completed.get(input_hash)
+	}
+}
+
+impl RunJournal {
+	/// Mark the journal as finished (all the inputs completed), so that its run dir can be pruned
+	pub fn finish(&self) -> Result<()> {
+		if !self.record_inputs {
+			return Ok(());
+		}
+		let mut meta = load_meta(&self.run_dir)?;
+		meta.finished = true;
+		save_meta(&self.run_dir, &meta)
+	}
+
+	/// Append a completed input to the journal
+	pub fn append(&self, input_idx: usize, label: &str, input_hash: &str, output: &Value) -> Result<()> {
+		self.append_entries(&[JournalEntry::new(input_idx, label, input_hash, output)])
+	}
+
+	/// Keep a completed input whose file writes are staged (e.g., `--review`), appended by `append_deferred`
+	/// once the writes are applied (never appended if they are not)
+	pub fn defer(&self, input_idx: usize, label: &str, input_hash: &str, output: &Value) {
+		let mut deferred = self.deferred.lock().unwrap_or_else(|err| err.into_inner());
+		deferred.push(JournalEntry::new(input_idx, label, input_hash, output));
+	}
+
+	/// Append the deferred completed inputs to the journal
+	pub fn append_deferred(&self) -> Result<()> {
+		let entries = std::mem::take(&mut *self.deferred.lock().unwrap_or_else(|err| err.into_inner()));
+		self.append_entries(&entries)
+	}
+
+	fn append_entries(&self, entries: &[JournalEntry]) -> Result<()> {
+		if entries.is_empty() {
+			return Ok(());
+		}
+		let mut content = String::new();
+		for entry in entries {
+			content.push_str(&serde_json::to_string(entry)?);
+			content.push('\n');
+		}
+
+		let _guard = self.write_lock.lock().unwrap_or_else(|err| err.into_inner());
+		let mut file = OpenOptions::new().create(true).append(true).open(self.journal_file.path())?;
+		file.write_all(content.as_bytes())?;
+
+		Ok(())
+	}
+
+	/// Returns the hash of an input (to match the inputs on resume)
+	pub fn input_hash(input: &Value) -> Result<String> {
+		let content = serde_json::to_string(input)?;
+		let hash = Sha1::digest(content.as_bytes());
+		Ok(hash.iter().map(|b| format!("{b:02x}")).collect())
+	}
+}
+
+fn load_meta(run_dir: &SPath) -> Result<RunMeta> {
+	let content = std::fs::read_to_string(run_dir.join_str(RUN_META_FILE).path())?;
+	Ok(serde_json::from_str(&content)?)
+}
+
+fn save_meta(run_dir: &SPath, meta: &RunMeta) -> Result<()> {
+	std::fs::write(
+		run_dir.join_str(RUN_META_FILE).path(),
+		serde_json::to_string_pretty(meta)?,
+	)?;
+	Ok(())
+}
+
+/// True if the run dir has a journal not finished (resumable)
+/// Note: A `run.json` that cannot be read is kept as well (to not lose a journal)
+fn is_unfinished_journal(run_dir: &SPath) -> bool {
+	if !run_dir.join_str(RUN_META_FILE).exists() {
+		return false;
+	}
+	load_meta(run_dir).map(|meta| !meta.finished).unwrap_or(true)
+}
+
+fn runs_dir(dir_context: &DirContext) -> SPath {
+	dir_context.aipack_paths().wks_aipack_dir().join_str(RUNS_DIR)
+}
+
+/// The `.aipack/.runs/<run-id>/` dir of a run
+pub fn run_dir(dir_context: &DirContext, run_id: &str) -> SPath {
+	runs_dir(dir_context).join_str(run_id)
+}
+
+/// The id of the latest run having this file in its run dir (e.g., `plan.json`)
+/// Note: The run ids are time based, so the latest is the greatest.
+pub fn latest_run_id_with_file(dir_context: &DirContext, file_name: &str) -> Result<Option<String>> {
+	let runs_dir = runs_dir(dir_context);
+	if !runs_dir.exists() {
+		return Ok(None);
+	}
+
+	let mut run_ids = Vec::new();
+	for entry in std::fs::read_dir(runs_dir.path())? {
+		let entry = entry?;
+		if entry.path().join(file_name).is_file() {
+			run_ids.push(entry.file_name().to_string_lossy().to_string());
+		}
+	}
+
+	Ok(run_ids.into_iter().max())
+}
+
+/// Remove the oldest run dirs of the runs dir, keeping the latest `keep` ones
+///
+/// - The unfinished journals (resumable with `--resume`) are never removed, and not counted
+/// - Same for the file backups not undone (for `aip undo <run-id>`)
+///
+/// Note: The run ids are time based, so the oldest are the smallest.
+fn prune_run_dirs(runs_dir: &SPath, keep: usize) -> Result<()> {
+	if !runs_dir.exists() {
+		return Ok(());
+	}
+
+	let mut run_dirs = Vec::new();
+	for entry in std::fs::read_dir(runs_dir.path())? {
+		let entry = entry?;
+		if entry.file_type()?.is_dir() {
+			let run_dir = SPath::from_path(entry.path())?;
+			if !is_unfinished_journal(&run_dir) && !has_backup_to_undo(&run_dir) {
+				run_dirs.push(run_dir);
+			}
+		}
+	}
+	run_dirs.sort_by(|a, b| a.to_str().cmp(b.to_str()));
+
+	let remove_count = run_dirs.len().saturating_sub(keep);
+	for run_dir in run_dirs.into_iter().take(remove_count) {
+		std::fs::remove_dir_all(run_dir.path())?;
+	}
+
+	Ok(())
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::assert_contains;
+	use crate::run::Runtime;
+	use serde_json::json;
+
+	#[test]
+	fn test_run_journal_create_append_load() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let dir_context = runtime.dir_context();
+		let journal = RunJournal::create(dir_context, "agent-journal.aip", true)?;
+		let input_one = RunJournal::input_hash(&json!("one"))?;
+		let input_two = RunJournal::input_hash(&json!("two"))?;
+
+		// -- Exec
+		journal.append(0, "one", &input_one, &json!("output one"))?;
+		let resumed = RunJournal::load(dir_context, journal.run_id(), "agent-journal.aip")?;
+		let other_agent_res = RunJournal::load(dir_context, journal.run_id(), "other.aip");
+		let unfinished_before = is_unfinished_journal(journal.run_dir());
+		journal.finish()?;
+		let unfinished_after = is_unfinished_journal(journal.run_dir());
+
+		// -- Check
+		assert_eq!(resumed.completed_output(&input_one), Some(&json!("output one")));
+		assert!(
+			resumed.completed_output(&input_two).is_none(),
+			"two should not be completed"
+		);
+		let err = other_agent_res.err().ok_or("Should fail for another agent")?;
+		assert_contains(&err.to_string(), "It was for agent 'agent-journal.aip'");
+		assert!(unfinished_before, "journal should be unfinished before finish");
+		assert!(!unfinished_after, "journal should be finished");
+
+		// -- Clean
+		let run_dir = runs_dir(dir_context).join_str(journal.run_id());
+		std::fs::remove_dir_all(run_dir.path())?;
+		let _ = std::fs::remove_dir(runs_dir(dir_context).path()); // only if empty
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_run_journal_defer_and_not_recorded() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let dir_context = runtime.dir_context();
+		// Note: Checked before the other journal is created (same run id if created in the same millisecond)
+		let not_recorded = RunJournal::create(dir_context, "agent-journal-defer.aip", false)?;
+		let not_recorded_dir_exists = not_recorded.run_dir().exists();
+		let journal = RunJournal::create(dir_context, "agent-journal-defer.aip", true)?;
+		let input_one = RunJournal::input_hash(&json!("one"))?;
+
+		// -- Exec
+		journal.defer(0, "one", &input_one, &json!("output one"));
+		let before_apply = RunJournal::load(dir_context, journal.run_id(), "agent-journal-defer.aip")?;
+		journal.append_deferred()?;
+		let after_apply = RunJournal::load(dir_context, journal.run_id(), "agent-journal-defer.aip")?;
+
+		// -- Check
+		assert!(
+			before_apply.completed_output(&input_one).is_none(),
+			"deferred input should not be recorded before apply"
+		);
+		assert_eq!(after_apply.completed_output(&input_one), Some(&json!("output one")));
+		assert!(!not_recorded.records_inputs());
+		assert!(!not_recorded_dir_exists, "run dir should be created on first use");
+
+		// -- Clean
+		std::fs::remove_dir_all(journal.run_dir().path())?;
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_run_journal_prune_run_dirs() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let fx_runs_dir = runtime.dir_context().wks_dir().join_str(".tmp/test_run_journal_prune_run_dirs");
+		let _ = std::fs::remove_dir_all(fx_runs_dir.path());
+		for run_id in [
+			"20250101-000000-001",
+			"20250101-000000-003",
+			"20250101-000000-002",
+			"20250101-000000-000",
+		] {
+			ensure_dir(fx_runs_dir.join_str(run_id))?;
+		}
+		let fx_meta = |finished: bool| RunMeta {
+			agent_path: "agent.aip".to_string(),
+			created: "0".to_string(),
+			finished,
+		};
+		// the oldest is an unfinished journal, the next one a finished journal
+		save_meta(&fx_runs_dir.join_str("20250101-000000-000"), &fx_meta(false))?;
+		save_meta(&fx_runs_dir.join_str("20250101-000000-001"), &fx_meta(true))?;
+		// an older backup not undone, and one undone
+		ensure_dir(fx_runs_dir.join_str("20241231-000000-000/backup"))?;
+		std::fs::write(
+			fx_runs_dir.join_str("20241231-000000-000/backup/backup.json").path(),
+			"[]",
+		)?;
+		ensure_dir(fx_runs_dir.join_str("20241231-000000-001/backup"))?;
+		std::fs::write(
+			fx_runs_dir.join_str("20241231-000000-001/backup/backup-undone.json").path(),
+			"[]",
+		)?;
+
+		// -- Exec
+		prune_run_dirs(&fx_runs_dir, 2)?;
+
+		// -- Check
+		assert!(
+			fx_runs_dir.join_str("20250101-000000-000").exists(),
+			"unfinished journal should be kept"
+		);
+		assert!(
+			fx_runs_dir.join_str("20241231-000000-000").exists(),
+			"backup not undone should be kept"
+		);
+		assert!(
+			!fx_runs_dir.join_str("20241231-000000-001").exists(),
+			"undone backup should be removed"
+		);
+		assert!(
+			!fx_runs_dir.join_str("20250101-000000-001").exists(),
+			"oldest should be removed"
+		);
+		assert!(fx_runs_dir.join_str("20250101-000000-002").exists());
+		assert!(fx_runs_dir.join_str("20250101-000000-003").exists());
+
+		// -- Clean
+		std::fs::remove_dir_all(fx_runs_dir.path())?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -14,6 +14,8 @@ pub struct RunCommandOptions {
 pub struct RunCommandOptionsInner {
 	on_file_globs: Option<Vec<String>>,
 	on_inputs: Option<Vec<String>>,
+	/// The config profile (`--profile`)
+	profile: Option<String>,
 
 	base_run_options: RunBaseOptions,
 }
@@ -37,6 +39,10 @@ impl RunCommandOptions {
 		self.inner.on_inputs.as_ref().map(|v| v.iter().map(|s| s.as_str()).collect())
 	}
 
+	pub fn profile(&self) -> Option<&str> {
+		self.inner.profile.as_deref()
+	}
+
 	pub fn base_run_config(&self) -> &RunBaseOptions {
 		&self.inner.base_run_options
 	}
@@ -78,11 +84,22 @@ impl RunCommandOptions {
 			verbose: args.verbose,
 			dry_mode,
 			open: args.open,
+			report: args.report,
+			no_cache: args.no_cache,
+			refresh_cache: args.refresh_cache,
+			keep_going: args.keep_going,
+			resume: args.resume,
+			journal: args.journal,
+			keep_run_files: true,
+			review: args.review,
+			auto_approve: args.auto_approve,
+			interactive: !args.not_interactive,
 		};
 
 		Ok(RunCommandOptionsInner {
 			on_file_globs,
 			on_inputs: args.on_inputs,
+			profile: args.profile,
 			base_run_options,
 		}
 		.into())
@@ -100,6 +117,8 @@ impl RunCommandOptions {
 pub enum DryMode {
 	Req,
 	Res,
+	/// Run all the stages, but record the file writes (printed as diffs at the end, to apply with `aip apply`)
+	Plan,
 	#[default]
 	None, // not dry mode
 }
@@ -110,6 +129,27 @@ pub struct RunBaseOptions {
 	verbose: bool,
 	dry_mode: DryMode,
 	open: bool,
+	/// The eventual file path to write the JSON run report to
+	report: Option<String>,
+	/// Do not use the AI response cache (even if the agent `cache` option is true)
+	no_cache: bool,
+	/// Do not read the AI response cache, but update it with the new responses
+	refresh_cache: bool,
+	/// Continue the run when an input fails (same as the `continue_on_error` agent option)
+	keep_going: bool,
+	/// The run id to resume (from `.aipack/.runs/<run-id>/`)
+	resume: Option<String>,
+	/// Record the completed inputs in the run journal (with `--journal`)
+	journal: bool,
+	/// Keep the plan and the file backups in the run dir `.aipack/.runs/<run-id>/` (on for the cli runs)
+	/// Note: The run dir is only created on first use (journal, plan, or first file write backup)
+	keep_run_files: bool,
+	/// Stage the file writes, and review them at the end of the run
+	review: bool,
+	/// Write the staged file changes without prompting
+	auto_approve: bool,
+	/// The run can prompt the user (the tui keys)
+	interactive: bool,
 }
 
 impl RunBaseOptions {
@@ -128,6 +168,46 @@ impl RunBaseOptions {
 	pub fn open(&self) -> bool {
 		self.open
 	}
+
+	pub fn report(&self) -> Option<&str> {
+		self.report.as_deref()
+	}
+
+	pub fn no_cache(&self) -> bool {
+		self.no_cache
+	}
+
+	pub fn refresh_cache(&self) -> bool {
+		self.refresh_cache
+	}
+
+	pub fn keep_going(&self) -> bool {
+		self.keep_going
+	}
+
+	pub fn resume(&self) -> Option<&str> {
+		self.resume.as_deref()
+	}
+
+	pub fn journal(&self) -> bool {
+		self.journal
+	}
+
+	pub fn keep_run_files(&self) -> bool {
+		self.keep_run_files
+	}
+
+	pub fn review(&self) -> bool {
+		self.review
+	}
+
+	pub fn auto_approve(&self) -> bool {
+		self.auto_approve
+	}
+
+	pub fn interactive(&self) -> bool {
+		self.interactive
+	}
 }
 
 // endregion: --- Common
@@ -138,6 +218,7 @@ fn parse_dry_mode(dry_mode: Option<&str>) -> DryMode {
 	match dry_mode {
 		Some("req") => DryMode::Req,
 		Some("res") => DryMode::Res,
+		Some("plan") => DryMode::Plan,
 		_ => DryMode::None,
 	}
 }
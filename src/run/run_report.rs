@@ -0,0 +1,431 @@
+use crate::run::run_input::AiResponse;
+use crate::support::text::{format_duration, format_num, truncate_with_ellipsis};
+use crate::{Error, Result};
+use genai::chat::MetaUsage;
+use serde::Serialize;
+use serde_json::{Value, json};
+use simple_fs::{SPath, ensure_file_dir};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Collects the per-input usage, cost, timings, and status of a run (shared across the input tasks),
+/// to publish the end-of-run summary, and optionally write it as JSON (`--report path.json`).
+///
+/// Note: Cheap to clone (Arc inner).
+#[derive(Debug, Clone)]
+pub struct RunReport {
+	inner: Arc<Mutex<RunReportInner>>,
+}
+
+#[derive(Debug)]
+struct RunReportInner {
+	start: Instant,
+	inputs: Vec<InputReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InputReport {
+	pub input_idx: usize,
+	pub label: String,
+	pub status: InputStatus,
+	/// Number of AI responses (e.g., more than one with follow-ups)
+	pub ai_responses: usize,
+	pub usage: MetaUsage,
+	pub price_usd: Option<f64>,
+	/// The sum of the AI responses duration
+	pub ai_duration_sec: f64,
+	/// The total duration of the input (Data, AI, Output)
+	pub duration_sec: f64,
+	/// The last stage started (the stage of the error when status is error)
+	pub stage: InputStage,
+	pub error: Option<String>,
+	#[serde(skip)]
+	start: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputStage {
+	Data,
+	Ai,
+	Output,
+}
+
+/// The error of a failed input (e.g., for the `errors` of the `# After All` stage)
+#[derive(Debug, Clone, Serialize)]
+pub struct InputError {
+	pub input_idx: usize,
+	pub label: String,
+	pub stage: InputStage,
+	pub error: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputStatus {
+	Ok,
+	Skipped,
+	Error,
+}
+
+/// Handle to report on one input of the run
+#[derive(Debug, Clone)]
+pub struct InputReporter {
+	run_report: RunReport,
+	input_idx: usize,
+}
+
+// region:    --- RunReport
+
+/// Constructors
+impl RunReport {
+	pub fn new() -> Self {
+		RunReport {
+			inner: Arc::new(Mutex::new(RunReportInner {
+				start: Instant::now(),
+				inputs: Vec::new(),
+			})),
+		}
+	}
+
+	/// Register a new input in the report, and returns its reporter
+	pub fn new_input_reporter(&self, input_idx: usize, label: impl Into<String>) -> InputReporter {
+		self.lock().inputs.push(InputReport {
+			input_idx,
+			label: label.into(),
+			status: InputStatus::Ok,
+			ai_responses: 0,
+			usage: MetaUsage::default(),
+			price_usd: None,
+			ai_duration_sec: 0.,
+			duration_sec: 0.,
+			stage: InputStage::Data,
+			error: None,
+			start: Instant::now(),
+		});
+
+		InputReporter {
+			run_report: self.clone(),
+			input_idx,
+		}
+	}
+
+	/// Returns the reporter of an already registered input
+	pub fn input_reporter(&self, input_idx: usize) -> InputReporter {
+		InputReporter {
+			run_report: self.clone(),
+			input_idx,
+		}
+	}
+}
+
+impl Default for RunReport {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Getters
+impl RunReport {
+	/// The total cost of the run so far
+	pub fn total_price_usd(&self) -> f64 {
+		let total: f64 = self.lock().inputs.iter().filter_map(|i| i.price_usd).sum();
+		round_price(total)
+	}
+
+	/// The total tokens of the run so far
+	pub fn total_tokens(&self) -> u64 {
+		self.lock().inputs.iter().map(|i| usage_total_tokens(&i.usage)).sum()
+	}
+
+	/// Returns the input reports sorted by input index
+	pub fn inputs(&self) -> Vec<InputReport> {
+		let mut inputs = self.lock().inputs.clone();
+		inputs.sort_by_key(|i| i.input_idx);
+		inputs
+	}
+
+	pub fn duration(&self) -> Duration {
+		self.lock().start.elapsed()
+	}
+
+	/// Returns the errors of the failed inputs (sorted by input index)
+	pub fn input_errors(&self) -> Vec<InputError> {
+		self.inputs()
+			.into_iter()
+			.filter(|i| i.status == InputStatus::Error)
+			.map(|i| InputError {
+				input_idx: i.input_idx,
+				label: i.label,
+				stage: i.stage,
+				error: i.error.unwrap_or_default(),
+			})
+			.collect()
+	}
+}
+
+/// Formatters
+impl RunReport {
+	/// Returns the formatted summary (totals and one line per input)
+	pub fn to_summary_table(&self) -> String {
+		let inputs = self.inputs();
+		let usage = total_usage(&inputs);
+		let count = |status: InputStatus| inputs.iter().filter(|i| i.status == status).count();
+
+		let mut lines: Vec<String> = Vec::new();
+		lines.push("==== Run Summary".to_string());
+		lines.push(format!(
+			"Inputs: {} (ok: {}, skipped: {}, error: {}) | Duration: {}",
+			inputs.len(),
+			count(InputStatus::Ok),
+			count(InputStatus::Skipped),
+			count(InputStatus::Error),
+			format_duration(self.duration())
+		));
+		lines.push(format!(
+			"Cost: ~${} | Tokens: {} (prompt: {}, cached: {}, completion: {}, reasoning: {})",
+			self.total_price_usd(),
+			format_num(usage_total_tokens(&usage) as i64),
+			format_num(usage.prompt_tokens.unwrap_or_default() as i64),
+			format_num(cached_tokens(&usage) as i64),
+			format_num(usage.completion_tokens.unwrap_or_default() as i64),
+			format_num(reasoning_tokens(&usage) as i64),
+		));
+
+		if !inputs.is_empty() {
+			lines.push(String::new());
+			lines.push(format!(
+				"{:<5} {:<40} {:<8} {:>10} {:>12} {:>10}",
+				"#", "Input", "Status", "Tokens", "Cost", "Duration"
+			));
+			for input in inputs.iter() {
+				let price = input.price_usd.map(|p| format!("~${p}")).unwrap_or_else(|| "-".to_string());
+				lines.push(format!(
+					"{:<5} {:<40} {:<8} {:>10} {:>12} {:>9}s",
+					input.input_idx,
+					truncate_with_ellipsis(&input.label, 40, "..."),
+					input.status.as_str(),
+					format_num(usage_total_tokens(&input.usage) as i64),
+					price,
+					input.duration_sec
+				));
+			}
+		}
+
+		lines.join("\n")
+	}
+
+	pub fn to_json(&self) -> Result<Value> {
+		let inputs = self.inputs();
+		let usage = total_usage(&inputs);
+
+		Ok(json!({
+			"duration_sec": round_duration(self.duration()),
+			"total_price_usd": self.total_price_usd(),
+			"total_tokens": usage_total_tokens(&usage),
+			"usage": usage,
+			"inputs": inputs,
+		}))
+	}
+
+	/// Write the JSON report to the file path (creating the parent directory if needed)
+	pub fn write_json(&self, path: &SPath) -> Result<()> {
+		let content = serde_json::to_string_pretty(&self.to_json()?)?;
+		ensure_file_dir(path)?;
+		std::fs::write(path.path(), content)
+			.map_err(|err| Error::custom(format!("Cannot write report file '{path}'. Cause: {err}")))?;
+		Ok(())
+	}
+}
+
+impl RunReport {
+	fn lock(&self) -> MutexGuard<'_, RunReportInner> {
+		// Note: if poisoned, the data is still valid (report only)
+		self.inner.lock().unwrap_or_else(|err| err.into_inner())
+	}
+
+	fn update_input(&self, input_idx: usize, f: impl FnOnce(&mut InputReport)) {
+		let mut inner = self.lock();
+		if let Some(input) = inner.inputs.iter_mut().find(|i| i.input_idx == input_idx) {
+			f(input)
+		}
+	}
+}
+
+// endregion: --- RunReport
+
+// region:    --- InputReporter
+
+impl InputReporter {
+	pub fn add_ai_response(&self, ai_response: &AiResponse) {
+		self.run_report.update_input(self.input_idx, |input| {
+			input.ai_responses += 1;
+			add_usage(&mut input.usage, &ai_response.usage);
+			if let Some(price_usd) = ai_response.price_usd {
+				input.price_usd = Some(round_price(input.price_usd.unwrap_or_default() + price_usd));
+			}
+			input.ai_duration_sec += ai_response.duration_sec;
+		});
+	}
+
+	pub fn set_stage(&self, stage: InputStage) {
+		self.run_report.update_input(self.input_idx, |input| input.stage = stage);
+	}
+
+	pub fn set_skipped(&self) {
+		self.run_report
+			.update_input(self.input_idx, |input| input.status = InputStatus::Skipped);
+	}
+
+	pub fn set_error(&self, err: &Error) {
+		self.run_report.update_input(self.input_idx, |input| {
+			input.status = InputStatus::Error;
+			input.error = Some(err.to_string());
+		});
+	}
+
+	/// Mark the end of the input (for the duration)
+	pub fn end(&self) {
+		self.run_report.update_input(self.input_idx, |input| {
+			input.duration_sec = round_duration(input.start.elapsed());
+		});
+	}
+}
+
+// endregion: --- InputReporter
+
+// region:    --- Support
+
+impl InputStatus {
+	fn as_str(&self) -> &'static str {
+		match self {
+			InputStatus::Ok => "ok",
+			InputStatus::Skipped => "skipped",
+			InputStatus::Error => "error",
+		}
+	}
+}
+
+fn total_usage(inputs: &[InputReport]) -> MetaUsage {
+	let mut usage = MetaUsage::default();
+	for input in inputs {
+		add_usage(&mut usage, &input.usage);
+	}
+	usage
+}
+
+fn usage_total_tokens(usage: &MetaUsage) -> u64 {
+	let total = match usage.total_tokens {
+		Some(total) => total,
+		None => usage.prompt_tokens.unwrap_or_default() + usage.completion_tokens.unwrap_or_default(),
+	};
+	total.max(0) as u64
+}
+
+fn cached_tokens(usage: &MetaUsage) -> i32 {
+	usage
+		.prompt_tokens_details
+		.as_ref()
+		.and_then(|d| d.cached_tokens)
+		.unwrap_or_default()
+}
+
+fn reasoning_tokens(usage: &MetaUsage) -> i32 {
+	usage
+		.completion_tokens_details
+		.as_ref()
+		.and_then(|d| d.reasoning_tokens)
+		.unwrap_or_default()
+}
+
+/// Add the usage to the accumulator usage (including the details)
+pub fn add_usage(acc: &mut MetaUsage, usage: &MetaUsage) {
+	fn add(a: Option<i32>, b: Option<i32>) -> Option<i32> {
+		match (a, b) {
+			(None, None) => None,
+			(a, b) => Some(a.unwrap_or_default() + b.unwrap_or_default()),
+		}
+	}
+
+	acc.prompt_tokens = add(acc.prompt_tokens, usage.prompt_tokens);
+	acc.completion_tokens = add(acc.completion_tokens, usage.completion_tokens);
+	acc.total_tokens = add(acc.total_tokens, usage.total_tokens);
+
+	if let Some(details) = usage.prompt_tokens_details.as_ref() {
+		let acc_details = acc.prompt_tokens_details.get_or_insert_with(Default::default);
+		acc_details.cached_tokens = add(acc_details.cached_tokens, details.cached_tokens);
+		acc_details.audio_tokens = add(acc_details.audio_tokens, details.audio_tokens);
+	}
+
+	if let Some(details) = usage.completion_tokens_details.as_ref() {
+		let acc_details = acc.completion_tokens_details.get_or_insert_with(Default::default);
+		acc_details.reasoning_tokens = add(acc_details.reasoning_tokens, details.reasoning_tokens);
+		acc_details.audio_tokens = add(acc_details.audio_tokens, details.audio_tokens);
+		acc_details.accepted_prediction_tokens = add(
+			acc_details.accepted_prediction_tokens,
+			details.accepted_prediction_tokens,
+		);
+		acc_details.rejected_prediction_tokens = add(
+			acc_details.rejected_prediction_tokens,
+			details.rejected_prediction_tokens,
+		);
+	}
+}
+
+/// Round to 6 decimals as the sum of prices can have float noise
+pub fn round_price(price_usd: f64) -> f64 {
+	(price_usd * 1_000_000.0).round() / 1_000_000.0
+}
+
+/// Duration in second with 3 digits for the millis
+fn round_duration(duration: Duration) -> f64 {
+	(duration.as_secs_f64() * 1000.0).round() / 1000.0
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::assert_contains;
+
+	#[test]
+	fn test_run_report_totals_and_json() -> Result<()> {
+		// -- Setup & Fixtures
+		let run_report = RunReport::new();
+		let input_0 = run_report.new_input_reporter(0, "file-01.md");
+		let input_1 = run_report.new_input_reporter(1, "file-02.md");
+		let input_2 = run_report.new_input_reporter(2, "file-03.md");
+
+		// -- Exec
+		input_0.set_stage(InputStage::Ai);
+		input_0.add_ai_response(&AiResponse::new_for_test(100, 20, 0.001));
+		input_0.add_ai_response(&AiResponse::new_for_test(200, 30, 0.002));
+		input_1.set_skipped();
+		input_2.set_stage(InputStage::Output);
+		input_2.set_error(&Error::custom("Some error"));
+
+		// -- Check
+		assert_eq!(run_report.total_tokens(), 350);
+		assert_eq!(run_report.total_price_usd(), 0.003);
+		let json = run_report.to_json()?;
+		assert_eq!(json["inputs"][0]["ai_responses"], 2);
+		assert_eq!(json["inputs"][1]["status"], "skipped");
+		assert_eq!(json["inputs"][2]["error"], "Some error");
+		let errors = run_report.input_errors();
+		assert_eq!(errors.len(), 1);
+		assert_eq!(errors[0].stage, InputStage::Output);
+		assert_eq!(errors[0].label, "file-03.md");
+		let table = run_report.to_summary_table();
+		assert_contains(&table, "Inputs: 3 (ok: 1, skipped: 1, error: 1)");
+		assert_contains(&table, "Cost: ~$0.003 | Tokens: 350 (prompt: 300");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
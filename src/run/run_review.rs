@@ -0,0 +1,241 @@
+use crate::Result;
+use crate::dir_context::DirContext;
+use crate::exec::open_vscode;
+use crate::hub::{HubEvent, get_hub};
+use crate::run::{FileChange, FileChanges};
+use simple_fs::{SPath, ensure_file_dir};
+use std::sync::Arc;
+
+const REVIEW_DIR: &str = ".review";
+
+// region:    --- ReviewRequest
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReviewDecision {
+	Accept,
+	Reject,
+	/// Open the new content in the editor (the edited content is used on accept)
+	Edit,
+}
+
+/// A file change to be reviewed by the user (sent with `HubEvent::Review`, answered with `decide`)
+#[derive(Debug)]
+pub struct ReviewRequest {
+	/// 1-based
+	pub num: usize,
+	pub total: usize,
+	pub display_path: String,
+	pub diff: String,
+	decision_tx: flume::Sender<ReviewDecision>,
+}
+
+impl ReviewRequest {
+	/// Send the user decision to the run (ignored if the run is not waiting anymore)
+	pub fn decide(&self, decision: ReviewDecision) {
+		let _ = self.decision_tx.send(decision);
+	}
+}
+
+// endregion: --- ReviewRequest
+
+// region:    --- Review
+
+/// Review the staged file changes, and write the accepted ones (with the `file_changes` backup).
+///
+/// - `auto_approve` - all the changes are written without prompting
+/// - not `interactive` - no change is written (the user cannot be prompted)
+/// - otherwise, each change is sent as a `HubEvent::Review`, and the run waits for the decision
+///
+/// Returns the display paths of the written changes.
+pub async fn review_changes(
+	dir_context: &DirContext,
+	file_changes: &FileChanges,
+	changes: Vec<FileChange>,
+	auto_approve: bool,
+	interactive: bool,
+) -> Result<Vec<String>> {
+	let hub = get_hub();
+	let total = changes.len();
+	let mut written = Vec::new();
+
+	if total == 0 {
+		return Ok(written);
+	}
+
+	if auto_approve {
+		for change in changes.iter() {
+			file_changes.write_change(change)?;
+			written.push(change.display_path(dir_context));
+		}
+		hub.publish(format!("\n-> Review: {total} file change(s) auto-approved and written"))
+			.await;
+		return Ok(written);
+	}
+
+	if !interactive {
+		let paths = changes
+			.iter()
+			.map(|change| format!("   - {}", change.display_path(dir_context)))
+			.collect::<Vec<_>>()
+			.join("\n");
+		hub.publish(format!(
+			"\n-! Review: {total} file change(s) NOT written (use --auto-approve in the non-interactive mode)\n{paths}"
+		))
+		.await;
+		return Ok(written);
+	}
+
+	for (idx, mut change) in changes.into_iter().enumerate() {
+		let display_path = change.display_path(dir_context);
+		let review_file = dir_context
+			.aipack_paths()
+			.wks_aipack_dir()
+			.join_str(REVIEW_DIR)
+			.join_str(&display_path.replace(['/', '\\'], "__"));
+
+		loop {
+			let (decision_tx, decision_rx) = flume::bounded(1);
+			let request = ReviewRequest {
+				num: idx + 1,
+				total,
+				display_path: display_path.clone(),
+				diff: change.to_unified_diff(dir_context),
+				decision_tx,
+			};
+			hub.publish(HubEvent::Review(Arc::new(request))).await;
+
+			// Note: If the tui is gone (e.g., quit), the remaining changes are rejected
+			let decision = decision_rx.recv_async().await.unwrap_or(ReviewDecision::Reject);
+
+			match decision {
+				ReviewDecision::Accept => {
+					if review_file.exists() {
+						change = change.with_new_content(std::fs::read_to_string(review_file.path())?);
+						std::fs::remove_file(review_file.path())?;
+					}
+					file_changes.write_change(&change)?;
+					hub.publish(format!("-> Accepted: {display_path}")).await;
+					written.push(display_path);
+					break;
+				}
+				ReviewDecision::Reject => {
+					if review_file.exists() {
+						std::fs::remove_file(review_file.path())?;
+					}
+					hub.publish(format!("-! Rejected: {display_path}")).await;
+					break;
+				}
+				ReviewDecision::Edit => {
+					if !review_file.exists() {
+						write_review_file(&review_file, change.new_content())?;
+					}
+					open_vscode(review_file.path()).await;
+					hub.publish(format!(
+						"-> Editing: {review_file} (save it, then accept to use the edited content)"
+					))
+					.await;
+				}
+			}
+		}
+	}
+
+	hub.publish(format!(
+		"\n-> Review: {} of {total} file change(s) written",
+		written.len()
+	))
+	.await;
+
+	Ok(written)
+}
+
+fn write_review_file(review_file: &SPath, content: &str) -> Result<()> {
+	ensure_file_dir(review_file)?;
+	std::fs::write(review_file.path(), content)?;
+	Ok(())
+}
+
+// endregion: --- Review
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::run::{FileChanges, Runtime, WriteMode};
+
+	#[tokio::test]
+	async fn test_review_changes_accept_reject() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let dir_context = runtime.dir_context();
+		let fx_dir = dir_context.wks_dir().join_str(".tmp/test_review_changes_accept_reject");
+		let fx_accepted = fx_dir.join_str("accepted.txt");
+		let fx_rejected = fx_dir.join_str("rejected.txt");
+		// Note: Clean the previous test run (e.g., failed before its check)
+		let _ = std::fs::remove_dir_all(fx_dir.path());
+		let changes = FileChanges::default();
+		changes.start(WriteMode::Review, None);
+		changes.save(&fx_accepted, "accepted content")?;
+		changes.save(&fx_rejected, "rejected content")?;
+
+		// the user answers (accept the first, reject the second)
+		let mut hub_rx = get_hub().subscriber();
+		tokio::spawn(async move {
+			while let Ok(event) = hub_rx.recv().await {
+				if let HubEvent::Review(request) = event {
+					if request.display_path.ends_with("accepted.txt") {
+						request.decide(ReviewDecision::Accept);
+					} else if request.display_path.ends_with("rejected.txt") {
+						request.decide(ReviewDecision::Reject);
+					}
+				}
+			}
+		});
+
+		// -- Exec
+		let written = review_changes(dir_context, &changes, changes.take_changes(), false, true).await?;
+
+		// -- Check
+		assert_eq!(
+			written,
+			vec![".tmp/test_review_changes_accept_reject/accepted.txt".to_string()]
+		);
+		assert_eq!(std::fs::read_to_string(fx_accepted.path())?, "accepted content");
+		assert!(!fx_rejected.exists(), "rejected file should not be written");
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_review_changes_not_interactive() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let dir_context = runtime.dir_context();
+		let fx_file = dir_context
+			.wks_dir()
+			.join_str(".tmp/test_review_changes_not_interactive/file.txt");
+		// Note: Clean the previous test run (e.g., failed before its check)
+		let _ = std::fs::remove_file(fx_file.path());
+		let changes = FileChanges::default();
+		changes.start(WriteMode::Review, None);
+		changes.save(&fx_file, "some content")?;
+		let staged = changes.take_changes();
+
+		// -- Exec
+		let written_ni = review_changes(dir_context, &changes, staged.clone(), false, false).await?;
+		let exists_ni = fx_file.exists();
+		let written_auto = review_changes(dir_context, &changes, staged, true, false).await?;
+
+		// -- Check
+		assert!(written_ni.is_empty(), "non-interactive should not write");
+		assert!(!exists_ni, "non-interactive should not write");
+		assert_eq!(written_auto.len(), 1);
+		assert_eq!(std::fs::read_to_string(fx_file.path())?, "some content");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
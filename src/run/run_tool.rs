@@ -0,0 +1,155 @@
+use crate::Result;
+use crate::agent::Agent;
+use crate::hub::get_hub;
+use crate::run::Runtime;
+use crate::run::literals::Literals;
+use genai::chat::{ToolCall, ToolResponse};
+use serde_json::Value;
+
+/// Execute a AI tool call with the matching `# Tools` lua handler of the agent.
+///
+/// - The tool call arguments are in the `args` variable of the lua scope.
+/// - The handler return value is sent back to the AI as is when string, otherwise, as json string.
+/// - Handler errors are sent back to the AI (and published to the hub), so that the AI can recover.
+pub(super) async fn exec_tool_call(
+	runtime: &Runtime,
+	agent: &Agent,
+	literals: &Literals,
+	tool_call: &ToolCall,
+) -> ToolResponse {
+	let hub = get_hub();
+
+	hub.publish(format!("-> Tool call '{}'", tool_call.fn_name)).await;
+
+	let content = match exec_tool_handler(runtime, agent, literals, tool_call) {
+		Ok(content) => content,
+		Err(err) => {
+			hub.publish(format!("-! Tool call '{}' failed. Cause: {err}", tool_call.fn_name))
+				.await;
+			format!("ERROR: {err}")
+		}
+	};
+
+	ToolResponse::new(tool_call.call_id.clone(), content)
+}
+
+fn exec_tool_handler(runtime: &Runtime, agent: &Agent, literals: &Literals, tool_call: &ToolCall) -> Result<String> {
+	let tool = agent
+		.tools()
+		.iter()
+		.find(|tool| tool.name == tool_call.fn_name)
+		.ok_or_else(|| format!("Tool '{}' is not defined in the '# Tools' section", tool_call.fn_name))?;
+
+	let lua_engine = runtime.new_lua_engine()?;
+	let lua_scope = lua_engine.create_table()?;
+	lua_scope.set("args", lua_engine.serde_to_lua_value(tool_call.fn_arguments.clone())?)?;
+	lua_scope.set("CTX", literals.to_lua(&lua_engine)?)?;
+	lua_scope.set("options", agent.options_as_ref())?;
+
+	let agent_dir = agent.file_dir()?;
+	let lua_value = lua_engine.eval(&tool.script, Some(lua_scope), Some(&[agent_dir.to_str()]))?;
+
+	let content = match serde_json::to_value(lua_value)? {
+		Value::String(content) => content,
+		Value::Null => String::new(),
+		other => other.to_string(),
+	};
+
+	Ok(content)
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::{assert_contains, load_inline_agent};
+	use serde_json::json;
+
+	const FX_AGENT: &str = r#"
+# Tools
+
+## add_numbers
+
+Add two numbers
+
+```json
+{
+	"type": "object",
+	"properties": {
+		"a": { "type": "number" },
+		"b": { "type": "number" }
+	},
+	"required": ["a", "b"]
+}
+```
+
+```lua
+return "sum: " .. (args.a + args.b)
+```
+
+## fail_tool
+
+```lua
+error("Something went wrong")
+```
+
+# Instruction
+
+Add 1 and 2
+"#;
+
+	#[tokio::test]
+	async fn test_run_tool_exec_tool_call_simple() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let agent = load_inline_agent("./dummy/path.aip", FX_AGENT)?;
+		let literals = Literals::from_dir_context_and_agent_path(runtime.dir_context(), &agent)?;
+		let tool_call = ToolCall {
+			call_id: "call-01".to_string(),
+			fn_name: "add_numbers".to_string(),
+			fn_arguments: json!({"a": 1, "b": 2}),
+		};
+
+		// -- Exec
+		let tool_response = exec_tool_call(&runtime, &agent, &literals, &tool_call).await;
+
+		// -- Check
+		assert_eq!(tool_response.call_id, "call-01");
+		assert_eq!(tool_response.content, "sum: 3");
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_run_tool_exec_tool_call_errors() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let agent = load_inline_agent("./dummy/path.aip", FX_AGENT)?;
+		let literals = Literals::from_dir_context_and_agent_path(runtime.dir_context(), &agent)?;
+		let fail_call = ToolCall {
+			call_id: "call-01".to_string(),
+			fn_name: "fail_tool".to_string(),
+			fn_arguments: json!({}),
+		};
+		let unknown_call = ToolCall {
+			call_id: "call-02".to_string(),
+			fn_name: "unknown_tool".to_string(),
+			fn_arguments: json!({}),
+		};
+
+		// -- Exec
+		let fail_response = exec_tool_call(&runtime, &agent, &literals, &fail_call).await;
+		let unknown_response = exec_tool_call(&runtime, &agent, &literals, &unknown_call).await;
+
+		// -- Check
+		assert_contains(&fail_response.content, "Something went wrong");
+		assert_contains(&unknown_response.content, "'unknown_tool' is not defined");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -1,8 +1,9 @@
 use crate::Result;
 use crate::dir_context::DirContext;
-use crate::run::{RuntimeContext, get_genai_client};
+use crate::run::{
+	FileChanges, GenaiClients, RuntimeContext, get_genai_clients, load_cmd_policy, load_providers_config,
+};
 use crate::script::LuaEngine;
-use genai::Client;
 
 #[derive(Clone)]
 pub struct Runtime {
@@ -12,10 +13,14 @@ pub struct Runtime {
 /// Constructors
 impl Runtime {
 	pub fn new(dir_context: DirContext) -> Result<Self> {
-		// Note: Make the type explicit for clarity
-		let client = get_genai_client()?;
+		// Note: The `[providers.<name>]` of the config files are routed by the genai clients
+		let providers = load_providers_config(&dir_context)?;
+		let genai_clients = get_genai_clients(providers)?;
 
-		let context = RuntimeContext::new(dir_context, client);
+		// Note: The `[cmd_policy]` of the config files is checked by `utils.cmd.exec`
+		let cmd_policy = load_cmd_policy(&dir_context)?;
+
+		let context = RuntimeContext::new(dir_context, genai_clients, cmd_policy);
 
 		let runtime = Self { context };
 
@@ -24,6 +29,30 @@ impl Runtime {
 
 	#[cfg(test)]
 	pub fn new_test_runtime_sandbox_01() -> Result<Self> {
+		Self::new(Self::new_test_dir_context_sandbox_01()?)
+	}
+
+	/// The sandbox-01 runtime with the `mock` provider at this base url (for the `mock::<model>` models)
+	/// See `_test_support::MockLlmServer`
+	#[cfg(test)]
+	pub fn new_test_runtime_sandbox_01_with_mock(base_url: &str) -> Result<Self> {
+		use crate::run::ProviderConfig;
+
+		let dir_context = Self::new_test_dir_context_sandbox_01()?;
+		let mut providers = load_providers_config(&dir_context)?;
+		let mock_provider: ProviderConfig =
+			serde_json::from_value(serde_json::json!({"base_url": base_url, "api_key": "fx-key"}))?;
+		providers.insert("mock".to_string(), mock_provider);
+		let genai_clients = get_genai_clients(providers)?;
+		let cmd_policy = load_cmd_policy(&dir_context)?;
+
+		let context = RuntimeContext::new(dir_context, genai_clients, cmd_policy);
+
+		Ok(Self { context })
+	}
+
+	#[cfg(test)]
+	fn new_test_dir_context_sandbox_01() -> Result<DirContext> {
 		use crate::_test_support::{SANDBOX_01_BASE_AIPACK_DIR, SANDBOX_01_WKS_DIR};
 		use crate::dir_context::AipackPaths;
 		use simple_fs::SPath;
@@ -39,9 +68,7 @@ impl Runtime {
 
 		let aipack_paths = AipackPaths::from_aipack_base_and_wks_dirs(base_aipack_dir, wks_aipack_dir)?;
 
-		let dir_context = DirContext::from_current_and_aipack_paths(current_dir, aipack_paths)?;
-
-		Self::new(dir_context)
+		DirContext::from_current_and_aipack_paths(current_dir, aipack_paths)
 	}
 }
 
@@ -61,11 +88,15 @@ impl Runtime {
 		self.context.clone()
 	}
 
-	pub fn genai_client(&self) -> &Client {
-		self.context.genai_client()
+	pub fn genai_clients(&self) -> &GenaiClients {
+		self.context.genai_clients()
 	}
 
 	pub fn dir_context(&self) -> &DirContext {
 		self.context.dir_context()
 	}
+
+	pub fn file_changes(&self) -> &FileChanges {
+		self.context.file_changes()
+	}
 }
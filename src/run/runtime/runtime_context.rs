@@ -1,5 +1,5 @@
 use crate::dir_context::DirContext;
-use genai::Client;
+use crate::run::{CmdPolicy, FileChanges, GenaiClients};
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -9,11 +9,13 @@ pub struct RuntimeContext {
 
 /// Constructors
 impl RuntimeContext {
-	pub fn new(dir_context: DirContext, genai_client: Client) -> Self {
+	pub fn new(dir_context: DirContext, genai_clients: GenaiClients, cmd_policy: CmdPolicy) -> Self {
 		Self {
 			inner: Arc::new(RuntimeContextInner {
 				dir_context,
-				genai_client,
+				genai_clients,
+				cmd_policy,
+				file_changes: FileChanges::default(),
 			}),
 		}
 	}
@@ -25,12 +27,23 @@ impl RuntimeContext {
 		&self.inner.dir_context
 	}
 
-	pub fn genai_client(&self) -> &Client {
-		&self.inner.genai_client
+	pub fn genai_clients(&self) -> &GenaiClients {
+		&self.inner.genai_clients
+	}
+
+	pub fn cmd_policy(&self) -> &CmdPolicy {
+		&self.inner.cmd_policy
+	}
+
+	/// The `utils.file.*` writes (direct, or recorded with `--dry plan`)
+	pub fn file_changes(&self) -> &FileChanges {
+		&self.inner.file_changes
 	}
 }
 
 struct RuntimeContextInner {
 	dir_context: DirContext,
-	genai_client: Client,
+	genai_clients: GenaiClients,
+	cmd_policy: CmdPolicy,
+	file_changes: FileChanges,
 }
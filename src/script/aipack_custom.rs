@@ -10,6 +10,12 @@ pub enum AipackCustom {
 		reason: Option<String>,
 	},
 	BeforeAllResponse(BeforeAllResponse),
+	/// The data, and the options override for this input only (from the `# Data` stage)
+	DataResponse(DataResponse),
+	/// Ask the runtime (from the `# Output` stage) to send the AI response and this message back to the AI
+	FollowUp {
+		message: String,
+	},
 }
 
 #[derive(Debug, Default)]
@@ -19,6 +25,14 @@ pub struct BeforeAllResponse {
 	pub options: Option<Value>,
 }
 
+#[derive(Debug, Default)]
+pub struct DataResponse {
+	pub data: Option<Value>,
+	pub options: Option<Value>,
+	/// The attachments (images, files) to send with the last user prompt message
+	pub attachments: Option<Value>,
+}
+
 /// Return of the `AipackCustom::from_value` allowing to avoid cloning in case it's not a AipackCustom.
 #[derive(Debug)]
 pub enum FromValue {
@@ -58,6 +72,32 @@ impl AipackCustom {
 	/// }
 	/// ```
 	///
+	/// - The DataResponse (only supported at the Data stage)
+	/// ```
+	/// {
+	///   _aipack_: {
+	///     kind: "DataResponse",
+	///     data: { // each property is optional
+	///       "data": {some: "data for this input"},
+	///       "options": {model: "gpt-4o", temperature: 0.2},
+	///       "attachments": ["shot.png", {path: "spec.pdf", content_type: "application/pdf"}]
+	///     }
+	///   }
+	/// }
+	/// ```
+	///
+	/// - The FollowUp (only supported at the Output stage)
+	/// ```
+	/// {
+	///   _aipack_: {
+	///     kind: "FollowUp",
+	///     data: {
+	///       "message": "The follow-up user message"
+	///     }
+	///   }
+	/// }
+	/// ```
+	///
 	pub fn from_value(value: Value) -> Result<FromValue> {
 		let Some(kind) = value.x_get::<String>("/_aipack_/kind").ok() else {
 			return Ok(FromValue::OriginalValue(value));
@@ -72,6 +112,15 @@ impl AipackCustom {
 			Ok(FromValue::AipackCustom(AipackCustom::BeforeAllResponse(
 				before_all_response,
 			)))
+		} else if kind == "DataResponse" {
+			let custom_data: Option<Value> = value.x_get("/_aipack_/data").ok();
+			let data_response = extract_data_and_options(custom_data)?;
+			Ok(FromValue::AipackCustom(AipackCustom::DataResponse(data_response)))
+		} else if kind == "FollowUp" {
+			let message: String = value.x_get("/_aipack_/data/message").map_err(|_| {
+				Error::custom("aipack::follow_up(message) requires a message string (`_aipack_.data.message`)")
+			})?;
+			Ok(FromValue::AipackCustom(Self::FollowUp { message }))
 		} else {
 			Err(format!("_aipack_ kind '{kind}' is not known.").into())
 		}
@@ -125,6 +174,39 @@ fn extract_inputs_and_before_all(custom_data: Option<Value>) -> Result<BeforeAll
 	Ok(before_all_response)
 }
 
+/// extract, (data, options, attachments)
+fn extract_data_and_options(custom_data: Option<Value>) -> Result<DataResponse> {
+	let Some(custom_data) = custom_data else {
+		return Ok(DataResponse::default());
+	};
+
+	const ERROR_CAUSE: &str = "aipack::data_response(data), can only have `.data`, `.options`, and `.attachments`";
+
+	let data_response = match custom_data {
+		Value::Object(mut obj) => {
+			let data = obj.remove("data");
+			let options = obj.remove("options");
+			let attachments = obj.remove("attachments");
+
+			let keys: Vec<String> = obj.keys().map(|k| k.to_string()).collect();
+			if !keys.is_empty() {
+				return Err(Error::custom(format!(
+					"{ERROR_CAUSE}. But also contained: {}",
+					keys.join(", ")
+				)));
+			}
+			DataResponse {
+				data,
+				options,
+				attachments,
+			}
+		}
+		_ => DataResponse::default(),
+	};
+
+	Ok(data_response)
+}
+
 // endregion: --- Support
 
 // region:    --- Tests
@@ -164,6 +246,58 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[test]
+	fn test_aipack_custom_data_response() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_custom = json!({
+			"_aipack_": {
+				"kind": "DataResponse",
+				"data": {
+					"data": {"file": "big.rs"},
+					"options": {"model": "gpt-4o"},
+					"attachments": ["shot.png"]
+				}
+			}
+		});
+
+		// -- Exec
+		let custom = AipackCustom::from_value(fx_custom)?;
+
+		// -- Check
+		let FromValue::AipackCustom(AipackCustom::DataResponse(data_response)) = custom else {
+			return Err("Should be a aipack DataResponse".into());
+		};
+		assert_eq!(data_response.data, Some(json!({"file": "big.rs"})));
+		assert_eq!(data_response.options, Some(json!({"model": "gpt-4o"})));
+		assert_eq!(data_response.attachments, Some(json!(["shot.png"])));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_aipack_custom_follow_up() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_custom = json!({
+			"_aipack_": {
+				"kind": "FollowUp",
+				"data": {
+					"message": "Please fix the failing test"
+				}
+			}
+		});
+
+		// -- Exec
+		let custom = AipackCustom::from_value(fx_custom)?;
+
+		// -- Check
+		let FromValue::AipackCustom(AipackCustom::FollowUp { message }) = custom else {
+			return Err("Should be a aipack FollowUp".into());
+		};
+		assert_eq!(message, "Please fix the failing test");
+
+		Ok(())
+	}
 }
 
 // endregion: --- Tests
@@ -8,6 +8,8 @@
 //! ### Functions
 //! * `utils.aipack.before_all_response(data: any) -> table`
 //! * `utils.aipack.skip(reason?: string) -> table`
+//! * `utils.aipack.data_response(data: {data?: any, options?: table, attachments?: list}) -> table`
+//! * `utils.aipack.follow_up(message: string) -> table`
 
 use crate::Result;
 use crate::run::RuntimeContext;
@@ -22,6 +24,12 @@ pub fn init_module(lua: &Lua, _runtime_context: &RuntimeContext) -> Result<Table
 	let skip_fn = lua.create_function(aipack_skip)?;
 	table.set("skip", skip_fn)?;
 
+	let data_response_fn = lua.create_function(aipack_data_response)?;
+	table.set("data_response", data_response_fn)?;
+
+	let follow_up_fn = lua.create_function(aipack_follow_up)?;
+	table.set("follow_up", follow_up_fn)?;
+
 	let globals = lua.globals();
 	globals.set("aipack", &table)?;
 
@@ -93,6 +101,76 @@ fn aipack_skip(lua: &Lua, reason: Option<String>) -> mlua::Result<Value> {
 	Ok(Value::Table(outer))
 }
 
+/// ## Lua Documentation
+///
+/// Returns a response with the data and the agent options override for this input only.
+///
+/// Only supported in the `# Data` stage. The options (e.g., `model`, `temperature`) are merged
+/// on top of the agent options for the AI call and the `# Output` stage of this input.
+///
+/// The `attachments` (path strings or `{path, content_type?}` tables, relative to the workspace dir)
+/// are sent as multi-part content with the last user prompt message (e.g., `{"screenshot.png"}`).
+///
+/// ```lua
+/// -- API Signature
+/// utils.aipack.data_response(data: {data?: any, options?: table, attachments?: list}) -> table
+/// ```
+///
+/// Returns a table with the following structure:
+/// ```lua
+/// {
+///   _aipack_ = {
+///     kind = "DataResponse",
+///     data = <data passed to function>
+///   }
+/// }
+/// ```
+fn aipack_data_response(lua: &Lua, data: Value) -> mlua::Result<Value> {
+	let inner = lua.create_table()?;
+	inner.set("kind", "DataResponse")?;
+	inner.set("data", data)?;
+	let outer = lua.create_table()?;
+	outer.set("_aipack_", inner)?;
+
+	Ok(Value::Table(outer))
+}
+
+/// ## Lua Documentation
+///
+/// Returns a response asking the runtime to send the AI response and the follow-up message
+/// back to the AI, and then re-run the `# Output` stage with the new AI response.
+///
+/// Only supported in the `# Output` stage, and capped by the `max_turns` agent option
+/// (when reached, the last AI response is the response of the input, with a warning).
+///
+/// ```lua
+/// -- API Signature
+/// utils.aipack.follow_up(message: string) -> table
+/// ```
+///
+/// Returns a table with the following structure:
+/// ```lua
+/// {
+///   _aipack_ = {
+///     kind = "FollowUp",
+///     data = { message = <message passed to function> }
+///   }
+/// }
+/// ```
+fn aipack_follow_up(lua: &Lua, message: String) -> mlua::Result<Value> {
+	let data = lua.create_table()?;
+	data.set("message", message)?;
+
+	let inner = lua.create_table()?;
+	inner.set("kind", "FollowUp")?;
+	inner.set("data", data)?;
+
+	let outer = lua.create_table()?;
+	outer.set("_aipack_", inner)?;
+
+	Ok(Value::Table(outer))
+}
+
 // endregion: --- Lua Functions
 
 // region:    --- Section
@@ -166,6 +244,26 @@ mod tests {
 		assert!(reason.is_none(), "reason should be none");
 		Ok(())
 	}
+
+	#[tokio::test]
+	async fn test_lua_aipack_follow_up_simple() -> Result<()> {
+		// -- Setup
+		let lua = setup_lua(super::init_module, "aipack")?;
+		let script = r#"
+			return aipack.follow_up("Now, make it shorter")
+		"#;
+
+		// -- Exec
+		let res = eval_lua(&lua, script)?;
+
+		// -- Check
+		let kind = res.x_get_str("/_aipack_/kind")?;
+		assert_eq!(kind, "FollowUp");
+
+		let message = res.x_get_str("/_aipack_/data/message")?;
+		assert_eq!(message, "Now, make it shorter");
+		Ok(())
+	}
 }
 
 // endregion: --- Section
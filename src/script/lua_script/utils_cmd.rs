@@ -6,18 +6,28 @@
 //! The `cmd` module exposes functions to execute system commands.
 //!
 //! ### Functions
-//! * `utils.cmd.exec(cmd_name: string, args?: string | table) -> {stdout: string, stderr: string, exit: number}`
+//! * `utils.cmd.exec(cmd_name: string, args?: string | table, options?: {timeout_ms?, cwd?, env?, stdin?}) -> {stdout: string, stderr: string, exit: number, duration_ms: number}`
 
 use crate::Result;
+use crate::dir_context::PathResolver;
 use crate::run::RuntimeContext;
 use crate::script::lua_script::helpers::to_vec_of_strings;
-use mlua::{Lua, Table, Value};
-use std::process::Command;
+use mlua::{FromLua, Lua, Table, Value};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-pub fn init_module(lua: &Lua, _runtime_context: &RuntimeContext) -> Result<Table> {
+pub fn init_module(lua: &Lua, runtime_context: &RuntimeContext) -> Result<Table> {
 	let table = lua.create_table()?;
 
-	let exec_fn = lua.create_function(cmd_exec)?;
+	let ctx = runtime_context.clone();
+	let exec_fn = lua.create_function(
+		move |lua, (cmd_name, args, options): (String, Option<Value>, Option<CmdExecOptions>)| {
+			cmd_exec(lua, &ctx, cmd_name, args, options)
+		},
+	)?;
 
 	table.set("exec", exec_fn)?;
 
@@ -26,16 +36,29 @@ pub fn init_module(lua: &Lua, _runtime_context: &RuntimeContext) -> Result<Table
 
 /// ## Lua Documentation
 ///
-/// Execute a system command with optional arguments.
+/// Execute a system command with optional arguments and options.
 ///
 /// ```lua
 /// -- API Signature
-/// utils.cmd.exec(cmd_name: string, args?: string | table) -> CmdResponse
+/// utils.cmd.exec(cmd_name: string, args?: string | table, options?: CmdExecOptions) -> CmdResponse
 /// ```
 ///
 /// The command will be executed using the system shell. Arguments can be provided as a single string
 /// or a table of strings.
 ///
+/// The command must be allowed by the `[cmd_policy]` of the `config.toml` (`allow` / `deny` lists).
+///
+/// ### Options (CmdExecOptions)
+///
+/// ```lua
+/// {
+///   timeout_ms = number,   -- Kill the command after this duration (default from `[cmd_policy] timeout_ms`, none if absent)
+///   cwd        = string,   -- Working directory, relative to the workspace dir (default the current dir)
+///   env        = table,    -- Environment variables to add, e.g., {RUST_LOG = "debug"}
+///   stdin      = string,   -- Content written to the command stdin
+/// }
+/// ```
+///
 /// ### Example
 /// ```lua
 /// -- Single string argument
@@ -43,6 +66,9 @@ pub fn init_module(lua: &Lua, _runtime_context: &RuntimeContext) -> Result<Table
 ///
 /// -- Table of arguments
 /// local result = utils.cmd.exec("ls", {"-l", "-a"})
+///
+/// -- With options
+/// local result = utils.cmd.exec("cargo", {"test"}, {cwd = "my-crate/", timeout_ms = 60000})
 /// ```
 ///
 /// ### Returns (CmdResponse)
@@ -51,9 +77,10 @@ pub fn init_module(lua: &Lua, _runtime_context: &RuntimeContext) -> Result<Table
 ///
 /// ```lua
 /// {
-///   stdout = string,  -- Standard output from the command
-///   stderr = string,  -- Standard error from the command
-///   exit   = number   -- Exit code (0 for success)
+///   stdout      = string,  -- Standard output from the command
+///   stderr      = string,  -- Standard error from the command
+///   exit        = number,  -- Exit code (0 for success)
+///   duration_ms = number   -- Execution duration in milliseconds
 /// }
 /// ```
 ///
@@ -67,7 +94,26 @@ pub fn init_module(lua: &Lua, _runtime_context: &RuntimeContext) -> Result<Table
 ///   error  = string        -- Error message from command execution
 /// }
 /// ```
-fn cmd_exec(lua: &Lua, (cmd_name, args): (String, Option<Value>)) -> mlua::Result<Value> {
+///
+/// When the timeout is reached, the command is killed, and the error is a timeout error.
+/// On unix, a command with a timeout runs in its own process group, and the whole group is killed (including the sub processes).
+/// On other platforms, only the command process is killed.
+///
+/// Note: The `[cmd_policy]` matches the command name only (not its arguments). When `deny` is set,
+///       the command launchers (e.g., `sh`, `bash`, `env`) are denied unless in `allow`.
+///       The `allow` list is the recommended way to restrict the commands.
+fn cmd_exec(
+	lua: &Lua,
+	ctx: &RuntimeContext,
+	cmd_name: String,
+	args: Option<Value>,
+	options: Option<CmdExecOptions>,
+) -> mlua::Result<Value> {
+	let options = options.unwrap_or_default();
+	let cmd_policy = ctx.cmd_policy();
+
+	cmd_policy.check(&cmd_name)?;
+
 	let mut command = Command::new(&cmd_name);
 
 	// Handle optional arguments
@@ -76,16 +122,29 @@ fn cmd_exec(lua: &Lua, (cmd_name, args): (String, Option<Value>)) -> mlua::Resul
 		command.args(args);
 	}
 
-	match command.output() {
-		Ok(output) => {
-			let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-			let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-			let exit_code = output.status.code().unwrap_or(-1) as i64;
+	// Handle the options
+	if let Some(cwd) = options.cwd.as_ref() {
+		let cwd = ctx.dir_context().resolve_path(cwd, PathResolver::WksDir)?;
+		command.current_dir(cwd.path());
+	}
+	if let Some(env) = options.env.as_ref() {
+		command.envs(env);
+	}
+	let timeout_ms = options.timeout_ms.or(cmd_policy.timeout_ms());
+
+	let start = Instant::now();
+	let output = run_command(&mut command, options.stdin, timeout_ms.map(Duration::from_millis));
+	let duration_ms = start.elapsed().as_millis() as i64;
+
+	match output {
+		Ok(CmdOutput::Exited { status, stdout, stderr }) => {
+			let exit_code = status.code().unwrap_or(-1) as i64;
 
 			let res = lua.create_table()?;
 			res.set("stdout", stdout.as_str())?;
 			res.set("stderr", stderr.as_str())?;
 			res.set("exit", exit_code)?;
+			res.set("duration_ms", duration_ms)?;
 
 			if exit_code == 0 {
 				Ok(Value::Table(res))
@@ -107,6 +166,11 @@ exit code: {exit_code}\n"
 				.into())
 			}
 		}
+		Ok(CmdOutput::TimedOut { timeout_ms }) => Err(crate::Error::CmdTimeout {
+			cmd: cmd_name,
+			timeout_ms,
+		}
+		.into()),
 		Err(err) => {
 			let cmd = command.get_program().to_str().unwrap_or_default();
 			let args = command
@@ -124,6 +188,150 @@ Cause:\n{err}"
 	}
 }
 
+// region:    --- Options
+
+#[derive(Debug, Default)]
+struct CmdExecOptions {
+	timeout_ms: Option<u64>,
+	cwd: Option<String>,
+	env: Option<HashMap<String, String>>,
+	stdin: Option<String>,
+}
+
+impl FromLua for CmdExecOptions {
+	fn from_lua(value: Value, _lua: &Lua) -> mlua::Result<Self> {
+		let table = value
+			.as_table()
+			.ok_or(crate::Error::custom("CmdExecOptions should be a table"))?;
+		Ok(Self {
+			timeout_ms: table.get("timeout_ms")?,
+			cwd: table.get("cwd")?,
+			env: table.get("env")?,
+			stdin: table.get("stdin")?,
+		})
+	}
+}
+
+// endregion: --- Options
+
+// region:    --- Support
+
+enum CmdOutput {
+	Exited {
+		status: ExitStatus,
+		stdout: String,
+		stderr: String,
+	},
+	TimedOut {
+		timeout_ms: u64,
+	},
+}
+
+/// Run the command, with the stdin content and the optional timeout (the child is killed when reached).
+///
+/// Note: The stdout/stderr are read in their own threads, so that a full pipe does not block the child.
+fn run_command(command: &mut Command, stdin: Option<String>, timeout: Option<Duration>) -> std::io::Result<CmdOutput> {
+	command
+		.stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped());
+
+	// Note: Own process group, so that the timeout can kill the grand children as well
+	//       (only with a timeout, as the terminal Ctrl-C does not reach the other process groups)
+	#[cfg(unix)]
+	if timeout.is_some() {
+		use std::os::unix::process::CommandExt as _;
+		command.process_group(0);
+	}
+
+	let mut child = command.spawn()?;
+
+	if let (Some(content), Some(mut child_stdin)) = (stdin, child.stdin.take()) {
+		std::thread::spawn(move || {
+			// Note: The child might not read all of its stdin (broken pipe), which is fine
+			let _ = child_stdin.write_all(content.as_bytes());
+		});
+	}
+	let stdout_handle = spawn_reader(child.stdout.take());
+	let stderr_handle = spawn_reader(child.stderr.take());
+
+	let status = match timeout {
+		Some(timeout) => match wait_with_timeout(&mut child, timeout)? {
+			Some(status) => status,
+			None => {
+				// Note: The reader threads are not joined, as grand children might still hold the pipes
+				return Ok(CmdOutput::TimedOut {
+					timeout_ms: timeout.as_millis() as u64,
+				});
+			}
+		},
+		None => child.wait()?,
+	};
+
+	Ok(CmdOutput::Exited {
+		status,
+		stdout: join_reader(stdout_handle),
+		stderr: join_reader(stderr_handle),
+	})
+}
+
+/// Returns None if the timeout was reached (and the child killed, with its process group on unix)
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> std::io::Result<Option<ExitStatus>> {
+	let start = Instant::now();
+	loop {
+		if let Some(status) = child.try_wait()? {
+			return Ok(Some(status));
+		}
+		if start.elapsed() >= timeout {
+			kill_child(child)?;
+			child.wait()?;
+			return Ok(None);
+		}
+		std::thread::sleep(Duration::from_millis(10));
+	}
+}
+
+/// Kill the child process group (the child is its process group leader, see `run_command`)
+///
+/// Note: With the `kill` command, as this crate does not use `unsafe` (for `libc::kill`).
+#[cfg(unix)]
+fn kill_child(child: &mut Child) -> std::io::Result<()> {
+	let killed_group = Command::new("kill")
+		.args(["-KILL", "--", &format!("-{}", child.id())])
+		.stdout(Stdio::null())
+		.stderr(Stdio::null())
+		.status()
+		.is_ok_and(|status| status.success());
+	if !killed_group {
+		// Fallback, e.g., no `kill` command
+		child.kill()?;
+	}
+	Ok(())
+}
+
+/// Kill the child process (the grand children are not killed on these platforms)
+#[cfg(not(unix))]
+fn kill_child(child: &mut Child) -> std::io::Result<()> {
+	child.kill()
+}
+
+fn spawn_reader(pipe: Option<impl Read + Send + 'static>) -> Option<JoinHandle<Vec<u8>>> {
+	pipe.map(|mut pipe| {
+		std::thread::spawn(move || {
+			let mut buf = Vec::new();
+			let _ = pipe.read_to_end(&mut buf);
+			buf
+		})
+	})
+}
+
+fn join_reader(handle: Option<JoinHandle<Vec<u8>>>) -> String {
+	let bytes = handle.and_then(|handle| handle.join().ok()).unwrap_or_default();
+	String::from_utf8_lossy(&bytes).to_string()
+}
+
+// endregion: --- Support
+
 // region:    --- Tests
 
 #[cfg(test)]
@@ -167,6 +375,81 @@ mod tests {
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn test_lua_cmd_exec_with_options() -> Result<()> {
+		// -- Setup & Fixtures
+		let lua = setup_lua(super::init_module, "cmd")?;
+		let script = r#"
+			local opts = {cwd = "sub-dir-a", env = {FX_NAME = "from-env"}, stdin = "from-stdin", timeout_ms = 5000}
+			return utils.cmd.exec("sh", {"-c", "cat; echo \" $FX_NAME\"; basename \"$(pwd)\""}, opts)
+		"#;
+
+		// -- Exec
+		let res = eval_lua(&lua, script)?;
+
+		// -- Check
+		assert_eq!(res.x_get_str("stdout")?, "from-stdin from-env\nsub-dir-a\n");
+		assert_eq!(res.x_get_i64("exit")?, 0);
+		assert!(res.x_get_i64("duration_ms")? >= 0, "should have a duration_ms");
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_lua_cmd_exec_timeout() -> Result<()> {
+		// -- Setup & Fixtures
+		let lua = setup_lua(super::init_module, "cmd")?;
+		let script = r#"return utils.cmd.exec("sleep", "5", {timeout_ms = 100})"#;
+
+		// -- Exec
+		let start = std::time::Instant::now();
+		let Err(err) = eval_lua(&lua, script) else {
+			return Err("Should have returned a timeout error".into());
+		};
+
+		// -- Check
+		assert_contains(&err.to_string(), "timed out after 100ms");
+		assert!(start.elapsed().as_secs() < 4, "should have been killed on timeout");
+
+		Ok(())
+	}
+
+	#[cfg(target_os = "linux")]
+	#[tokio::test]
+	async fn test_lua_cmd_exec_timeout_kills_grand_children() -> Result<()> {
+		// -- Setup & Fixtures
+		let lua = setup_lua(super::init_module, "cmd")?;
+		let fx_pid_file = std::env::temp_dir().join(format!("aipack-test-cmd-grand-child-{}.pid", std::process::id()));
+		let script = format!(
+			r#"return utils.cmd.exec("sh", {{"-c", "sleep 5 & echo $! > {}; wait"}}, {{timeout_ms = 300}})"#,
+			fx_pid_file.display()
+		);
+
+		// -- Exec
+		let res = eval_lua(&lua, &script);
+
+		// -- Check
+		assert!(res.is_err(), "should have timed out");
+		let pid = std::fs::read_to_string(&fx_pid_file)?.trim().to_string();
+		// Note: The killed grand child might be a zombie until reaped by init
+		let mut alive = true;
+		for _ in 0..50 {
+			let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).unwrap_or_default();
+			let state = stat.rsplit(')').next().and_then(|rest| rest.split_whitespace().next());
+			if matches!(state, None | Some("Z")) {
+				alive = false;
+				break;
+			}
+			std::thread::sleep(std::time::Duration::from_millis(20));
+		}
+		assert!(!alive, "grand child 'sleep' should have been killed");
+
+		// -- Clean
+		std::fs::remove_file(&fx_pid_file)?;
+
+		Ok(())
+	}
+
 	#[tokio::test]
 	async fn test_lua_cmd_exec_invalid_command_pcall() -> Result<()> {
 		// -- Setup & Fixtures
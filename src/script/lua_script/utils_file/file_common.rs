@@ -63,10 +63,8 @@ pub(super) fn file_load(
 /// Does not return anything
 ///
 pub(super) fn file_save(_lua: &Lua, ctx: &RuntimeContext, rel_path: String, content: String) -> mlua::Result<()> {
-	let path = ctx.dir_context().resolve_path(&rel_path, PathResolver::WksDir)?;
-	ensure_file_dir(&path).map_err(Error::from)?;
-
-	write(&path, content)?;
+	let path = ctx.dir_context().resolve_write_path(&rel_path, PathResolver::WksDir)?;
+	ctx.file_changes().save(&path, &content)?;
 
 	get_hub().publish_sync(format!("-> Lua utils.file.save called on: {}", rel_path));
 
@@ -86,16 +84,8 @@ pub(super) fn file_save(_lua: &Lua, ctx: &RuntimeContext, rel_path: String, cont
 /// Does not return anything
 ///
 pub(super) fn file_append(_lua: &Lua, ctx: &RuntimeContext, rel_path: String, content: String) -> mlua::Result<()> {
-	let path = ctx.dir_context().resolve_path(&rel_path, PathResolver::WksDir)?;
-	ensure_file_dir(&path).map_err(Error::from)?;
-
-	let mut file = std::fs::OpenOptions::new()
-		.append(true)
-		.create(true)
-		.open(&path)
-		.map_err(Error::from)?;
-
-	file.write_all(content.as_bytes())?;
+	let path = ctx.dir_context().resolve_write_path(&rel_path, PathResolver::WksDir)?;
+	ctx.file_changes().append(&path, &content)?;
 
 	// NOTE: Could be too many prints
 	// get_hub().publish_sync(format!("-> Lua utils.file.append called on: {}", rel_path));
@@ -124,18 +114,13 @@ pub(super) fn file_ensure_exists(
 ) -> mlua::Result<mlua::Value> {
 	let options = options.unwrap_or_default();
 	let rel_path = SPath::new(path).map_err(Error::from)?;
-	let full_path = ctx.dir_context().resolve_path(&rel_path, PathResolver::WksDir)?;
+	let full_path = ctx.dir_context().resolve_write_path(&rel_path, PathResolver::WksDir)?;
+	let file_changes = ctx.file_changes();
 
-	// if the file does not exist, create it.
-	if !full_path.exists() {
-		simple_fs::ensure_file_dir(&full_path).map_err(|err| Error::custom(err.to_string()))?;
-		let content = content.unwrap_or_default();
-		write(&full_path, content)?;
-	}
-	// if we have the options.content_when_empty flag, if empty
-	else if options.content_when_empty && files::is_file_empty(&full_path)? {
+	// if the file does not exist, or is empty with the options.content_when_empty flag, create it.
+	if !file_changes.exists(&full_path) || (options.content_when_empty && file_changes.is_empty(&full_path)?) {
 		let content = content.unwrap_or_default();
-		write(full_path, content)?;
+		file_changes.save(&full_path, &content)?;
 	}
 
 	let file_meta = FileMeta::from(rel_path);
@@ -444,6 +429,27 @@ mod tests {
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn test_lua_file_save_write_policy_blocked() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_outside_path = "../test_file_save_write_policy_blocked.txt";
+		let fx_git_path = ".git/test_file_save_write_policy_blocked.txt";
+
+		// -- Exec
+		let outside_res =
+			run_reflective_agent(&format!(r#"return utils.file.save("{fx_outside_path}", "x");"#), None).await;
+		let git_res = run_reflective_agent(&format!(r#"return utils.file.append("{fx_git_path}", "x");"#), None).await;
+
+		// -- Check
+		let err = outside_res.err().ok_or("Should be blocked (outside of the workspace)")?;
+		assert_contains(&err.to_string(), "allowed_roots");
+		let err = git_res.err().ok_or("Should be blocked (.git/)")?;
+		assert_contains(&err.to_string(), "denied_globs '.git/**'");
+		assert!(!Path::new(SANDBOX_01_WKS_DIR).join(fx_outside_path).exists());
+
+		Ok(())
+	}
+
 	#[tokio::test]
 	async fn test_lua_file_list_glob_direct() -> Result<()> {
 		// -- Fixtures
@@ -8,6 +8,7 @@
 //! ### Functions
 //! * `utils.git.restore(path: string) -> string | table`
 
+use crate::dir_context::PathResolver;
 use crate::hub::get_hub;
 use crate::run::RuntimeContext;
 use crate::{Error, Result};
@@ -35,6 +36,10 @@ pub fn init_module(lua: &Lua, runtime_context: &RuntimeContext) -> Result<Table>
 /// ### Returns
 /// Returns the standard output as a string if the command is successful.
 ///
+/// The path is checked against the `[write_policy]` (like the `utils.file.save`).
+///
+/// With `--dry plan` (or `--review`), the restore is recorded as a file change (the index content of the file, from `git show :path`).
+///
 /// ### Exception
 /// Throws an error if the command's stderr output is not empty.
 ///
@@ -44,10 +49,17 @@ pub fn init_module(lua: &Lua, runtime_context: &RuntimeContext) -> Result<Table>
 /// print(result)
 /// ```
 fn git_restore(lua: &Lua, ctx: &RuntimeContext, path: String) -> mlua::Result<Value> {
+	if ctx.file_changes().mode().is_staged() {
+		return git_restore_plan(lua, ctx, path);
+	}
+
+	// Note: Checked like the other file writes (the restore overwrites the file)
+	let full_path = ctx.dir_context().resolve_write_path(&path, PathResolver::WksDir)?;
+
 	let output = std::process::Command::new("git")
 		.current_dir(ctx.dir_context().wks_dir())
 		.arg("restore")
-		.arg(&path)
+		.arg(full_path.path())
 		.output()
 		.expect("Failed to execute command");
 
@@ -63,3 +75,93 @@ fn git_restore(lua: &Lua, ctx: &RuntimeContext, path: String) -> mlua::Result<Va
 }
 
 // endregion: --- Lua Functions
+
+// region:    --- Support
+
+/// Record the `git restore` of the file as a file change (the file content of the index)
+///
+/// Note: The git path is the resolved path relative to the workspace dir, with `:./` so that it is relative to
+///       the current dir of the command (the workspace dir might be a sub dir of the git repo).
+fn git_restore_plan(lua: &Lua, ctx: &RuntimeContext, path: String) -> mlua::Result<Value> {
+	let wks_dir = ctx.dir_context().wks_dir();
+	let full_path = ctx.dir_context().resolve_write_path(&path, PathResolver::WksDir)?;
+	let git_path = full_path
+		.diff(wks_dir)
+		.ok()
+		.filter(|rel_path| !rel_path.to_str().starts_with(".."))
+		.ok_or_else(|| {
+			Error::custom(format!(
+				"'git restore {path}' (plan) failed. Path is outside of the workspace"
+			))
+		})?;
+
+	let output = std::process::Command::new("git")
+		.current_dir(wks_dir)
+		.arg("show")
+		.arg(format!(":./{git_path}"))
+		.output()?;
+
+	if !output.status.success() {
+		let stderr = String::from_utf8_lossy(&output.stderr);
+		return Err(Error::cc(format!("'git restore {path}' (plan) failed"), stderr).into());
+	}
+
+	let content = String::from_utf8_lossy(&output.stdout);
+	ctx.file_changes().save(&full_path, &content)?;
+
+	"".into_lua(lua)
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::assert_contains;
+	use crate::run::{Runtime, WriteMode};
+
+	#[test]
+	fn test_lua_git_restore_write_policy() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let ctx = runtime.context();
+		let lua = Lua::new();
+
+		// -- Exec
+		let res = git_restore(&lua, &ctx, "../../outside-of-wks.txt".to_string());
+
+		// -- Check
+		let err = res.err().ok_or("Should be blocked by the write policy")?;
+		assert_contains(&err.to_string(), "allowed_roots");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_lua_git_restore_plan_resolved_path() -> Result<()> {
+		// -- Setup & Fixtures
+		let runtime = Runtime::new_test_runtime_sandbox_01()?;
+		let ctx = runtime.context();
+		let lua = Lua::new();
+		let fx_path = "agent-script/../agent-script/agent-hello.aip";
+		let fx_file = runtime.dir_context().wks_dir().join_str("agent-script/agent-hello.aip");
+		ctx.file_changes().start(WriteMode::Plan, None);
+
+		// -- Exec
+		git_restore_plan(&lua, &ctx, fx_path.to_string())?;
+
+		// -- Check
+		let changes = ctx.file_changes().take_changes();
+		assert_eq!(changes.len(), 1);
+		assert_eq!(changes[0].path(), fx_file.to_str());
+		assert_eq!(changes[0].new_content(), std::fs::read_to_string(fx_file.path())?);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
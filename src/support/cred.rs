@@ -1,4 +1,5 @@
 use crate::Result;
+use crate::support::cred_store::CredStore;
 use crossterm::cursor::MoveTo;
 use crossterm::terminal::ClearType;
 use crossterm::{execute, terminal};
@@ -19,6 +20,23 @@ pub fn get_or_prompt_api_key(key_name: &str) -> Result<String> {
 	Ok(api_key)
 }
 
+/// Get the secret (without prompting)
+/// - From the local keychain (on Mac), then from the `~/.aipack-base/credentials.toml` store
+/// - Returns None if there is no entry for this name
+pub fn get_key(key_name: &str) -> Result<Option<String>> {
+	#[cfg(target_os = "macos")]
+	{
+		let entry = Entry::new(KEY_SERVICE, key_name)?;
+		match entry.get_password() {
+			Ok(pwd) => return Ok(Some(pwd)),
+			Err(keyring::Error::NoEntry) => (),
+			Err(other) => return Err(format!("Failed to execute keyring: {other}").into()),
+		}
+	}
+
+	CredStore::from_base_dir()?.get(key_name)
+}
+
 // region:    --- Support
 
 // Get the value from the local keychain, or prompt the user to save and return.
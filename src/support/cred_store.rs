@@ -0,0 +1,324 @@
+//! The file credential store of `~/.aipack-base/credentials.toml` (managed with `aip key set|list|remove`)
+//!
+//! - The key names are in clear, the values are encrypted (ChaCha20-Poly1305, with the key name as associated data)
+//! - The encryption key is created on first `set`, and is kept outside of the `~/.aipack-base/` data dir:
+//!   - First in the OS keyring (Mac keychain, Windows credential manager, Linux Secret Service),
+//!     under `aipack_secrets/credentials_key`
+//!   - Otherwise (if the keyring fails, e.g., no Secret Service running) in `~/.config/aipack/credentials.key`
+//!     (`$XDG_CONFIG_HOME` if set, user read/write only on unix)
+//!
+//! Note: This protects the secrets at rest (e.g., backups, file syncs, accidental commits of the data dir),
+//!       not from a process running as the same user.
+
+use crate::dir_context::aipack_base_dir;
+use crate::{Error, Result};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use keyring::Entry;
+use ring::aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use simple_fs::SPath;
+use std::collections::BTreeMap;
+
+const CREDENTIALS_FILE_NAME: &str = "credentials.toml";
+const CREDENTIALS_KEY_FILE_NAME: &str = "credentials.key";
+const KEYRING_SERVICE: &str = "aipack_secrets";
+const KEYRING_KEY_NAME: &str = "credentials_key";
+const VALUE_PREFIX: &str = "v1:";
+
+const CREDENTIALS_FILE_HEADER: &str = "\
+# aipack credentials (values encrypted with the credentials key, kept outside of this dir)
+# Managed with `aip key set|list|remove <KEY_NAME>`
+";
+
+#[derive(Debug, Clone)]
+pub struct CredStore {
+	file: SPath,
+	/// The fallback key file (when the OS keyring is not used or not available)
+	key_file: SPath,
+	use_keyring: bool,
+}
+
+/// Constructors
+impl CredStore {
+	/// The store of the `~/.aipack-base/` dir, with the key in the OS keyring (or the user config dir)
+	pub fn from_base_dir() -> Result<Self> {
+		let key_dir = default_key_dir()?;
+		// Note: On the other OSes, the keyring crate has no persistent store (only a mock one)
+		let use_keyring = cfg!(any(target_os = "macos", target_os = "windows", target_os = "linux"));
+		Ok(Self::new(&aipack_base_dir()?, &key_dir, use_keyring))
+	}
+
+	/// - `key_dir` is the dir of the fallback key file (should not be the `base_dir`)
+	/// - `use_keyring` to store the key in the OS keyring first
+	pub fn new(base_dir: &SPath, key_dir: &SPath, use_keyring: bool) -> Self {
+		Self {
+			file: base_dir.join_str(CREDENTIALS_FILE_NAME),
+			key_file: key_dir.join_str(CREDENTIALS_KEY_FILE_NAME),
+			use_keyring,
+		}
+	}
+}
+
+impl CredStore {
+	/// Returns the decrypted value of this key name (None if not in the store)
+	pub fn get(&self, name: &str) -> Result<Option<String>> {
+		let entries = self.load_entries()?;
+		let Some(encrypted) = entries.get(name) else {
+			return Ok(None);
+		};
+
+		let value = decrypt(&self.load_key(false)?, name, encrypted)
+			.map_err(|err| Error::custom(format!("Cannot decrypt credential '{name}' in '{}'. {err}", self.file)))?;
+
+		Ok(Some(value))
+	}
+
+	pub fn set(&self, name: &str, value: &str) -> Result<()> {
+		if name.trim().is_empty() {
+			return Err(Error::custom("Credential key name cannot be empty"));
+		}
+		let mut entries = self.load_entries()?;
+		let encrypted = encrypt(&self.load_key(true)?, name, value)?;
+		entries.insert(name.to_string(), encrypted);
+		self.save_entries(&entries)
+	}
+
+	/// Remove the key name. Returns true if it was present.
+	pub fn remove(&self, name: &str) -> Result<bool> {
+		let mut entries = self.load_entries()?;
+		let removed = entries.remove(name).is_some();
+		if removed {
+			self.save_entries(&entries)?;
+		}
+		Ok(removed)
+	}
+
+	/// The key names (sorted)
+	pub fn list(&self) -> Result<Vec<String>> {
+		Ok(self.load_entries()?.into_keys().collect())
+	}
+}
+
+// region:    --- Support
+
+impl CredStore {
+	fn load_entries(&self) -> Result<BTreeMap<String, String>> {
+		if !self.file.exists() {
+			return Ok(BTreeMap::new());
+		}
+		let content = std::fs::read_to_string(self.file.path())?;
+		let entries: BTreeMap<String, String> = toml::from_str(&content).map_err(|err| Error::Config {
+			path: self.file.to_string(),
+			reason: format!("Invalid credentials file. Cause: {err}"),
+		})?;
+		Ok(entries)
+	}
+
+	fn save_entries(&self, entries: &BTreeMap<String, String>) -> Result<()> {
+		let content =
+			toml::to_string(entries).map_err(|err| Error::custom(format!("Cannot save credentials. {err}")))?;
+		write_private_file(&self.file, &format!("{CREDENTIALS_FILE_HEADER}\n{content}"))
+	}
+
+	/// Load the encryption key (create it if `create` and it does not exist)
+	fn load_key(&self, create: bool) -> Result<LessSafeKey> {
+		let key_b64 = match self.read_key()? {
+			Some(key_b64) => key_b64,
+			None if create => {
+				let mut key_bytes = vec![0u8; CHACHA20_POLY1305.key_len()];
+				SystemRandom::new()
+					.fill(&mut key_bytes)
+					.map_err(|_| Error::custom("Cannot generate the credentials key"))?;
+				let key_b64 = BASE64.encode(&key_bytes);
+				self.save_key(&key_b64)?;
+				key_b64
+			}
+			None => {
+				return Err(Error::custom(format!(
+					"Credentials key not found (in the OS keyring or '{}')",
+					self.key_file
+				)));
+			}
+		};
+
+		let unbound_key = BASE64
+			.decode(key_b64.trim())
+			.ok()
+			.and_then(|key_bytes| UnboundKey::new(&CHACHA20_POLY1305, &key_bytes).ok())
+			.ok_or("Invalid credentials key")?;
+
+		Ok(LessSafeKey::new(unbound_key))
+	}
+
+	/// Read the key (base64) from the OS keyring first, then from the key file
+	fn read_key(&self) -> Result<Option<String>> {
+		if self.use_keyring {
+			// Note: A keyring error (e.g., not available) falls back to the key file
+			if let Ok(key_b64) = keyring_entry().and_then(|entry| Ok(entry.get_password()?)) {
+				return Ok(Some(key_b64));
+			}
+		}
+
+		if self.key_file.exists() {
+			Ok(Some(std::fs::read_to_string(self.key_file.path())?))
+		} else {
+			Ok(None)
+		}
+	}
+
+	/// Save the key (base64) in the OS keyring, or in the key file if the keyring is not used or not available
+	fn save_key(&self, key_b64: &str) -> Result<()> {
+		if self.use_keyring && keyring_entry().and_then(|entry| Ok(entry.set_password(key_b64)?)).is_ok() {
+			return Ok(());
+		}
+
+		write_private_file(&self.key_file, key_b64)
+	}
+}
+
+fn keyring_entry() -> Result<Entry> {
+	Ok(Entry::new(KEYRING_SERVICE, KEYRING_KEY_NAME)?)
+}
+
+/// The dir of the fallback key file, `$XDG_CONFIG_HOME/aipack/` or `~/.config/aipack/`
+fn default_key_dir() -> Result<SPath> {
+	let config_dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+		Some(dir) => std::path::PathBuf::from(dir),
+		None => home::home_dir()
+			.ok_or("No Home Dir Found, cannot locate the credentials key")?
+			.join(".config"),
+	};
+
+	Ok(SPath::new(config_dir.join("aipack"))?)
+}
+
+/// Encrypt the value, returns `v1:<base64 of nonce + ciphertext + tag>`
+fn encrypt(key: &LessSafeKey, name: &str, value: &str) -> Result<String> {
+	let mut nonce_bytes = [0u8; NONCE_LEN];
+	SystemRandom::new()
+		.fill(&mut nonce_bytes)
+		.map_err(|_| Error::custom("Cannot generate the nonce"))?;
+
+	let mut in_out = value.as_bytes().to_vec();
+	key.seal_in_place_append_tag(
+		Nonce::assume_unique_for_key(nonce_bytes),
+		Aad::from(name.as_bytes()),
+		&mut in_out,
+	)
+	.map_err(|_| Error::custom("Cannot encrypt the credential"))?;
+
+	let mut data = nonce_bytes.to_vec();
+	data.extend(in_out);
+
+	Ok(format!("{VALUE_PREFIX}{}", BASE64.encode(data)))
+}
+
+fn decrypt(key: &LessSafeKey, name: &str, encrypted: &str) -> Result<String> {
+	let data = encrypted
+		.strip_prefix(VALUE_PREFIX)
+		.and_then(|b64| BASE64.decode(b64).ok())
+		.filter(|data| data.len() > NONCE_LEN)
+		.ok_or("Invalid encrypted value format")?;
+
+	let (nonce_bytes, ciphertext) = data.split_at(NONCE_LEN);
+	let nonce = Nonce::try_assume_unique_for_key(nonce_bytes).map_err(|_| Error::custom("Invalid nonce"))?;
+	let mut in_out = ciphertext.to_vec();
+	let plain = key
+		.open_in_place(nonce, Aad::from(name.as_bytes()), &mut in_out)
+		.map_err(|_| Error::custom("Wrong credentials key or corrupted value"))?;
+
+	String::from_utf8(plain.to_vec()).map_err(|_| Error::custom("Credential value is not UTF-8"))
+}
+
+/// Write the file with user only read/write permissions (on unix)
+fn write_private_file(file: &SPath, content: &str) -> Result<()> {
+	if let Some(parent) = file.parent() {
+		std::fs::create_dir_all(parent.path())?;
+	}
+
+	#[cfg(unix)]
+	{
+		use std::io::Write as _;
+		use std::os::unix::fs::OpenOptionsExt as _;
+		use std::os::unix::fs::PermissionsExt as _;
+
+		let mut f = std::fs::OpenOptions::new()
+			.write(true)
+			.create(true)
+			.truncate(true)
+			.mode(0o600)
+			.open(file.path())?;
+		// Note: The mode is only applied on create, so make sure it is set for existing files
+		f.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+		f.write_all(content.as_bytes())?;
+	}
+	#[cfg(not(unix))]
+	{
+		std::fs::write(file.path(), content)?;
+	}
+
+	Ok(())
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_cred_store_set_get_list_remove() -> Result<()> {
+		// -- Setup & Fixtures
+		let base_dir = SPath::new(std::env::temp_dir().join(format!("aipack-test-cred-store-{}", std::process::id())))?;
+		let key_dir = SPath::new(std::env::temp_dir().join(format!("aipack-test-cred-key-{}", std::process::id())))?;
+		let store = CredStore::new(&base_dir, &key_dir, false);
+
+		// -- Exec
+		store.set("OPENAI_API_KEY", "sk-fx-123")?;
+		store.set("MYPROXY_API_KEY", "proxy-fx-456")?;
+		let value = store.get("OPENAI_API_KEY")?;
+		let names = store.list()?;
+		let removed = store.remove("OPENAI_API_KEY")?;
+		let removed_again = store.remove("OPENAI_API_KEY")?;
+		let file_content = std::fs::read_to_string(base_dir.join_str(CREDENTIALS_FILE_NAME).path())?;
+
+		// -- Check
+		assert_eq!(value.as_deref(), Some("sk-fx-123"));
+		assert_eq!(names, vec!["MYPROXY_API_KEY".to_string(), "OPENAI_API_KEY".to_string()]);
+		assert!(removed, "should have been removed");
+		assert!(!removed_again, "should not be there anymore");
+		assert_eq!(store.get("OPENAI_API_KEY")?, None);
+		assert_eq!(store.get("MYPROXY_API_KEY")?.as_deref(), Some("proxy-fx-456"));
+		assert!(
+			!file_content.contains("proxy-fx-456"),
+			"value should be encrypted at rest"
+		);
+		assert!(
+			key_dir.join_str(CREDENTIALS_KEY_FILE_NAME).exists(),
+			"key should be in the key dir"
+		);
+		let base_dir_files = std::fs::read_dir(base_dir.path())?.count();
+		assert_eq!(base_dir_files, 1, "only the credentials file should be in the base dir");
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt as _;
+			let key_file_mode = std::fs::metadata(key_dir.join_str(CREDENTIALS_KEY_FILE_NAME).path())?
+				.permissions()
+				.mode();
+			assert_eq!(key_file_mode & 0o777, 0o600, "key file should be user read/write only");
+		}
+
+		// -- Clean
+		std::fs::remove_dir_all(base_dir.path())?;
+		std::fs::remove_dir_all(key_dir.path())?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
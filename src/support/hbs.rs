@@ -1,19 +1,18 @@
 // region:    --- Modules
 
 use crate::Result;
-use handlebars::Handlebars;
+use crate::support::md::MdSectionIter;
+use handlebars::{Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderErrorReason};
 use serde_json::Value;
-use std::sync::{Arc, LazyLock};
+use simple_fs::{SPath, read_to_string};
+use std::sync::{Arc, LazyLock, Mutex};
 
 // endregion: --- Modules
 
-static HANDLEBARS: LazyLock<Arc<Handlebars>> = LazyLock::new(|| {
-	let mut handlebars = Handlebars::new();
-	// Disable escaping globally
-	handlebars.register_escape_fn(|s| s.to_string());
+static HANDLEBARS: LazyLock<Arc<Handlebars>> = LazyLock::new(|| Arc::new(new_handlebars()));
 
-	Arc::new(handlebars)
-});
+/// Resolves the path of a `{{include "..."}}` (e.g., relative to the agent dir, or a pack ref) to a file path.
+pub type HbsIncludeResolver = Arc<dyn Fn(&str) -> Result<SPath> + Send + Sync>;
 
 pub fn hbs_render(hbs_tmpl: &str, data_root: &Value) -> Result<String> {
 	let handlebars = &*HANDLEBARS;
@@ -21,6 +20,197 @@ pub fn hbs_render(hbs_tmpl: &str, data_root: &Value) -> Result<String> {
 	Ok(res)
 }
 
+/// Render the template with the `include` helper, which inserts the content of a file (rendered with the same data).
+///
+/// - `{{include "path/to/guide.md"}}` - the whole file
+/// - `{{include "path/to/guide.md" section="## Style"}}` - only the matching markdown section(s)
+///   (same heading patterns as `utils.file.load_md_sections`, can be an array)
+///
+/// Note: The included files can include other files, and the include cycles are reported as an error.
+pub fn hbs_render_with_include(
+	hbs_tmpl: &str,
+	data_root: &Value,
+	include_resolver: HbsIncludeResolver,
+) -> Result<String> {
+	let handlebars = new_handlebars_with_include(include_resolver);
+	let res = handlebars.render_template(hbs_tmpl, &data_root)?;
+	Ok(res)
+}
+
+/// An `{{attach "path"}}` of a rendered prompt part (the path is resolved and loaded by the caller).
+#[derive(Debug, Clone, PartialEq)]
+pub struct HbsAttach {
+	pub path: String,
+	pub content_type: Option<String>,
+}
+
+/// Render a prompt part, with the `include` helper (see `hbs_render_with_include`) and the `attach` helper.
+///
+/// - `{{attach "path/to/screenshot.png"}}` - attach the file to the message (renders nothing)
+/// - `{{attach input.path content_type="application/pdf"}}` - with an explicit content type
+///
+/// Returns the rendered content and the attachments, in order.
+pub fn hbs_render_prompt(
+	hbs_tmpl: &str,
+	data_root: &Value,
+	include_resolver: HbsIncludeResolver,
+) -> Result<(String, Vec<HbsAttach>)> {
+	let attachments: Arc<Mutex<Vec<HbsAttach>>> = Default::default();
+
+	let mut handlebars = new_handlebars_with_include(include_resolver);
+	handlebars.register_helper(
+		"attach",
+		Box::new(AttachHelper {
+			attachments: attachments.clone(),
+		}),
+	);
+
+	let res = handlebars.render_template(hbs_tmpl, &data_root)?;
+	let attachments = std::mem::take(&mut *attachments.lock().unwrap_or_else(|err| err.into_inner()));
+
+	Ok((res, attachments))
+}
+
+fn new_handlebars_with_include(include_resolver: HbsIncludeResolver) -> Handlebars<'static> {
+	let mut handlebars = new_handlebars();
+	handlebars.register_helper(
+		"include",
+		Box::new(IncludeHelper {
+			resolver: include_resolver,
+			include_stack: Default::default(),
+		}),
+	);
+	handlebars
+}
+
+fn new_handlebars() -> Handlebars<'static> {
+	let mut handlebars = Handlebars::new();
+	// Disable escaping globally
+	handlebars.register_escape_fn(|s| s.to_string());
+	handlebars
+}
+
+// region:    --- IncludeHelper
+
+struct IncludeHelper {
+	resolver: HbsIncludeResolver,
+	/// The files being included (to detect the cycles)
+	include_stack: Mutex<Vec<String>>,
+}
+
+impl HelperDef for IncludeHelper {
+	fn call<'reg: 'rc, 'rc>(
+		&self,
+		h: &Helper<'rc>,
+		r: &'reg Handlebars<'reg>,
+		ctx: &'rc Context,
+		_rc: &mut RenderContext<'reg, 'rc>,
+		out: &mut dyn Output,
+	) -> HelperResult {
+		let path = h
+			.param(0)
+			.and_then(|v| v.value().as_str())
+			.ok_or(RenderErrorReason::ParamNotFoundForIndex("include", 0))?;
+
+		let sections: Option<Vec<String>> = match h.hash_get("section").map(|v| v.value()) {
+			None => None,
+			Some(Value::String(section)) => Some(vec![section.to_string()]),
+			Some(Value::Array(sections)) => Some(
+				sections
+					.iter()
+					.map(|v| v.as_str().map(String::from))
+					.collect::<Option<Vec<_>>>()
+					.ok_or(RenderErrorReason::InvalidParamType(
+						"section string or array of strings",
+					))?,
+			),
+			Some(_) => return Err(RenderErrorReason::InvalidParamType("section string or array of strings").into()),
+		};
+
+		let file = (self.resolver)(path)
+			.map_err(|err| RenderErrorReason::Other(format!("include \"{path}\" failed. Cause: {err}")))?;
+		let file_key = file.to_str().to_string();
+
+		// -- Check the include cycle
+		{
+			let mut include_stack = self.include_stack.lock().unwrap_or_else(|err| err.into_inner());
+			if include_stack.contains(&file_key) {
+				let mut chain = include_stack.clone();
+				chain.push(file_key);
+				return Err(RenderErrorReason::Other(format!("include cycle detected: {}", chain.join(" -> "))).into());
+			}
+			include_stack.push(file_key);
+		}
+
+		// Note: The included content is rendered with the same data (and can include other files)
+		let res = match read_include_content(&file, sections.as_deref()) {
+			Ok(content) => r.render_template(&content, ctx.data()),
+			Err(err) => Err(RenderErrorReason::Other(format!("include \"{path}\" failed. Cause: {err}")).into()),
+		};
+
+		self.include_stack.lock().unwrap_or_else(|err| err.into_inner()).pop();
+
+		out.write(&res?)?;
+
+		Ok(())
+	}
+}
+
+fn read_include_content(file: &SPath, sections: Option<&[String]>) -> Result<String> {
+	let content = read_to_string(file)?;
+
+	let Some(sections) = sections else {
+		return Ok(content);
+	};
+
+	let heading_patterns: Vec<&str> = sections.iter().map(|s| s.as_str()).collect();
+	let content = MdSectionIter::from_str(&content, Some(&heading_patterns))?
+		.map(|section| format!("{}{}", section.heading_raw(), section.content))
+		.collect::<Vec<_>>()
+		.join("\n");
+
+	Ok(content)
+}
+
+// endregion: --- IncludeHelper
+
+// region:    --- AttachHelper
+
+struct AttachHelper {
+	attachments: Arc<Mutex<Vec<HbsAttach>>>,
+}
+
+impl HelperDef for AttachHelper {
+	fn call<'reg: 'rc, 'rc>(
+		&self,
+		h: &Helper<'rc>,
+		_r: &'reg Handlebars<'reg>,
+		_ctx: &'rc Context,
+		_rc: &mut RenderContext<'reg, 'rc>,
+		_out: &mut dyn Output,
+	) -> HelperResult {
+		let path = h
+			.param(0)
+			.and_then(|v| v.value().as_str())
+			.ok_or(RenderErrorReason::ParamNotFoundForIndex("attach", 0))?;
+
+		let content_type = match h.hash_get("content_type").map(|v| v.value()) {
+			None => None,
+			Some(Value::String(content_type)) => Some(content_type.to_string()),
+			Some(_) => return Err(RenderErrorReason::InvalidParamType("content_type string").into()),
+		};
+
+		self.attachments.lock().unwrap_or_else(|err| err.into_inner()).push(HbsAttach {
+			path: path.to_string(),
+			content_type,
+		});
+
+		Ok(())
+	}
+}
+
+// endregion: --- AttachHelper
+
 // region:    --- Tests
 
 #[cfg(test)]
@@ -29,8 +219,10 @@ mod tests {
 
 	use crate::_test_support::assert_contains;
 	use crate::run::Runtime;
-	use crate::support::hbs::hbs_render;
+	use crate::support::hbs::{HbsAttach, HbsIncludeResolver, hbs_render, hbs_render_prompt, hbs_render_with_include};
 	use serde_json::json;
+	use simple_fs::SPath;
+	use std::sync::Arc;
 
 	#[tokio::test]
 	async fn test_hbs_with_lua_ok() -> Result<()> {
@@ -64,6 +256,79 @@ The files are:
 
 		Ok(())
 	}
+
+	#[test]
+	fn test_hbs_include_section() -> Result<()> {
+		// -- Setup & Fixtures
+		let tmpl = r###"Guide:
+{{include "style-guide.md" section="## Tone"}}"###;
+		let data = json!({"data": {"name": "the user"}});
+
+		// -- Exec
+		let res = hbs_render_with_include(tmpl, &data, fx_include_resolver())?;
+
+		// -- Check
+		assert_contains(&res, "## Tone");
+		assert_contains(&res, "Be concise with the user.");
+		assert!(
+			!res.contains("Use markdown lists."),
+			"Should not include the '## Format' section"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_hbs_include_cycle() -> Result<()> {
+		// -- Setup & Fixtures
+		let tmpl = r#"{{include "cycle-a.md"}}"#;
+
+		// -- Exec
+		let res = hbs_render_with_include(tmpl, &json!({}), fx_include_resolver());
+
+		// -- Check
+		let err = res.err().ok_or("Should fail with an include cycle")?;
+		assert_contains(&err.to_string(), "include cycle detected");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_hbs_render_prompt_attach() -> Result<()> {
+		// -- Setup & Fixtures
+		let tmpl = r#"Check this screenshot:{{attach data.shot}}
+{{attach "doc.bin" content_type="application/pdf"}}"#;
+		let data = json!({"data": {"shot": "screens/bug.png"}});
+
+		// -- Exec
+		let (content, attachments) = hbs_render_prompt(tmpl, &data, fx_include_resolver())?;
+
+		// -- Check
+		assert_eq!(content.trim(), "Check this screenshot:");
+		assert_eq!(
+			attachments,
+			vec![
+				HbsAttach {
+					path: "screens/bug.png".to_string(),
+					content_type: None
+				},
+				HbsAttach {
+					path: "doc.bin".to_string(),
+					content_type: Some("application/pdf".to_string())
+				},
+			]
+		);
+
+		Ok(())
+	}
+
+	// region:    --- Support
+
+	fn fx_include_resolver() -> HbsIncludeResolver {
+		Arc::new(|path: &str| Ok(SPath::new(format!("./tests-data/sandbox-01/other/include/{path}"))?))
+	}
+
+	// endregion: --- Support
 }
 
 // endregion: --- Tests
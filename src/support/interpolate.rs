@@ -0,0 +1,170 @@
+//! Interpolation of the `${env:VAR}` and `${keyring:name}` references in the config and options values.
+//!
+//! Note: The error messages only have the reference names, never the resolved values.
+
+use crate::Result;
+use crate::support::cred::get_key;
+use serde_json::Value;
+
+const ENV_PREFIX: &str = "${env:";
+const KEYRING_PREFIX: &str = "${keyring:";
+
+/// Resolve the `${env:VAR}` and `${keyring:name}` references of all the string values (recursively).
+///
+/// - `${env:VAR}` - the environment variable `VAR`
+/// - `${keyring:name}` - the `name` secret of the Mac keychain (service `aipack_secrets`) or of the credentials store
+/// - Other `${...}` are kept as is
+pub fn interpolate_value(value: &mut Value) -> Result<()> {
+	interpolate_value_with(value, &resolve_ref)
+}
+
+/// Same as `interpolate_value` with a custom resolver (kind, name) -> Option<value>
+pub fn interpolate_value_with(
+	value: &mut Value,
+	resolver: &dyn Fn(RefKind, &str) -> Result<Option<String>>,
+) -> Result<()> {
+	match value {
+		Value::String(content) if content.contains("${") => {
+			*content = interpolate_str(content, resolver)?;
+		}
+		Value::Array(items) => {
+			for item in items.iter_mut() {
+				interpolate_value_with(item, resolver)?;
+			}
+		}
+		Value::Object(obj) => {
+			for (_, item) in obj.iter_mut() {
+				interpolate_value_with(item, resolver)?;
+			}
+		}
+		_ => (),
+	}
+
+	Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefKind {
+	Env,
+	Keyring,
+}
+
+// region:    --- Support
+
+fn interpolate_str(content: &str, resolver: &dyn Fn(RefKind, &str) -> Result<Option<String>>) -> Result<String> {
+	let mut res = String::with_capacity(content.len());
+	let mut rest = content;
+
+	while let Some(start) = rest.find("${") {
+		let (before, from_ref) = rest.split_at(start);
+		res.push_str(before);
+
+		let (kind, prefix) = if from_ref.starts_with(ENV_PREFIX) {
+			(RefKind::Env, ENV_PREFIX)
+		} else if from_ref.starts_with(KEYRING_PREFIX) {
+			(RefKind::Keyring, KEYRING_PREFIX)
+		} else {
+			// not a reference we support, keep the `${` as is
+			res.push_str("${");
+			rest = &from_ref[2..];
+			continue;
+		};
+
+		let Some(end) = from_ref.find('}') else {
+			return Err(format!("Unclosed reference '{from_ref}' (missing '}}')").into());
+		};
+		let reference = &from_ref[..=end];
+		let name = from_ref[prefix.len()..end].trim();
+		if name.is_empty() {
+			return Err(format!("Reference '{reference}' has no name").into());
+		}
+
+		let value = resolver(kind, name)?.ok_or_else(|| match kind {
+			RefKind::Env => format!("Environment variable '{name}' not found (for '{reference}')"),
+			RefKind::Keyring => format!("Keyring secret '{name}' not found (for '{reference}')"),
+		})?;
+		res.push_str(&value);
+
+		rest = &from_ref[end + 1..];
+	}
+	res.push_str(rest);
+
+	Ok(res)
+}
+
+fn resolve_ref(kind: RefKind, name: &str) -> Result<Option<String>> {
+	match kind {
+		RefKind::Env => Ok(std::env::var(name).ok()),
+		RefKind::Keyring => get_key(name),
+	}
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::assert_contains;
+	use serde_json::json;
+
+	#[test]
+	fn test_interpolate_value_env_and_keyring() -> Result<()> {
+		// -- Setup & Fixtures
+		let mut value = json!({
+			"model": "${env:FX_MODEL}",
+			"extras": {"headers": ["Bearer ${keyring:fx_token}"]},
+			"other": "cost ${USD} ${env:FX_MODEL}-x",
+			"temperature": 0.2
+		});
+
+		// -- Exec
+		interpolate_value_with(&mut value, &fx_resolver)?;
+
+		// -- Check
+		assert_eq!(
+			value,
+			json!({
+				"model": "gpt-4o",
+				"extras": {"headers": ["Bearer secret-123"]},
+				"other": "cost ${USD} gpt-4o-x",
+				"temperature": 0.2
+			})
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_interpolate_value_missing() -> Result<()> {
+		// -- Setup & Fixtures
+		let mut value = json!({"model": "${env:FX_MISSING}"});
+
+		// -- Exec
+		let res = interpolate_value_with(&mut value, &fx_resolver);
+
+		// -- Check
+		let err = res.err().ok_or("Should fail for a missing env variable")?;
+		assert_contains(&err.to_string(), "Environment variable 'FX_MISSING' not found");
+
+		Ok(())
+	}
+
+	// region:    --- Support
+
+	fn fx_resolver(kind: RefKind, name: &str) -> crate::Result<Option<String>> {
+		let value = match (kind, name) {
+			(RefKind::Env, "FX_MODEL") => Some("gpt-4o".to_string()),
+			(RefKind::Keyring, "fx_token") => Some("secret-123".to_string()),
+			_ => None,
+		};
+		Ok(value)
+	}
+
+	// endregion: --- Support
+}
+
+// endregion: --- Tests
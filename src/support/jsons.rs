@@ -9,3 +9,137 @@ pub fn into_values<T: serde::Serialize>(vals: Vec<T>) -> Result<Vec<Value>> {
 
 	Ok(inputs)
 }
+
+// region:    --- Json Schema
+
+/// Validate a json value against a json schema (full JSON Schema support, e.g., `$ref`, `$defs`, `pattern`, `format`),
+/// returning the violations as error message (with the json pointer of the offending value).
+///
+/// Note: An invalid schema, or a schema with remote `$ref`, is an error (the value is never considered valid).
+pub fn validate_json_schema(value: &Value, schema: &Value) -> core::result::Result<(), String> {
+	let validator = jsonschema::options()
+		.should_validate_formats(true)
+		.build(schema)
+		.map_err(|err| format!("Invalid output_schema. Cause: {err}"))?;
+
+	let violations = validator
+		.iter_errors(value)
+		.map(|err| format!("'{}' {err}", display_path(&err.instance_path.to_string())))
+		.collect::<Vec<_>>();
+
+	if violations.is_empty() {
+		Ok(())
+	} else {
+		Err(violations.join("; "))
+	}
+}
+
+fn display_path(path: &str) -> &str {
+	if path.is_empty() { "/" } else { path }
+}
+
+// endregion: --- Json Schema
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::_test_support::assert_contains;
+	use serde_json::json;
+
+	#[test]
+	fn test_support_jsons_validate_json_schema_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_schema = json!({
+			"type": "object",
+			"properties": {
+				"title": { "type": "string", "minLength": 1 },
+				"tags": { "type": "array", "items": { "type": "string" } },
+				"level": { "enum": ["low", "high"] }
+			},
+			"required": ["title"],
+			"additionalProperties": false
+		});
+		let fx_value = json!({"title": "Hello", "tags": ["a", "b"], "level": "low"});
+
+		// -- Exec & Check
+		validate_json_schema(&fx_value, &fx_schema)?;
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_support_jsons_validate_json_schema_errors() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_schema = json!({
+			"type": "object",
+			"properties": {
+				"title": { "type": "string" },
+				"tags": { "type": "array", "items": { "type": "string" } }
+			},
+			"required": ["title"],
+			"additionalProperties": false
+		});
+
+		// -- Exec
+		let missing = validate_json_schema(&json!({"tags": []}), &fx_schema)
+			.err()
+			.ok_or("should fail")?;
+		let wrong_item = validate_json_schema(&json!({"title": "t", "tags": ["a", 2]}), &fx_schema)
+			.err()
+			.ok_or("should fail")?;
+		let additional = validate_json_schema(&json!({"title": "t", "other": 1}), &fx_schema)
+			.err()
+			.ok_or("should fail")?;
+
+		// -- Check
+		assert_contains(&missing, r#"'/' "title" is a required property"#);
+		assert_contains(&wrong_item, r#"'/tags/1' 2 is not of type "string""#);
+		assert_contains(
+			&additional,
+			"'/' Additional properties are not allowed ('other' was unexpected)",
+		);
+
+		Ok(())
+	}
+	#[test]
+	fn test_support_jsons_validate_json_schema_ref_pattern_format() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_schema = json!({
+			"$defs": {
+				"code": { "type": "string", "pattern": "^[A-Z]{3}$" }
+			},
+			"type": "object",
+			"properties": {
+				"code": { "$ref": "#/$defs/code" },
+				"email": { "type": "string", "format": "email" }
+			}
+		});
+
+		// -- Exec
+		let valid_res = validate_json_schema(&json!({"code": "ABC", "email": "a@b.com"}), &fx_schema);
+		let pattern_err = validate_json_schema(&json!({"code": "abc"}), &fx_schema)
+			.err()
+			.ok_or("should fail")?;
+		let format_err = validate_json_schema(&json!({"email": "not-an-email"}), &fx_schema)
+			.err()
+			.ok_or("should fail")?;
+		let invalid_schema_err = validate_json_schema(&json!({}), &json!({"type": 12}))
+			.err()
+			.ok_or("should fail")?;
+
+		// -- Check
+		assert!(valid_res.is_ok(), "should be valid");
+		assert_contains(&pattern_err, "'/code'");
+		assert_contains(&pattern_err, "^[A-Z]{3}$");
+		assert_contains(&format_err, "'/email'");
+		assert_contains(&invalid_schema_err, "Invalid output_schema");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -12,9 +12,11 @@ pub use str_ext::*;
 
 pub mod code;
 pub mod cred;
+pub mod cred_store;
 pub mod files;
 pub mod hbs;
 pub mod html;
+pub mod interpolate;
 pub mod jsons;
 pub mod md;
 pub mod paths;
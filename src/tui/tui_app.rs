@@ -1,6 +1,7 @@
 use crate::cli::CliArgs;
 use crate::exec::{ExecCommand, ExecEvent};
 use crate::hub::{HubEvent, get_hub};
+use crate::run::{ReviewDecision, ReviewRequest};
 use crate::tui::in_reader::InReader;
 use crate::tui::tui_elem;
 use crate::{Error, Result};
@@ -9,6 +10,7 @@ use crossterm::event::{KeyCode, KeyModifiers};
 use crossterm::terminal::{Clear, ClearType};
 use crossterm::{cursor, execute, terminal};
 use std::io::Write as _;
+use std::sync::{Arc, Mutex};
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::{mpsc, oneshot};
 
@@ -16,12 +18,19 @@ use tokio::sync::{mpsc, oneshot};
 #[derive(Debug)]
 pub struct TuiApp {
 	executor_tx: mpsc::Sender<ExecCommand>,
+	/// The file change review waiting for the user decision (`--review`)
+	pending_review: PendingReview,
 }
 
+type PendingReview = Arc<Mutex<Option<Arc<ReviewRequest>>>>;
+
 /// Constructor
 impl TuiApp {
 	pub fn new(executor_tx: mpsc::Sender<ExecCommand>) -> Self {
-		Self { executor_tx }
+		Self {
+			executor_tx,
+			pending_review: Default::default(),
+		}
 	}
 }
 
@@ -30,6 +39,10 @@ impl TuiApp {
 	fn executor_tx(&self) -> mpsc::Sender<ExecCommand> {
 		self.executor_tx.clone()
 	}
+
+	fn pending_review(&self) -> PendingReview {
+		self.pending_review.clone()
+	}
 }
 
 /// Starter
@@ -83,10 +96,30 @@ impl TuiApp {
 			in_reader.start();
 
 			let exec_tx = self.executor_tx();
+			let pending_review = self.pending_review();
 
 			tokio::spawn(async move {
 				let hub = get_hub();
 				while let Ok(key_event) = in_rx.recv_async().await {
+					// -- When a review is pending, the keys answer it (only the quit keys are still active)
+					let review = pending_review.lock().unwrap_or_else(|err| err.into_inner()).clone();
+					if let Some(review) = review {
+						let decision = match key_event.code {
+							KeyCode::Char('y') => Some(ReviewDecision::Accept),
+							KeyCode::Char('n') => Some(ReviewDecision::Reject),
+							KeyCode::Char('e') => Some(ReviewDecision::Edit),
+							_ => None,
+						};
+						if let Some(decision) = decision {
+							pending_review.lock().unwrap_or_else(|err| err.into_inner()).take();
+							review.decide(decision);
+							continue;
+						}
+						if !matches!(key_event.code, KeyCode::Char('q') | KeyCode::Char('c')) {
+							continue;
+						}
+					}
+
 					match key_event.code {
 						// -- Redo
 						KeyCode::Char('r') => {
@@ -123,6 +156,7 @@ impl TuiApp {
 	/// For now, we just print most of tose event content.
 	fn handle_hub_event(&self, interactive: bool) {
 		let exec_tx = self.executor_tx();
+		let pending_review = self.pending_review();
 
 		tokio::spawn(async move {
 			let mut rx = get_hub().subscriber();
@@ -141,6 +175,13 @@ impl TuiApp {
 
 							HubEvent::LuaPrint(text) => safer_println(&text, interactive),
 
+							HubEvent::AiStreamChunk(chunk) => safer_print_chunk(&chunk, interactive),
+
+							HubEvent::Review(request) => {
+								tui_elem::print_review_change(&request);
+								*pending_review.lock().unwrap_or_else(|err| err.into_inner()) = Some(request);
+							}
+
 							HubEvent::Executor(exec_event) => {
 								if let (ExecEvent::RunEnd, true) = (exec_event, interactive) {
 									// safer_println("\n[ r ]: Redo   |   [ q ]: Quit", interactive);
@@ -232,6 +273,22 @@ fn safer_println(msg: &str, interactive: bool) {
 	}
 }
 
+/// Print a streamed chunk as is (no new line added).
+/// When interactive (raw mode), the `\n` needs to be `\r\n` to go back to the start of the line.
+fn safer_print_chunk(chunk: &str, interactive: bool) {
+	let stdout = std::io::stdout();
+	let mut stdout_lock = stdout.lock();
+
+	let chunk = if interactive {
+		chunk.replace('\n', "\r\n")
+	} else {
+		chunk.to_string()
+	};
+
+	write!(stdout_lock, "{chunk}").expect("Failed to write to stdout");
+	stdout_lock.flush().expect("Failed to flush stdout");
+}
+
 async fn send_to_executor(exec_tx: &mpsc::Sender<ExecCommand>, exec_cmd: ExecCommand) {
 	// clear_last_n_lines(1);
 	if let Err(err) = exec_tx.send(exec_cmd).await {
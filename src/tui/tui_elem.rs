@@ -1,4 +1,5 @@
 use crate::dir_context::PackDir;
+use crate::run::ReviewRequest;
 use crossterm::{
 	cursor::{MoveToColumn, MoveToNextLine},
 	execute,
@@ -88,6 +89,68 @@ pub fn print_bottom_bar() {
 	let _ = execute!(stdout, Print("\n"));
 }
 
+// endregion: --- Bottom Bar
+
+// region:    --- Review
+
+/// Print the colored diff of the file change to review, and the review keys
+pub fn print_review_change(request: &ReviewRequest) {
+	let mut stdout = stdout();
+
+	let _ = execute!(
+		stdout,
+		Clear(ClearType::CurrentLine),
+		MoveToColumn(0),
+		SetAttribute(Attribute::Bold),
+		Print(format!(
+			"\n==== Review {}/{}: {}\n",
+			request.num, request.total, request.display_path
+		)),
+		SetAttribute(Attribute::Reset),
+	);
+
+	for line in request.diff.lines() {
+		let color = if line.starts_with("+++") || line.starts_with("---") {
+			Color::White
+		} else if line.starts_with('+') {
+			Color::Green
+		} else if line.starts_with('-') {
+			Color::Red
+		} else if line.starts_with("@@") {
+			Color::Cyan
+		} else {
+			Color::Reset
+		};
+		// Note: MoveToColumn(0) because the terminal is in raw mode in interactive mode
+		let _ = execute!(
+			stdout,
+			MoveToColumn(0),
+			SetForegroundColor(color),
+			Print(line),
+			ResetColor,
+			Print("\n")
+		);
+	}
+
+	let _ = execute!(stdout, MoveToColumn(0), Print("\n"));
+
+	term_key_comp(&mut stdout, "y", "Accept");
+
+	let _ = execute!(stdout, Print("  "),);
+
+	term_key_comp(&mut stdout, "n", "Reject");
+
+	let _ = execute!(stdout, Print("  "),);
+
+	term_key_comp(&mut stdout, "e", "Open in editor");
+
+	let _ = execute!(stdout, Print("\n"), MoveToColumn(0));
+}
+
+// endregion: --- Review
+
+// region:    --- Support
+
 /// Return a `[ k ] name` term component in crossterm Commans
 pub fn term_key_comp(stdout: &mut Stdout, key: &str, name: &str) {
 	let _ = execute!(
@@ -102,4 +165,4 @@ pub fn term_key_comp(stdout: &mut Stdout, key: &str, name: &str) {
 	);
 }
 
-// endregion: --- Bottom Bar
+// endregion: --- Support